@@ -0,0 +1,227 @@
+use crate::{math::mul_div, stable_swap::StableSwap, CurveType, QuoteError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapResult {
+    pub x: u64,        // X reserve after the swap
+    pub y: u64,        // Y reserve after the swap
+    pub deposit: u64,  // Input added to the pool, fee included
+    pub withdraw: u64, // Output taken out of the pool
+    pub fee: u64,      // Fee taken off the input
+}
+
+// x * y = k, with the fee taken off the input and left in the pool
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantProduct {
+    x: u64,
+    y: u64,
+    fee: u16,
+}
+
+impl ConstantProduct {
+    pub fn init(x: u64, y: u64, fee: u16) -> Result<ConstantProduct, QuoteError> {
+        if fee >= 10_000 {
+            return Err(QuoteError::InvalidFee);
+        }
+        if x == 0 || y == 0 {
+            return Err(QuoteError::ZeroBalance);
+        }
+        Ok(ConstantProduct { x, y, fee })
+    }
+
+    // Swaps `a` of X (is_x) or Y in for the other one, taking the fee on the input
+    pub fn swap(&mut self, is_x: bool, a: u64, min: u64) -> Result<SwapResult, QuoteError> {
+        let fee = (a as u128 * self.fee as u128 / 10_000) as u64;
+        let a_less_fee = a - fee;
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.x, self.y),
+            false => (self.y, self.x),
+        };
+
+        // Round the remaining output reserve up, in favour of the pool
+        let k = reserve_in as u128 * reserve_out as u128;
+        let new_out = k.div_ceil(reserve_in as u128 + a_less_fee as u128) as u64;
+        let withdraw = reserve_out - new_out;
+
+        if withdraw < min {
+            return Err(QuoteError::SlippageExceeded);
+        }
+
+        let reserve_in = reserve_in.checked_add(a).ok_or(QuoteError::Overflow)?;
+        (self.x, self.y) = match is_x {
+            true => (reserve_in, new_out),
+            false => (new_out, reserve_in),
+        };
+
+        Ok(SwapResult {
+            x: self.x,
+            y: self.y,
+            deposit: a,
+            withdraw,
+            fee,
+        })
+    }
+
+    // Smallest input of X (is_x) or Y, fee included, for which `swap` pays out at least
+    // `amount_out` of the other one
+    pub fn amount_in_for_exact_out(&self, is_x: bool, amount_out: u64) -> Result<u64, QuoteError> {
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.x, self.y),
+            false => (self.y, self.x),
+        };
+        if amount_out >= reserve_out {
+            return Err(QuoteError::InsufficientBalance);
+        }
+
+        let k = reserve_in as u128 * reserve_out as u128;
+        let new_reserve_out = (reserve_out - amount_out) as u128;
+
+        // Input that has to reach the curve once the swap fee is taken
+        let amount_in_less_fee = k
+            .div_ceil(new_reserve_out)
+            .checked_sub(reserve_in as u128)
+            .ok_or(QuoteError::Underflow)?;
+
+        // Gross that up by the swap fee, rounding in favour of the pool
+        let amount_in = amount_in_less_fee
+            .checked_mul(10_000)
+            .ok_or(QuoteError::Overflow)?
+            .div_ceil(10_000 - self.fee as u128);
+
+        u64::try_from(amount_in).map_err(|_| QuoteError::Overflow)
+    }
+
+    // Output for `amount` of X (is_x) or Y at the current marginal price, i.e. with no
+    // slippage and no fee
+    pub fn spot_amount_out(&self, is_x: bool, amount: u64) -> Result<u64, QuoteError> {
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.x, self.y),
+            false => (self.y, self.x),
+        };
+        mul_div(
+            amount as u128,
+            reserve_out as u128,
+            reserve_in as u128,
+            false,
+        )
+        .and_then(|amount_out| u64::try_from(amount_out).ok())
+        .ok_or(QuoteError::Overflow)
+    }
+}
+
+// Pool reserves priced by whichever curve the pool was created with. Plain data, so it
+// can be copied to price a trade without touching the original
+#[derive(Debug, Clone, Copy)]
+pub struct Curve {
+    curve_type: CurveType,
+    x: u64,
+    y: u64,
+    fee: u16,
+    amp: u64,
+}
+
+impl Curve {
+    pub fn init(
+        curve_type: CurveType,
+        x: u64,
+        y: u64,
+        fee: u16,
+        amp: u64,
+    ) -> Result<Curve, QuoteError> {
+        let curve = Curve {
+            curve_type,
+            x,
+            y,
+            fee,
+            amp,
+        };
+        // Let the underlying curve validate its inputs
+        match curve_type {
+            CurveType::ConstantProduct => {
+                ConstantProduct::init(x, y, fee)?;
+            }
+            CurveType::StableSwap => {
+                StableSwap::init(x, y, amp, fee)?;
+            }
+        }
+        Ok(curve)
+    }
+
+    pub fn reserves(&self) -> (u64, u64) {
+        (self.x, self.y)
+    }
+
+    // Swaps `a` of X (is_x) or Y in. The whole input, fee included, stays in the pool
+    pub fn swap(&mut self, is_x: bool, a: u64, min: u64) -> Result<SwapResult, QuoteError> {
+        let result = match self.curve_type {
+            CurveType::ConstantProduct => {
+                ConstantProduct::init(self.x, self.y, self.fee)?.swap(is_x, a, min)?
+            }
+            CurveType::StableSwap => {
+                StableSwap::init(self.x, self.y, self.amp, self.fee)?.swap(is_x, a, min)?
+            }
+        };
+        (self.x, self.y) = (result.x, result.y);
+
+        Ok(result)
+    }
+
+    // Smallest input (after transfer fees, before the swap fee) that buys `amount_out`
+    // of the other token when swapping in X (`is_x`) or Y
+    pub fn amount_in_for_exact_out(&self, is_x: bool, amount_out: u64) -> Result<u64, QuoteError> {
+        match self.curve_type {
+            CurveType::ConstantProduct => ConstantProduct::init(self.x, self.y, self.fee)?
+                .amount_in_for_exact_out(is_x, amount_out),
+            CurveType::StableSwap => StableSwap::init(self.x, self.y, self.amp, self.fee)?
+                .amount_in_for_exact_out(is_x, amount_out),
+        }
+    }
+
+    // Output for `amount` of X (is_x) or Y at the current marginal price, before slippage
+    // and fees
+    pub fn spot_amount_out(&self, is_x: bool, amount: u64) -> Result<u64, QuoteError> {
+        match self.curve_type {
+            CurveType::ConstantProduct => {
+                ConstantProduct::init(self.x, self.y, self.fee)?.spot_amount_out(is_x, amount)
+            }
+            CurveType::StableSwap => {
+                StableSwap::init(self.x, self.y, self.amp, self.fee)?.spot_amount_out(is_x, amount)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_product_never_decreases_k() {
+        for (x, y, fee) in [
+            (1_000_000_000u64, 1_000_000_000u64, 30u16),
+            (1_000_000_000, 250_000_000, 0),
+            (7_000_000, 3_000_000_000_000, 5),
+        ] {
+            for (is_x, a) in [(true, x / 10), (false, y / 3)] {
+                let result = ConstantProduct::init(x, y, fee)
+                    .unwrap()
+                    .swap(is_x, a, 0)
+                    .unwrap();
+                assert!(result.x as u128 * result.y as u128 >= x as u128 * y as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn constant_product_exact_out_buys_the_output() {
+        let cp = ConstantProduct::init(1_000_000_000, 250_000_000, 30).unwrap();
+        for amount_out in [1, 1_000, 2_500_000, 125_000_000] {
+            let amount_in = cp.amount_in_for_exact_out(true, amount_out).unwrap();
+            assert!({ cp }.swap(true, amount_in, 0).unwrap().withdraw >= amount_out);
+        }
+        assert_eq!(
+            cp.amount_in_for_exact_out(true, 250_000_000),
+            Err(QuoteError::InsufficientBalance)
+        );
+    }
+}
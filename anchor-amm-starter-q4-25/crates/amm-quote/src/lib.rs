@@ -0,0 +1,715 @@
+#![cfg_attr(not(test), no_std)]
+
+// Quoting math of the AMM program, shared by its swap/deposit/withdraw instructions and
+// off-chain clients. No dependencies and no_std, so clients can use it without pulling
+// in Anchor or the Solana SDK: plain integers in and out, with its own error and enums
+// that the program maps to QuoteError and its account types. Amounts are what reaches or
+// leaves the vaults: Token-2022 transfer fees are left to the caller
+
+mod curve;
+mod math;
+mod stable_swap;
+
+pub use curve::{ConstantProduct, Curve, SwapResult};
+pub use math::mul_div;
+pub use stable_swap::{compute_d, StableSwap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteError {
+    Overflow,
+    Underflow,
+    InvalidFee,
+    InvalidAmount,
+    InvalidPrecision,
+    InsufficientBalance,
+    ZeroBalance,
+    SlippageExceeded,
+    NoLiquidityInPool,
+    LiquidityLessThanMinimum,
+    CurveError,
+}
+
+// Curve a pool prices swaps with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveType {
+    ConstantProduct,
+    StableSwap,
+}
+
+// Side of a swap the fee is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSide {
+    Input,
+    Output,
+}
+
+// LP tokens minted on the first deposit and locked forever
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+// Finest decimal precision of the LP share math, keeping 10^precision well inside a u128
+pub const MAX_PRECISION: u8 = 18;
+
+// LP token decimals for a pool between mints with the given decimals. The first deposit
+// mints sqrt(x * y) LP tokens, so the mean of the two keeps one LP token worth about one
+// whole token of each side
+pub fn lp_decimals(decimals_x: u8, decimals_y: u8) -> u8 {
+    (decimals_x as u16 + decimals_y as u16).div_ceil(2) as u8
+}
+
+// Decimal precision of the LP share math for the given mint decimals: enough digits for
+// the share of a billion whole tokens of the finer mint to come out within a base unit
+pub fn share_precision(decimals_x: u8, decimals_y: u8) -> u8 {
+    decimals_x
+        .max(decimals_y)
+        .saturating_add(9)
+        .min(MAX_PRECISION)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolState {
+    pub curve_type: CurveType, // Curve the pool was created with
+    pub reserve_x: u64,        // LP reserves of X, i.e. Config::reserves of the vault
+    pub reserve_y: u64,        // LP reserves of Y
+    pub lp_supply: u64,        // LP token supply, including the locked minimum liquidity
+    pub fee: u16,              // Swap fee in effect, Config::swap_fee
+    pub protocol_fee: u16,     // Protocol share of the swap fee, in basis points
+    pub amp: u64,              // StableSwap amplification in effect, Config::amp
+    pub precision: u8,         // Decimal precision of the LP share math, Config::precision
+    pub fee_side: FeeSide,     // Side of the swap the fee is taken from, Config::fee_side
+    pub referral_fee: u16,     // Referrer share of the swap fee in bps, 0 without a referrer
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub amount_in: u64,    // Input reaching the pool, fee included
+    pub amount_out: u64,   // Output leaving the pool
+    pub fee: u64,          // Total swap fee, in the token PoolState::fee_in_x picks
+    pub lp_fee: u64,       // Part of the fee left to the LPs
+    pub protocol_fee: u64, // Part of the fee set aside for the protocol
+    pub referral_fee: u64, // Part of the fee paid out to the referrer
+    pub price_impact: u64, // Shortfall against the marginal price, excluding the fee, in bps
+    pub reserve_x: u64,    // LP reserves of X after the swap
+    pub reserve_y: u64,    // LP reserves of Y after the swap
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositQuote {
+    pub amount_x: u64,  // X the vault has to receive
+    pub amount_y: u64,  // Y the vault has to receive
+    pub lp_amount: u64, // LP tokens minted to the depositor
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawQuote {
+    pub amount_x: u64,  // X leaving the vault
+    pub amount_y: u64,  // Y leaving the vault
+    pub lp_amount: u64, // LP tokens burned
+}
+
+impl PoolState {
+    // Curve holding the pool's reserves, e.g. to price a trade step by step
+    pub fn curve(&self) -> Result<Curve, QuoteError> {
+        Curve::init(
+            self.curve_type,
+            self.reserve_x,
+            self.reserve_y,
+            self.fee,
+            self.amp,
+        )
+        .map_err(|_| QuoteError::CurveError)
+    }
+
+    // If a swap of X (is_x) or Y pays its fee in token X
+    pub fn fee_in_x(&self, is_x: bool) -> bool {
+        match self.fee_side {
+            FeeSide::Input => is_x,
+            FeeSide::Output => !is_x,
+        }
+    }
+}
+
+// Part of a swap fee going to a share of `share` basis points of it, e.g. the protocol's
+pub fn fee_share(swap_fee: u64, share: u16) -> Result<u64, QuoteError> {
+    let cut = (swap_fee as u128)
+        .checked_mul(share as u128)
+        .ok_or(QuoteError::Overflow)?
+        / 10_000;
+    Ok(cut as u64)
+}
+
+// Swap of `amount_in` X (is_x) or Y for the other token. The fee comes off the input
+// before it is swapped or off the output afterwards, depending on the pool's fee side
+pub fn quote_swap(pool: &PoolState, is_x: bool, amount_in: u64) -> Result<SwapQuote, QuoteError> {
+    if pool.reserve_x == 0 || pool.reserve_y == 0 {
+        return Err(QuoteError::NoLiquidityInPool);
+    }
+    if amount_in == 0 {
+        return Err(QuoteError::InvalidAmount);
+    }
+
+    let before = pool.curve()?;
+
+    // Output before any fee on it, the fee and what the curve ends up holding
+    let (gross_out, fee, curve) = match pool.fee_side {
+        FeeSide::Input => {
+            let mut curve = before;
+            let result = curve
+                .swap(is_x, amount_in, 0)
+                .map_err(|_| QuoteError::CurveError)?;
+            (result.withdraw, result.fee, curve)
+        }
+        FeeSide::Output => {
+            let mut curve = PoolState { fee: 0, ..*pool }.curve()?;
+            let result = curve
+                .swap(is_x, amount_in, 0)
+                .map_err(|_| QuoteError::CurveError)?;
+            let fee = fee_share(result.withdraw, pool.fee)?;
+            (result.withdraw, fee, curve)
+        }
+    };
+    let amount_out = match pool.fee_side {
+        FeeSide::Input => gross_out,
+        FeeSide::Output => gross_out - fee,
+    };
+
+    let protocol_fee = fee_share(fee, pool.protocol_fee)?;
+    let referral_fee = fee_share(fee, pool.referral_fee)?;
+    let lp_fee = fee
+        .checked_sub(protocol_fee + referral_fee)
+        .ok_or(QuoteError::InvalidFee)?;
+
+    // Compare against the marginal price on what was swapped, excluding the fee
+    let swapped = match pool.fee_side {
+        FeeSide::Input => amount_in - fee,
+        FeeSide::Output => amount_in,
+    };
+    let spot_out = before.spot_amount_out(is_x, swapped)?;
+    let price_impact = match spot_out {
+        0 => 0,
+        _ => (spot_out.saturating_sub(gross_out) as u128 * 10_000 / spot_out as u128) as u64,
+    };
+
+    // An output side fee stays in the pool. The protocol cut stays in the vault but
+    // leaves the LP reserves, the referral fee leaves the vault altogether
+    let (x, y) = match (pool.fee_side, is_x, curve.reserves()) {
+        (FeeSide::Input, _, reserves) => reserves,
+        (FeeSide::Output, true, (x, y)) => (x, y + fee),
+        (FeeSide::Output, false, (x, y)) => (x + fee, y),
+    };
+    let cut = protocol_fee + referral_fee;
+    let (reserve_x, reserve_y) = match pool.fee_in_x(is_x) {
+        true => (x - cut, y),
+        false => (x, y - cut),
+    };
+
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee,
+        lp_fee,
+        protocol_fee,
+        referral_fee,
+        price_impact,
+        reserve_x,
+        reserve_y,
+    })
+}
+
+// Swap of the smallest amount of X (is_x) or Y that pays out at least `amount_out` of the
+// other token
+pub fn quote_swap_exact_out(
+    pool: &PoolState,
+    is_x: bool,
+    amount_out: u64,
+) -> Result<SwapQuote, QuoteError> {
+    if pool.reserve_x == 0 || pool.reserve_y == 0 {
+        return Err(QuoteError::NoLiquidityInPool);
+    }
+
+    // With the fee on the output, the curve has to pay out enough to cover it as well
+    let amount_in = match pool.fee_side {
+        FeeSide::Input => pool.curve()?.amount_in_for_exact_out(is_x, amount_out),
+        FeeSide::Output => {
+            let gross_out = mul_div(amount_out as u128, 10_000, 10_000 - pool.fee as u128, true)
+                .and_then(|gross_out| u64::try_from(gross_out).ok())
+                .ok_or(QuoteError::Overflow)?;
+            PoolState { fee: 0, ..*pool }
+                .curve()?
+                .amount_in_for_exact_out(is_x, gross_out)
+        }
+    }?;
+    let quote = quote_swap(pool, is_x, amount_in)?;
+    if quote.amount_out < amount_out {
+        return Err(QuoteError::SlippageExceeded);
+    }
+
+    Ok(quote)
+}
+
+// Deposit minting `lp_amount` LP tokens, paid in the pool ratio
+pub fn quote_deposit(pool: &PoolState, lp_amount: u64) -> Result<DepositQuote, QuoteError> {
+    if pool.lp_supply == 0 {
+        return Err(QuoteError::NoLiquidityInPool);
+    }
+    if lp_amount == 0 {
+        return Err(QuoteError::InvalidAmount);
+    }
+
+    let amount_x = lp_share(
+        pool.reserve_x,
+        lp_amount,
+        pool.lp_supply,
+        pool.precision,
+        true,
+    )?;
+    let amount_y = lp_share(
+        pool.reserve_y,
+        lp_amount,
+        pool.lp_supply,
+        pool.precision,
+        true,
+    )?;
+    if amount_x == 0 || amount_y == 0 {
+        return Err(QuoteError::LiquidityLessThanMinimum);
+    }
+
+    Ok(DepositQuote {
+        amount_x,
+        amount_y,
+        lp_amount,
+    })
+}
+
+// The first deposit into an empty pool sets the price. LP is the geometric mean of the
+// deposit (the invariant D for StableSwap, which is x + y at the peg), of which
+// MINIMUM_LIQUIDITY is locked forever so the LP share price can't be inflated from a
+// near-zero supply
+pub fn quote_initial_deposit(
+    pool: &PoolState,
+    amount_x: u64,
+    amount_y: u64,
+) -> Result<DepositQuote, QuoteError> {
+    if amount_x == 0 || amount_y == 0 {
+        return Err(QuoteError::LiquidityLessThanMinimum);
+    }
+
+    let lp = match pool.curve_type {
+        CurveType::ConstantProduct => initial_lp(amount_x, amount_y),
+        CurveType::StableSwap => {
+            let d = compute_d(amount_x, amount_y, pool.amp)?;
+            u64::try_from(d).map_err(|_| QuoteError::Overflow)?
+        }
+    };
+    let lp_amount = lp
+        .checked_sub(MINIMUM_LIQUIDITY)
+        .filter(|lp| *lp != 0)
+        .ok_or(QuoteError::LiquidityLessThanMinimum)?;
+
+    Ok(DepositQuote {
+        amount_x,
+        amount_y,
+        lp_amount,
+    })
+}
+
+// Withdrawal burning `lp_amount` LP tokens, paid out in the pool ratio
+pub fn quote_withdraw(pool: &PoolState, lp_amount: u64) -> Result<WithdrawQuote, QuoteError> {
+    if lp_amount == 0 {
+        return Err(QuoteError::InvalidAmount);
+    }
+    if lp_amount > pool.lp_supply {
+        return Err(QuoteError::InsufficientBalance);
+    }
+
+    let amount_x = lp_share(
+        pool.reserve_x,
+        lp_amount,
+        pool.lp_supply,
+        pool.precision,
+        false,
+    )?;
+    let amount_y = lp_share(
+        pool.reserve_y,
+        lp_amount,
+        pool.lp_supply,
+        pool.precision,
+        false,
+    )?;
+
+    Ok(WithdrawQuote {
+        amount_x,
+        amount_y,
+        lp_amount,
+    })
+}
+
+// `reserve` times lp_amount / lp_supply, with the ratio taken to `precision` decimals.
+// Both steps round up when the user pays in (round_up) and down when they take out, so
+// rounding never moves value from the other LPs to the user
+fn lp_share(
+    reserve: u64,
+    lp_amount: u64,
+    lp_supply: u64,
+    precision: u8,
+    round_up: bool,
+) -> Result<u64, QuoteError> {
+    if precision > MAX_PRECISION {
+        return Err(QuoteError::InvalidPrecision);
+    }
+    let scale = 10u128.pow(precision as u32);
+
+    let ratio = mul_div(lp_amount as u128, scale, lp_supply as u128, round_up)
+        .ok_or(QuoteError::Overflow)?;
+    mul_div(reserve as u128, ratio, scale, round_up)
+        .and_then(|share| u64::try_from(share).ok())
+        .ok_or(QuoteError::Overflow)
+}
+
+// Total LP for the first deposit into an empty constant product pool: floor(sqrt(x * y))
+pub fn initial_lp(x: u64, y: u64) -> u64 {
+    // Can't overflow, and the sqrt of a u128 always fits in a u64
+    (x as u128 * y as u128).isqrt() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const POOLS: [(u64, u64); 4] = [
+        (1_000_000_000, 1_000_000_000),
+        (1_000_000_000, 250_000_000),
+        (7_000_000, 3_000_000_000_000),
+        (123_456_789, 987_654_321),
+    ];
+
+    fn pool(curve_type: CurveType, x: u64, y: u64) -> PoolState {
+        PoolState {
+            curve_type,
+            reserve_x: x,
+            reserve_y: y,
+            lp_supply: initial_lp(x, y),
+            fee: 30,
+            protocol_fee: 1_000,
+            amp: 100,
+            precision: share_precision(6, 6),
+            fee_side: FeeSide::Input,
+            referral_fee: 0,
+        }
+    }
+
+    #[test]
+    fn swap_matches_the_curves() {
+        for (x, y) in POOLS {
+            for (is_x, amount) in [(true, x / 10), (false, y / 1_000)] {
+                let quote =
+                    quote_swap(&pool(CurveType::ConstantProduct, x, y), is_x, amount).unwrap();
+                let result = ConstantProduct::init(x, y, 30)
+                    .unwrap()
+                    .swap(is_x, amount, 0)
+                    .unwrap();
+                assert_eq!((quote.amount_out, quote.fee), (result.withdraw, result.fee));
+
+                let quote = quote_swap(&pool(CurveType::StableSwap, x, y), is_x, amount).unwrap();
+                let result = StableSwap::init(x, y, 100, 30)
+                    .unwrap()
+                    .swap(is_x, amount, 0)
+                    .unwrap();
+                assert_eq!((quote.amount_out, quote.fee), (result.withdraw, result.fee));
+
+                // With the fee on the output the curves swap the whole input fee-free
+                let gross = [
+                    ConstantProduct::init(x, y, 0)
+                        .unwrap()
+                        .swap(is_x, amount, 0)
+                        .unwrap()
+                        .withdraw,
+                    StableSwap::init(x, y, 100, 0)
+                        .unwrap()
+                        .swap(is_x, amount, 0)
+                        .unwrap()
+                        .withdraw,
+                ];
+                for (curve_type, gross) in [CurveType::ConstantProduct, CurveType::StableSwap]
+                    .into_iter()
+                    .zip(gross)
+                {
+                    let pool = PoolState {
+                        fee_side: FeeSide::Output,
+                        ..pool(curve_type, x, y)
+                    };
+                    let quote = quote_swap(&pool, is_x, amount).unwrap();
+                    assert_eq!(quote.amount_out + quote.fee, gross);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swap_fee_breakdown_adds_up() {
+        for (x, y) in POOLS {
+            let quote = quote_swap(&pool(CurveType::ConstantProduct, x, y), true, x / 10).unwrap();
+            assert_eq!(quote.fee, x / 10 * 30 / 10_000);
+            assert_eq!(quote.protocol_fee, quote.fee / 10);
+            assert_eq!(quote.lp_fee + quote.protocol_fee, quote.fee);
+            // Everything but the protocol cut ends up in the LP reserves
+            assert_eq!(quote.reserve_x, x + quote.amount_in - quote.protocol_fee);
+            assert_eq!(quote.reserve_y, y - quote.amount_out);
+        }
+    }
+
+    #[test]
+    fn referral_fee_leaves_the_reserves() {
+        for (x, y) in POOLS {
+            let plain = pool(CurveType::ConstantProduct, x, y);
+            let pool = PoolState {
+                referral_fee: 2_000,
+                ..plain
+            };
+            let quote = quote_swap(&pool, true, x / 10).unwrap();
+            assert_eq!(quote.referral_fee, quote.fee / 5);
+            assert_eq!(
+                quote.lp_fee + quote.protocol_fee + quote.referral_fee,
+                quote.fee
+            );
+            assert_eq!(
+                quote.reserve_x,
+                x + quote.amount_in - quote.protocol_fee - quote.referral_fee
+            );
+            // The trader gets the same output with or without a referrer
+            let plain = quote_swap(&plain, true, x / 10).unwrap();
+            assert_eq!(quote.amount_out, plain.amount_out);
+        }
+    }
+
+    #[test]
+    fn output_fee_is_taken_from_the_output() {
+        for (x, y) in POOLS {
+            for curve_type in [CurveType::ConstantProduct, CurveType::StableSwap] {
+                let pool = PoolState {
+                    fee_side: FeeSide::Output,
+                    ..pool(curve_type, x, y)
+                };
+                let gross = quote_swap(&PoolState { fee: 0, ..pool }, true, x / 10).unwrap();
+                let quote = quote_swap(&pool, true, x / 10).unwrap();
+                assert_eq!(quote.fee, gross.amount_out * 30 / 10_000);
+                assert_eq!(quote.amount_out, gross.amount_out - quote.fee);
+                assert_eq!(quote.protocol_fee, quote.fee / 10);
+                // The whole input is swapped and the LP fee stays in the output reserve
+                assert_eq!(quote.reserve_x, x + quote.amount_in);
+                assert_eq!(quote.reserve_y, y - quote.amount_out - quote.protocol_fee);
+                assert!(!pool.fee_in_x(true) && pool.fee_in_x(false));
+
+                let exact = quote_swap_exact_out(&pool, true, y / 100).unwrap();
+                assert!(exact.amount_out >= y / 100);
+            }
+        }
+    }
+
+    #[test]
+    fn price_impact_grows_with_size() {
+        for curve_type in [CurveType::ConstantProduct, CurveType::StableSwap] {
+            let pool = pool(curve_type, 1_000_000_000, 1_000_000_000);
+            let small = quote_swap(&pool, true, 1_000_000).unwrap().price_impact;
+            let large = quote_swap(&pool, true, 100_000_000).unwrap().price_impact;
+            assert!(small <= 10, "{small}");
+            assert!(large > small);
+        }
+
+        // x / (x + a) for a constant product pool: ~9.1% for a 10% trade
+        let pool = pool(CurveType::ConstantProduct, 1_000_000_000, 1_000_000_000);
+        let quote = quote_swap(&pool, true, 100_000_000).unwrap();
+        assert!(
+            quote.price_impact.abs_diff(907) <= 1,
+            "{}",
+            quote.price_impact
+        );
+        // StableSwap stays close to the peg
+        let pool = PoolState {
+            curve_type: CurveType::StableSwap,
+            ..pool
+        };
+        assert!(quote_swap(&pool, true, 100_000_000).unwrap().price_impact < 50);
+    }
+
+    #[test]
+    fn stable_spot_price_matches_small_trades() {
+        for (x, y) in POOLS {
+            let stable = StableSwap::init(x, y, 100, 0).unwrap();
+            let amount = x / 1_000_000;
+            let spot = stable.spot_amount_out(true, amount).unwrap();
+            let traded = { stable }.swap(true, amount, 0).unwrap().withdraw;
+            assert!(spot.abs_diff(traded) <= spot / 1_000 + 2, "{x} {y}");
+        }
+    }
+
+    #[test]
+    fn exact_out_buys_at_least_the_output() {
+        for (x, y) in POOLS {
+            for curve_type in [CurveType::ConstantProduct, CurveType::StableSwap] {
+                let pool = pool(curve_type, x, y);
+                let quote = quote_swap_exact_out(&pool, true, y / 100).unwrap();
+                assert!(quote.amount_out >= y / 100);
+                assert_eq!(quote, quote_swap(&pool, true, quote.amount_in).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn decimals_follow_the_mints() {
+        assert_eq!((lp_decimals(6, 6), share_precision(6, 6)), (6, 15));
+        assert_eq!((lp_decimals(9, 0), share_precision(9, 0)), (5, 18));
+        assert_eq!((lp_decimals(0, 0), share_precision(0, 0)), (0, 9));
+        assert_eq!(
+            (lp_decimals(255, 255), share_precision(255, 0)),
+            (255, MAX_PRECISION)
+        );
+    }
+
+    #[test]
+    fn withdraw_is_bounded_by_the_supply() {
+        let pool = pool(CurveType::ConstantProduct, 1_000, 1_000);
+        assert!(matches!(
+            quote_withdraw(&pool, pool.lp_supply + 1),
+            Err(QuoteError::InsufficientBalance)
+        ));
+        assert!(matches!(
+            quote_withdraw(
+                &PoolState {
+                    precision: MAX_PRECISION + 1,
+                    ..pool
+                },
+                1
+            ),
+            Err(QuoteError::InvalidPrecision)
+        ));
+    }
+
+    // Reserves and LP supply of a pool between mints of any decimals, with an LP amount
+    // of up to twice the supply
+    fn any_pool() -> impl Strategy<Value = (PoolState, u64)> {
+        (1..u64::MAX, 1..u64::MAX, 1..u64::MAX, 0..=9u8, 0..=9u8).prop_flat_map(
+            |(x, y, supply, decimals_x, decimals_y)| {
+                let pool = PoolState {
+                    lp_supply: supply,
+                    precision: share_precision(decimals_x, decimals_y),
+                    ..pool(CurveType::ConstantProduct, x, y)
+                };
+                (Just(pool), 1..=supply.saturating_mul(2))
+            },
+        )
+    }
+
+    // Exact share of `reserve` for `lp_amount` out of `lp_supply`, rounded down
+    fn exact_share(reserve: u64, lp_amount: u64, lp_supply: u64) -> u128 {
+        reserve as u128 * lp_amount as u128 / lp_supply as u128
+    }
+
+    proptest! {
+        // Depositors pay at least their exact share, so existing LPs are never diluted,
+        // and at most a base unit plus reserve / 10^precision more
+        #[test]
+        fn deposit_never_underpays((pool, lp) in any_pool()) {
+            let scale = 10u128.pow(pool.precision as u32);
+            if let Ok(quote) = quote_deposit(&pool, lp) {
+                for (paid, reserve) in [(quote.amount_x, pool.reserve_x), (quote.amount_y, pool.reserve_y)] {
+                    let paid = paid as u128;
+                    prop_assert!(paid * pool.lp_supply as u128 >= reserve as u128 * lp as u128);
+                    prop_assert!(paid <= exact_share(reserve, lp, pool.lp_supply) + reserve as u128 / scale + 2);
+                }
+            }
+        }
+
+        // Withdrawals pay out at most the exact share, so the remaining LPs never lose,
+        // and at most a base unit plus reserve / 10^precision less
+        #[test]
+        fn withdraw_never_overpays((pool, lp) in any_pool()) {
+            let scale = 10u128.pow(pool.precision as u32);
+            let lp = lp.min(pool.lp_supply);
+            let quote = quote_withdraw(&pool, lp).unwrap();
+            for (paid, reserve) in [(quote.amount_x, pool.reserve_x), (quote.amount_y, pool.reserve_y)] {
+                let paid = paid as u128;
+                let exact = exact_share(reserve, lp, pool.lp_supply);
+                prop_assert!(paid <= exact);
+                prop_assert!(paid + reserve as u128 / scale + 2 >= exact);
+            }
+        }
+
+        // Depositing and withdrawing the same LP straight away never comes out ahead
+        #[test]
+        fn deposit_withdraw_round_trip_never_profits((pool, lp) in any_pool()) {
+            if let Ok(deposit) = quote_deposit(&pool, lp) {
+                let (Some(reserve_x), Some(reserve_y), Some(lp_supply)) = (
+                    pool.reserve_x.checked_add(deposit.amount_x),
+                    pool.reserve_y.checked_add(deposit.amount_y),
+                    pool.lp_supply.checked_add(lp),
+                ) else {
+                    return Ok(());
+                };
+                let after = PoolState { reserve_x, reserve_y, lp_supply, ..pool };
+                let withdraw = quote_withdraw(&after, lp).unwrap();
+                prop_assert!(withdraw.amount_x <= deposit.amount_x);
+                prop_assert!(withdraw.amount_y <= deposit.amount_y);
+            }
+        }
+    }
+
+    #[test]
+    fn initial_lp_is_geometric_mean() {
+        assert_eq!(initial_lp(1_000_000_000, 1_000_000_000), 1_000_000_000);
+        assert_eq!(initial_lp(4, 9), 6);
+        assert_eq!(initial_lp(2, 3), 2);
+        assert_eq!(initial_lp(u64::MAX, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn initial_deposit_sets_the_curve_price() {
+        for (x, y) in [
+            (1_000_000_000u64, 1_000_000_000u64),
+            (1_000_000_000, 250_000_000),
+            (7_000_000, 3_000_000_000_000),
+            (123_456_789, 987_654),
+        ] {
+            let lp = initial_lp(x, y);
+            assert!(lp > MINIMUM_LIQUIDITY);
+
+            // A follow-up deposit of the same size has to pay the same X/Y ratio as the
+            // initial deposit
+            let pool = PoolState {
+                lp_supply: lp,
+                ..pool(CurveType::ConstantProduct, x, y)
+            };
+            let amounts = quote_deposit(&pool, lp).unwrap();
+            let expected = x as u128 * 1_000_000 / y as u128;
+            let actual = amounts.amount_x as u128 * 1_000_000 / amounts.amount_y as u128;
+            assert!(expected.abs_diff(actual) * 1_000 <= expected, "{x} {y}");
+
+            // and the curve prices a small swap at the initial ratio, net of the fee
+            let mut curve = ConstantProduct::init(x, y, 0).unwrap();
+            let out = curve.swap(true, x / 1_000, 0).unwrap().withdraw;
+            assert!(
+                out < y / 1_000 && out * 1_002 >= y / 1_000 * 1_000,
+                "{x} {y}"
+            );
+        }
+    }
+
+    #[test]
+    fn minimum_liquidity_is_enforced() {
+        let pool = pool(CurveType::ConstantProduct, 0, 0);
+        assert!(matches!(
+            quote_initial_deposit(&pool, 1_000, 1_000),
+            Err(QuoteError::LiquidityLessThanMinimum)
+        ));
+        assert!(matches!(
+            quote_initial_deposit(&pool, 1_000, 1_001),
+            Err(QuoteError::LiquidityLessThanMinimum)
+        ));
+        assert_eq!(
+            quote_initial_deposit(&pool, 1_001, 1_001)
+                .unwrap()
+                .lp_amount,
+            1
+        );
+    }
+}
@@ -0,0 +1,70 @@
+const LOW_64: u128 = (1 << 64) - 1;
+
+// Full 256-bit product of two u128s as (high, low)
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    let (a_hi, a_lo) = (a >> 64, a & LOW_64);
+    let (b_hi, b_lo) = (b >> 64, b & LOW_64);
+
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+
+    let mid = (lo_lo >> 64) + (hi_lo & LOW_64) + (lo_hi & LOW_64);
+    let low = (lo_lo & LOW_64) | (mid << 64);
+    let high = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (mid >> 64);
+    (high, low)
+}
+
+// a * b / denominator with a 256-bit intermediate, rounded down or up. None if the
+// denominator is zero or the result doesn't fit in a u128
+pub fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> Option<u128> {
+    if denominator == 0 {
+        return None;
+    }
+    let (high, low) = full_mul(a, b);
+    if high >= denominator {
+        return None;
+    }
+
+    // Long division of (high, low) by the denominator, one bit at a time
+    let (mut quotient, mut remainder) = (0u128, high);
+    for i in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> i) & 1);
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1 << i;
+        }
+    }
+
+    match round_up && remainder != 0 {
+        true => quotient.checked_add(1),
+        false => Some(quotient),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_matches_u128_math() {
+        for (a, b, d) in [
+            (7u128, 9u128, 4u128),
+            (u64::MAX as u128, 3, 2),
+            (1 << 100, 1 << 27, 1 << 64),
+        ] {
+            assert_eq!(mul_div(a, b, d, false), Some(a * b / d));
+            assert_eq!(mul_div(a, b, d, true), Some((a * b).div_ceil(d)));
+        }
+        // Intermediate above 2^128
+        assert_eq!(
+            mul_div(u128::MAX, u128::MAX, u128::MAX, false),
+            Some(u128::MAX)
+        );
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90, false), Some(1 << 110));
+        assert_eq!(mul_div(u128::MAX, 2, 1, false), None);
+        assert_eq!(mul_div(1, 1, 0, false), None);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{curve::SwapResult, math::mul_div, QuoteError};
+
+// Curve-style StableSwap invariant for two tokens with amplification A:
+//   4A(x + y) + D = 4AD + D^3 / (4xy)
+// Close to x + y = D around the peg and close to xy = (D/2)^2 far away from it.
+// Mirrors the ConstantProduct API so Curve can use either one
+
+// Newton's method converges in a handful of rounds for any sane pool; this only guards
+// against pathological inputs
+const MAX_ITERATIONS: usize = 255;
+
+#[derive(Debug, Clone, Copy)]
+pub struct StableSwap {
+    x: u64,
+    y: u64,
+    amp: u64,
+    fee: u16,
+}
+
+impl StableSwap {
+    pub fn init(x: u64, y: u64, amp: u64, fee: u16) -> Result<StableSwap, QuoteError> {
+        if fee >= 10_000 {
+            return Err(QuoteError::InvalidFee);
+        }
+        if x == 0 || y == 0 || amp == 0 {
+            return Err(QuoteError::ZeroBalance);
+        }
+        Ok(StableSwap { x, y, amp, fee })
+    }
+
+    // Swaps `a` of X (is_x) or Y in for the other one, taking the fee on the input
+    pub fn swap(&mut self, is_x: bool, a: u64, min: u64) -> Result<SwapResult, QuoteError> {
+        let fee = (a as u128 * self.fee as u128 / 10_000) as u64;
+        let a_less_fee = a - fee;
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.x, self.y),
+            false => (self.y, self.x),
+        };
+
+        let d = compute_d(self.x, self.y, self.amp)?;
+        let new_in = (reserve_in as u128)
+            .checked_add(a_less_fee as u128)
+            .ok_or(QuoteError::Overflow)?;
+        // Round the remaining output reserve up, in favour of the pool
+        let new_out = compute_y(new_in, d, self.amp)?
+            .checked_add(1)
+            .ok_or(QuoteError::Overflow)?;
+        let withdraw = (reserve_out as u128)
+            .checked_sub(new_out)
+            .ok_or(QuoteError::InsufficientBalance)? as u64;
+
+        if withdraw < min {
+            return Err(QuoteError::SlippageExceeded);
+        }
+
+        let reserve_in = reserve_in.checked_add(a).ok_or(QuoteError::Overflow)?;
+        let reserve_out = reserve_out - withdraw;
+        (self.x, self.y) = match is_x {
+            true => (reserve_in, reserve_out),
+            false => (reserve_out, reserve_in),
+        };
+
+        Ok(SwapResult {
+            x: self.x,
+            y: self.y,
+            deposit: a,
+            withdraw,
+            fee,
+        })
+    }
+
+    // Smallest input of X (is_x) or Y, fee included, for which `swap` pays out at least
+    // `amount_out` of the other one
+    pub fn amount_in_for_exact_out(&self, is_x: bool, amount_out: u64) -> Result<u64, QuoteError> {
+        let (reserve_in, reserve_out) = match is_x {
+            true => (self.x, self.y),
+            false => (self.y, self.x),
+        };
+        if amount_out >= reserve_out {
+            return Err(QuoteError::InsufficientBalance);
+        }
+
+        let d = compute_d(self.x, self.y, self.amp)?;
+        // Mirror the rounding in `swap`, plus one unit for Newton's tolerance
+        let new_out = (reserve_out - amount_out - 1) as u128;
+        let new_in = compute_y(new_out, d, self.amp)?
+            .checked_add(1)
+            .ok_or(QuoteError::Overflow)?;
+        let amount_in_less_fee = new_in
+            .checked_sub(reserve_in as u128)
+            .ok_or(QuoteError::Underflow)?;
+
+        let amount_in = amount_in_less_fee
+            .checked_mul(10_000)
+            .ok_or(QuoteError::Overflow)?
+            .div_ceil(10_000 - self.fee as u128);
+
+        u64::try_from(amount_in).map_err(|_| QuoteError::Overflow)
+    }
+
+    // Output for `amount` of X (is_x) or Y at the current marginal price, i.e. with no
+    // slippage and no fee. Differentiating the invariant gives, for X in,
+    //   dy/dx = y(4Ax + c) / (x(4Ay + c)) with c = D^3 / (4xy)
+    pub fn spot_amount_out(&self, is_x: bool, amount: u64) -> Result<u64, QuoteError> {
+        let d = compute_d(self.x, self.y, self.amp)?;
+        let (x, y) = (self.x as u128, self.y as u128);
+        let ann = self.amp as u128 * 4;
+        let c = d
+            .checked_mul(d)
+            .ok_or(QuoteError::Overflow)?
+            .checked_div(x * 2)
+            .ok_or(QuoteError::Overflow)?
+            .checked_mul(d)
+            .ok_or(QuoteError::Overflow)?
+            / (y * 2);
+
+        let (reserve_in, reserve_out) = match is_x {
+            true => (x, y),
+            false => (y, x),
+        };
+        let weight_in = ann
+            .checked_mul(reserve_in)
+            .and_then(|n| n.checked_add(c))
+            .ok_or(QuoteError::Overflow)?;
+        let weight_out = ann
+            .checked_mul(reserve_out)
+            .and_then(|n| n.checked_add(c))
+            .ok_or(QuoteError::Overflow)?;
+
+        let amount_out = mul_div(amount as u128, reserve_out, reserve_in, false)
+            .and_then(|n| mul_div(n, weight_in, weight_out, false))
+            .ok_or(QuoteError::Overflow)?;
+        u64::try_from(amount_out).map_err(|_| QuoteError::Overflow)
+    }
+}
+
+// Invariant D of a pool holding x and y
+pub fn compute_d(x: u64, y: u64, amp: u64) -> Result<u128, QuoteError> {
+    if x == 0 || y == 0 {
+        return Err(QuoteError::ZeroBalance);
+    }
+    let (x, y) = (x as u128, y as u128);
+    let s = x + y;
+    let ann = amp as u128 * 4;
+
+    let mut d = s;
+    for _ in 0..MAX_ITERATIONS {
+        // D^3 / (4xy)
+        let d_p = d
+            .checked_mul(d)
+            .ok_or(QuoteError::Overflow)?
+            .checked_div(x * 2)
+            .ok_or(QuoteError::Overflow)?
+            .checked_mul(d)
+            .ok_or(QuoteError::Overflow)?
+            / (y * 2);
+
+        let previous = d;
+        let numerator = ann
+            .checked_mul(s)
+            .and_then(|n| n.checked_add(d_p * 2))
+            .and_then(|n| n.checked_mul(d))
+            .ok_or(QuoteError::Overflow)?;
+        let denominator = (ann - 1)
+            .checked_mul(d)
+            .and_then(|n| n.checked_add(d_p.checked_mul(3)?))
+            .ok_or(QuoteError::Overflow)?;
+        d = numerator / denominator;
+
+        if d.abs_diff(previous) <= 1 {
+            return Ok(d);
+        }
+    }
+
+    Err(QuoteError::Overflow)
+}
+
+// Reserve of one token that keeps the invariant at `d` when the other one holds `x`
+fn compute_y(x: u128, d: u128, amp: u64) -> Result<u128, QuoteError> {
+    if x == 0 {
+        return Err(QuoteError::ZeroBalance);
+    }
+    let ann = amp as u128 * 4;
+
+    // c = D^3 / (4x * Ann), b = x + D / Ann
+    let c = d
+        .checked_mul(d)
+        .ok_or(QuoteError::Overflow)?
+        .checked_div(x * 2)
+        .ok_or(QuoteError::Overflow)?
+        .checked_mul(d)
+        .ok_or(QuoteError::Overflow)?
+        / (ann * 2);
+    let b = x + d / ann;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let previous = y;
+        let numerator = y
+            .checked_mul(y)
+            .and_then(|n| n.checked_add(c))
+            .ok_or(QuoteError::Overflow)?;
+        let denominator = (y * 2 + b)
+            .checked_sub(d)
+            .filter(|n| *n != 0)
+            .ok_or(QuoteError::Underflow)?;
+        y = numerator / denominator;
+
+        if y.abs_diff(previous) <= 1 {
+            return Ok(y);
+        }
+    }
+
+    Err(QuoteError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balanced_pool_invariant_is_the_sum() {
+        for amp in [1, 10, 100, 10_000, 1_000_000] {
+            let d = compute_d(1_000_000_000, 1_000_000_000, amp).unwrap();
+            assert!(d.abs_diff(2_000_000_000) <= 1, "{amp}");
+        }
+    }
+
+    #[test]
+    fn high_amp_trades_near_the_peg() {
+        let mut stable = StableSwap::init(1_000_000_000, 1_000_000_000, 100, 0).unwrap();
+        let out = stable.swap(true, 10_000_000, 0).unwrap().withdraw;
+        // A constant product pool would pay out ~9.9M here
+        assert!(out > 9_990_000 && out <= 10_000_000, "{out}");
+    }
+
+    #[test]
+    fn swaps_never_decrease_the_invariant() {
+        for (x, y, amp, fee) in [
+            (1_000_000_000u64, 1_000_000_000u64, 100u64, 30u16),
+            (1_000_000_000, 250_000_000, 10, 0),
+            (7_000_000, 3_000_000_000_000, 1, 5),
+            (123_456_789, 987_654_321, 5_000, 100),
+        ] {
+            for (is_x, a) in [(true, x / 10), (false, y / 3)] {
+                let d = compute_d(x, y, amp).unwrap();
+                let mut stable = StableSwap::init(x, y, amp, fee).unwrap();
+                let result = stable.swap(is_x, a, 0).unwrap();
+                assert!(
+                    compute_d(result.x, result.y, amp).unwrap() >= d,
+                    "{x} {y} {amp}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_out_input_buys_the_output() {
+        for (x, y, amp, fee) in [
+            (1_000_000_000u64, 1_000_000_000u64, 100u64, 30u16),
+            (1_000_000_000, 250_000_000, 10, 0),
+            (7_000_000, 3_000_000_000_000, 1, 5),
+            (123_456_789, 987_654_321, 5_000, 100),
+        ] {
+            for amount_out in [1, 1_000, y / 100, y / 2] {
+                let mut stable = StableSwap::init(x, y, amp, fee).unwrap();
+                let amount_in = stable.amount_in_for_exact_out(true, amount_out).unwrap();
+                let withdraw = stable.swap(true, amount_in, 0).unwrap().withdraw;
+                assert!(withdraw >= amount_out, "{x} {y} {amp} {amount_out}");
+                // while 0.1% less would not have been enough
+                if amount_in >= 100_000 {
+                    let mut stable = StableSwap::init(x, y, amp, fee).unwrap();
+                    let less = amount_in - amount_in / 1_000;
+                    let withdraw = stable.swap(true, less, 0).unwrap().withdraw;
+                    assert!(withdraw < amount_out, "{x} {y} {amp} {amount_out}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_draining_the_pool() {
+        let stable = StableSwap::init(1_000, 1_000, 100, 0).unwrap();
+        assert!(matches!(
+            stable.amount_in_for_exact_out(false, 1_000),
+            Err(QuoteError::InsufficientBalance)
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+use amm_quote::mul_div;
+
+// Dynamic fee mode. Every swap records how far it moved the reserve price, in basis
+// points, into a volatility accumulator that decays linearly to zero over
+// VOLATILITY_DECAY seconds. The swap fee is the pool's base fee plus one basis point for
+// every VOLATILITY_FEE_DIVISOR basis points of volatility, bounded by the pool's min and
+// max fee
+
+pub const VOLATILITY_DECAY: i64 = 600;
+pub const VOLATILITY_FEE_DIVISOR: u64 = 10;
+
+// What is left at `now` of the volatility recorded at `timestamp`
+pub fn decayed_volatility(volatility: u64, timestamp: i64, now: i64) -> u64 {
+    let elapsed = now.saturating_sub(timestamp).max(0);
+    let remaining = VOLATILITY_DECAY.saturating_sub(elapsed).max(0);
+    (volatility as u128 * remaining as u128 / VOLATILITY_DECAY as u128) as u64
+}
+
+pub fn dynamic_fee(base_fee: u16, min_fee: u16, max_fee: u16, volatility: u64) -> u16 {
+    let fee = (base_fee as u64).saturating_add(volatility / VOLATILITY_FEE_DIVISOR);
+    fee.clamp(min_fee as u64, max_fee as u64) as u16
+}
+
+// Relative change of the reserve price y / x between two pool states, in basis points:
+// |y1 / x1 - y0 / x0| / (y0 / x0) = |y1 * x0 - y0 * x1| / (y0 * x1)
+pub fn price_movement(before: (u64, u64), after: (u64, u64)) -> u64 {
+    let (x0, y0) = (before.0 as u128, before.1 as u128);
+    let (x1, y1) = (after.0 as u128, after.1 as u128);
+
+    let (current, previous) = (y1 * x0, y0 * x1);
+    mul_div(current.abs_diff(previous), 10_000, previous, false)
+        .map_or(u64::MAX, |movement| movement.min(u64::MAX as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volatility_decays_linearly() {
+        assert_eq!(decayed_volatility(1_000, 100, 100), 1_000);
+        assert_eq!(decayed_volatility(1_000, 100, 100 + VOLATILITY_DECAY / 2), 500);
+        assert_eq!(decayed_volatility(1_000, 100, 100 + VOLATILITY_DECAY), 0);
+        assert_eq!(decayed_volatility(1_000, 100, i64::MAX), 0);
+        // A clock going backwards doesn't inflate it
+        assert_eq!(decayed_volatility(1_000, 100, 50), 1_000);
+    }
+
+    #[test]
+    fn fee_follows_volatility_within_bounds() {
+        assert_eq!(dynamic_fee(30, 10, 100, 0), 30);
+        assert_eq!(dynamic_fee(30, 10, 100, 200), 50);
+        assert_eq!(dynamic_fee(30, 10, 100, u64::MAX), 100);
+        assert_eq!(dynamic_fee(5, 10, 100, 0), 10);
+    }
+
+    #[test]
+    fn price_movement_is_relative() {
+        // Price 1.0 -> 1.21
+        assert_eq!(price_movement((1_000, 1_000), (1_000, 1_210)), 2_100);
+        // Price 1.0 -> ~0.826, in either reserve
+        assert_eq!(price_movement((1_000, 1_000), (1_100, 909)), 1_736);
+        assert_eq!(price_movement((5, 7), (5, 7)), 0);
+        // Huge reserves don't overflow
+        assert_eq!(price_movement((u64::MAX, u64::MAX), (u64::MAX / 2, u64::MAX)), 10_000);
+        assert_eq!(price_movement((0, 0), (1, 1)), u64::MAX);
+    }
+}
@@ -1,5 +1,5 @@
+use amm_quote::QuoteError;
 use anchor_lang::error_code;
-use constant_product_curve::CurveError;
 
 #[error_code]
 pub enum AmmError {
@@ -7,7 +7,7 @@ pub enum AmmError {
     DefaultError,
     #[msg("Offer expired.")]
     OfferExpired,
-    #[msg("This pool is locked.")]
+    #[msg("This operation is paused on this pool.")]
     PoolLocked,
     #[msg("Slippage exceeded.")]
     SlippageExceeded,
@@ -39,18 +39,78 @@ pub enum AmmError {
     InsufficientBalance,
     #[msg("Zero balance.")]
     ZeroBalance,
+    #[msg("Invalid swap route.")]
+    InvalidRoute,
+    #[msg("Not enough oracle history for the requested window.")]
+    OracleWindowTooLong,
+    #[msg("Invalid timestamp.")]
+    InvalidTimestamp,
+    #[msg("No fee update is pending.")]
+    NoPendingFee,
+    #[msg("Timelock has not expired yet.")]
+    TimelockNotExpired,
+    #[msg("Not supported by this pool's curve.")]
+    InvalidCurve,
+    #[msg("Invalid amplification coefficient.")]
+    InvalidAmp,
+    #[msg("Invalid amplification ramp.")]
+    InvalidRamp,
+    #[msg("Invalid tick.")]
+    InvalidTick,
+    #[msg("Tick array is missing, repeated or not the pool's.")]
+    InvalidTickArray,
+    #[msg("A flash loan is outstanding.")]
+    FlashLoanActive,
+    #[msg("No flash loan is outstanding.")]
+    NoFlashLoan,
+    #[msg("Flash loan is not repaid in the same transaction.")]
+    FlashLoanNotRepaid,
+    #[msg("Flash loan repayment is short.")]
+    FlashLoanUnderpaid,
+    #[msg("Trade is larger than the pool allows.")]
+    TradeTooLarge,
+    #[msg("Circuit breaker tripped, swaps are halted.")]
+    CircuitBreakerTripped,
+    #[msg("Invalid trade limits.")]
+    InvalidTradeLimits,
+    #[msg("Invalid pause flags.")]
+    InvalidPauseFlags,
+    #[msg("Config account is not in the expected layout version.")]
+    InvalidConfigVersion,
+    #[msg("Metadata URI is too long.")]
+    InvalidUri,
+    #[msg("Pool still has liquidity.")]
+    PoolNotEmpty,
+    #[msg("Invalid limit order accounts.")]
+    InvalidOrder,
+    #[msg("No limit order can be filled at the current price.")]
+    OrderNotFillable,
+    #[msg("Referrer account is not in the token the fee is taken in.")]
+    InvalidReferrer,
+    #[msg("Position still has liquidity or uncollected fees.")]
+    PositionNotEmpty,
+    #[msg("Only pools without an authority can be registered.")]
+    AuthorityNotRenounced,
+    #[msg("Farm still has staked LP tokens or unharvested rewards.")]
+    FarmNotEmpty,
+    #[msg("Mints with a transfer hook are not supported.")]
+    TransferHookMint,
 }
 
-impl From<CurveError> for AmmError {
-    fn from(error: CurveError) -> AmmError {
+impl From<QuoteError> for AmmError {
+    fn from(error: QuoteError) -> AmmError {
         match error {
-            CurveError::InvalidPrecision => AmmError::InvalidPrecision,
-            CurveError::Overflow => AmmError::Overflow,
-            CurveError::Underflow => AmmError::Underflow,
-            CurveError::InvalidFeeAmount => AmmError::InvalidFee,
-            CurveError::InsufficientBalance => AmmError::InsufficientBalance,
-            CurveError::ZeroBalance => AmmError::ZeroBalance,
-            CurveError::SlippageLimitExceeded => AmmError::SlippageExceeded,
+            QuoteError::Overflow => AmmError::Overflow,
+            QuoteError::Underflow => AmmError::Underflow,
+            QuoteError::InvalidFee => AmmError::InvalidFee,
+            QuoteError::InvalidAmount => AmmError::InvalidAmount,
+            QuoteError::InvalidPrecision => AmmError::InvalidPrecision,
+            QuoteError::InsufficientBalance => AmmError::InsufficientBalance,
+            QuoteError::ZeroBalance => AmmError::ZeroBalance,
+            QuoteError::SlippageExceeded => AmmError::SlippageExceeded,
+            QuoteError::NoLiquidityInPool => AmmError::NoLiquidityInPool,
+            QuoteError::LiquidityLessThanMinimum => AmmError::LiquidityLessThanMinimum,
+            QuoteError::CurveError => AmmError::CurveError,
         }
     }
 }
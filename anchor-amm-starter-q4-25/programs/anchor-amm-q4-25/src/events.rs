@@ -0,0 +1,366 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{CurveType, FeeSide};
+
+#[event]
+pub struct AuthorityProposed {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub config: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityRenounced {
+    pub config: Pubkey,
+    pub previous_authority: Pubkey,
+}
+
+#[event]
+pub struct FeeUpdateQueued {
+    pub config: Pubkey,
+    pub fee: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct FeeUpdated {
+    pub config: Pubkey,
+    pub old_fee: u16,
+    pub new_fee: u16,
+}
+
+#[event]
+pub struct PoolInitialized {
+    pub config: Pubkey,
+    pub initializer: Pubkey,
+    pub authority: Option<Pubkey>,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub mint_lp: Pubkey,
+    pub fee: u16,
+    pub min_fee: u16,
+    pub max_fee: u16,
+    pub protocol_fee: u16,
+    pub fee_timelock: i64,
+    pub curve_type: CurveType,
+    pub amp: u64,
+    pub fee_side: FeeSide,
+    pub referral_fee: u16,
+}
+
+// Amounts are what left the user (deposits) or the vaults (withdrawals), before any
+// Token-2022 transfer fee. `fee` and `protocol_fee` are only set by the single-sided
+// variants, which swap part of the liquidity through the curve
+#[event]
+pub struct LiquidityDeposited {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub lp_supply: u64,
+}
+
+#[event]
+pub struct LiquidityWithdrawn {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub lp_supply: u64,
+}
+
+// Emitted once per pool, so a routed swap emits one per hop. `fee` is the full swap
+// fee in the token on the pool's fee side, of which `protocol_fee` was set aside for the
+// protocol and `referral_fee` paid to the referrer
+#[event]
+pub struct Swapped {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub is_x: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct AmpRampStarted {
+    pub config: Pubkey,
+    pub initial_amp: u64,
+    pub target_amp: u64,
+    pub ramp_start: i64,
+    pub ramp_end: i64,
+}
+
+#[event]
+pub struct AmpRampStopped {
+    pub config: Pubkey,
+    pub amp: u64,
+}
+
+#[event]
+pub struct PoolUpdated {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub paused: u8,
+}
+
+#[event]
+pub struct ProtocolFeesCollected {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+#[event]
+pub struct ConcentratedPoolInitialized {
+    pub config: Pubkey,
+    pub tick_spacing: u16,
+    pub sqrt_price: u128,
+    pub tick: i32,
+}
+
+#[event]
+pub struct PositionOpened {
+    pub config: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+}
+
+// `liquidity_delta` is positive for increase_liquidity and negative for
+// decrease_liquidity; amounts are what the vaults received or sent
+#[event]
+pub struct PositionLiquidityChanged {
+    pub config: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub liquidity_delta: i128,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub position_liquidity: u128,
+    pub pool_liquidity: u128,
+}
+
+#[event]
+pub struct PositionFeesCollected {
+    pub config: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+// `fee` is in the token on the pool's fee side, like Swapped's
+#[event]
+pub struct ConcentratedSwapped {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub is_x: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+    pub sqrt_price: u128,
+    pub tick: i32,
+    pub liquidity: u128,
+}
+
+#[event]
+pub struct FlashLoanBorrowed {
+    pub config: Pubkey,
+    pub borrower: Pubkey,
+    pub is_x: bool,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct FlashLoanRepaid {
+    pub config: Pubkey,
+    pub borrower: Pubkey,
+    pub is_x: bool,
+    pub amount: u64,
+    pub fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct TradeLimitsUpdated {
+    pub config: Pubkey,
+    pub max_trade: u16,
+    pub breaker_threshold: u16,
+    pub breaker_window: i64,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub config: Pubkey,
+    pub price_movement: u64,
+    pub window_start: i64,
+}
+
+#[event]
+pub struct CircuitBreakerReset {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct ConfigMigrated {
+    pub config: Pubkey,
+    pub version: u8,
+    pub paused: u8,
+}
+
+#[event]
+pub struct FarmCreated {
+    pub config: Pubkey,
+    pub farm: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_per_second: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+// `amount` is positive for stake_lp and negative for unstake_lp
+#[event]
+pub struct StakeChanged {
+    pub farm: Pubkey,
+    pub owner: Pubkey,
+    pub amount: i64,
+    pub stake: u64,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct RewardsHarvested {
+    pub farm: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LpUriUpdated {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub uri: String,
+}
+
+// `amount_x` and `amount_y` are the protocol fees paid out
+#[event]
+pub struct PoolClosed {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+#[event]
+pub struct LimitOrderPlaced {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub is_x: bool,
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+    pub tip: u64,
+    pub expiration: i64,
+}
+
+#[event]
+pub struct LimitOrderFilled {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub keeper: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub tip: u64,
+}
+
+#[event]
+pub struct LimitOrderCancelled {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ReferralFeeUpdated {
+    pub config: Pubkey,
+    pub old_referral_fee: u16,
+    pub new_referral_fee: u16,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub pool: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct ProtocolFeeUpdateQueued {
+    pub config: Pubkey,
+    pub protocol_fee: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ProtocolFeeUpdated {
+    pub config: Pubkey,
+    pub old_protocol_fee: u16,
+    pub new_protocol_fee: u16,
+}
+
+#[event]
+pub struct PoolRegistered {
+    pub config: Pubkey,
+    pub pool_entry: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub fee: u16,
+    pub curve_type: CurveType,
+}
+
+// `amount` is the unemitted rewards refunded to the authority
+#[event]
+pub struct FarmClosed {
+    pub config: Pubkey,
+    pub farm: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct TickArrayInitialized {
+    pub pool: Pubkey,
+    pub tick_array: Pubkey,
+    pub start_tick: i32,
+}
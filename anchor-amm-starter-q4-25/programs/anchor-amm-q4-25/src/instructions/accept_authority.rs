@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::AmmError, events::AuthorityTransferred, state::Config};
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> AcceptAuthority<'info> {
+    pub fn accept_authority(&mut self) -> Result<()> {
+        require!(
+            self.config.pending_authority == Some(self.new_authority.key()),
+            AmmError::InvalidAuthority
+        );
+        let previous_authority = self.config.authority.ok_or(AmmError::NoAuthoritySet)?;
+
+        self.config.authority = Some(self.new_authority.key());
+        self.config.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            config: self.config.key(),
+            previous_authority,
+            new_authority: self.new_authority.key(),
+        });
+
+        Ok(())
+    }
+}
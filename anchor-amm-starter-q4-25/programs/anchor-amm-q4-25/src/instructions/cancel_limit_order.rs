@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{events::LimitOrderCancelled, state::LimitOrder};
+
+#[derive(Accounts)]
+pub struct CancelLimitOrder<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mint::token_program = token_program,
+    )]
+    pub mint_in: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [
+            b"order",
+            order.config.as_ref(),
+            owner.key().as_ref(),
+            order.id.to_le_bytes().as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    pub order: Account<'info, LimitOrder>,
+    #[account(
+        mut,
+        associated_token::mint = mint_in,
+        associated_token::authority = order,
+        associated_token::token_program = token_program,
+    )]
+    pub order_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_in,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_in: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> CancelLimitOrder<'info> {
+    // Refunds the escrowed input and closes the order, tip and rent included, to the owner.
+    // Works whatever state the pool is in, even once it is closed
+    pub fn cancel_limit_order(&mut self) -> Result<()> {
+        let amount = self.order_vault.amount;
+        if amount > 0 {
+            self.withdraw_tokens(amount)?;
+        }
+        self.close_vault()?;
+
+        emit!(LimitOrderCancelled {
+            config: self.order.config,
+            order: self.order.key(),
+            owner: self.owner.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_tokens(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.order_vault.to_account_info(),
+            mint: self.mint_in.to_account_info(),
+            to: self.owner_in.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by the order PDA
+        let owner = self.owner.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            self.order.config.as_ref(),
+            owner.as_ref(),
+            &self.order.id.to_le_bytes(),
+            &[self.order.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, self.mint_in.decimals)
+    }
+
+    fn close_vault(&self) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.order_vault.to_account_info(),
+            destination: self.owner.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        let owner = self.owner.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            self.order.config.as_ref(),
+            owner.as_ref(),
+            &self.order.id.to_le_bytes(),
+            &[self.order.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        close_account(ctx)
+    }
+}
@@ -0,0 +1,188 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, close_account, transfer_checked, Burn, CloseAccount, Mint, TokenAccount, TokenInterface,
+    TransferChecked,
+};
+
+use crate::{
+    errors::AmmError,
+    events::FarmClosed,
+    state::{Config, Farm},
+    utils::harvest_withheld_fees,
+};
+
+#[derive(Accounts)]
+pub struct CloseFarm<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        close = authority,
+        has_one = config,
+        has_one = reward_mint,
+        seeds = [b"farm", config.key().as_ref(), reward_mint.key().as_ref()],
+        bump = farm.bump,
+    )]
+    pub farm: Account<'info, Farm>,
+    #[account(
+        mut,
+        associated_token::mint = mint_lp,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = authority,
+        token::token_program = token_program,
+    )]
+    pub authority_reward: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CloseFarm<'info> {
+    // Ends a farm nobody is staked in or owed anything by, refunding the rewards it never
+    // emitted to the authority. LP tokens sent to the vault without staking are burned,
+    // so they can't keep the farm, and with it the pool, from being closed
+    pub fn close_farm(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(
+            self.farm.total_staked == 0 && self.farm.rewards_owed == 0,
+            AmmError::FarmNotEmpty
+        );
+
+        let stray_lp = self.lp_vault.amount;
+        let amount = self.reward_vault.amount;
+
+        if stray_lp > 0 {
+            self.burn_stray_lp(stray_lp)?;
+        }
+        if amount > 0 {
+            self.withdraw_rewards(amount)?;
+        }
+        self.close_vault(true)?;
+        self.close_vault(false)?;
+
+        self.config.farms = self
+            .config
+            .farms
+            .checked_sub(1)
+            .ok_or(AmmError::Underflow)?;
+
+        emit!(FarmClosed {
+            config: self.config.key(),
+            farm: self.farm.key(),
+            reward_mint: self.reward_mint.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn burn_stray_lp(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = Burn {
+            mint: self.mint_lp.to_account_info(),
+            from: self.lp_vault.to_account_info(),
+            authority: self.farm.to_account_info(),
+        };
+
+        let config = self.config.key();
+        let reward_mint = self.reward_mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"farm",
+            config.as_ref(),
+            reward_mint.as_ref(),
+            &[self.farm.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        burn(ctx, amount)
+    }
+
+    pub fn withdraw_rewards(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.authority_reward.to_account_info(),
+            authority: self.farm.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by farm PDA
+        let config = self.config.key();
+        let reward_mint = self.reward_mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"farm",
+            config.as_ref(),
+            reward_mint.as_ref(),
+            &[self.farm.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, self.reward_mint.decimals)
+    }
+
+    // Closes the LP vault (is_lp) or the reward vault, returning its rent to the authority
+    fn close_vault(&self, is_lp: bool) -> Result<()> {
+        let account = match is_lp {
+            true => self.lp_vault.to_account_info(),
+            false => {
+                let account = self.reward_vault.to_account_info();
+                harvest_withheld_fees(&self.token_program, &self.reward_mint, account.clone())?;
+                account
+            }
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account,
+            destination: self.authority.to_account_info(),
+            authority: self.farm.to_account_info(),
+        };
+
+        let config = self.config.key();
+        let reward_mint = self.reward_mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"farm",
+            config.as_ref(),
+            reward_mint.as_ref(),
+            &[self.farm.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        close_account(ctx)
+    }
+}
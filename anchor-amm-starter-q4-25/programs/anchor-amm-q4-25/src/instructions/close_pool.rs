@@ -0,0 +1,242 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{
+    errors::AmmError,
+    events::PoolClosed,
+    state::{Config, Oracle},
+    utils::harvest_withheld_fees,
+};
+
+// Decommissions a pool nobody provides liquidity to anymore. The LP mint, the locked LP
+// account and the LP metadata stay behind: SPL Token mints can't be closed, the locked
+// LP is owned by the system program and the metadata program only closes on a burn. The
+// LP mint keeps its [b"lp", config] address, so the pool's seed can never be initialized
+// again. Every farm on the LP token has to be closed with close_farm first
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        close = authority,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    /// CHECK: the pool's concentrated liquidity PDA, passed whether or not
+    /// initialize_concentrated created it and closed if it did
+    #[account(
+        mut,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump,
+    )]
+    pub concentrated_pool: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        associated_token::mint = mint_lp,
+        associated_token::authority = system_program,
+        associated_token::token_program = token_program,
+    )]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::token_program = token_program_x,
+    )]
+    pub treasury_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::token_program = token_program_y,
+    )]
+    pub treasury_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    // The LP token's program, which X's and Y's don't have to match
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClosePool<'info> {
+    // Pays the uncollected protocol fees out to the treasury accounts and closes the pool
+    // accounts to the authority. Every LP token apart from the locked ones has to be
+    // burned first. The reserves behind the locked LP tokens belong to nobody, so they stay
+    // in the vaults, which are only closed once empty
+    pub fn close_pool(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.config.check_allowed(0)?;
+        require!(
+            self.mint_lp.supply == self.locked_lp.amount,
+            AmmError::PoolNotEmpty
+        );
+        // Concentrated positions and their uncollected fees
+        require!(
+            self.config.concentrated_x == 0 && self.config.concentrated_y == 0,
+            AmmError::PoolNotEmpty
+        );
+        // Farms pay out of their own vaults but stake LP tokens of this pool
+        require!(self.config.farms == 0, AmmError::PoolNotEmpty);
+
+        let amount_x = self.config.protocol_fees_x;
+        let amount_y = self.config.protocol_fees_y;
+
+        if amount_x > 0 {
+            self.withdraw_tokens(true, amount_x)?;
+        }
+        if amount_y > 0 {
+            self.withdraw_tokens(false, amount_y)?;
+        }
+        if self.vault_x.amount == amount_x {
+            self.close_vault(true)?;
+        }
+        if self.vault_y.amount == amount_y {
+            self.close_vault(false)?;
+        }
+        self.close_concentrated_pool()?;
+
+        emit!(PoolClosed {
+            config: self.config.key(),
+            authority: self.authority.key(),
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                self.treasury_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                self.treasury_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    // Only an account the program created can sit at the concentrated PDA
+    fn close_concentrated_pool(&self) -> Result<()> {
+        let pool = self.concentrated_pool.to_account_info();
+        if pool.owner != &crate::ID {
+            return Ok(());
+        }
+
+        let authority = self.authority.to_account_info();
+        let lamports = authority
+            .lamports()
+            .checked_add(pool.lamports())
+            .ok_or(AmmError::Overflow)?;
+        **authority.try_borrow_mut_lamports()? = lamports;
+        **pool.try_borrow_mut_lamports()? = 0;
+        pool.assign(&System::id());
+        pool.resize(0)?;
+
+        Ok(())
+    }
+
+    fn close_vault(&self, is_x: bool) -> Result<()> {
+        let (account, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+        harvest_withheld_fees(token_program, mint, account.clone())?;
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account,
+            destination: self.authority.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        close_account(ctx)
+    }
+}
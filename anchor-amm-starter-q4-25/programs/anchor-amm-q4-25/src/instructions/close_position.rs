@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::AmmError, events::PositionClosed, state::Position};
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [
+            b"position",
+            position.pool.as_ref(),
+            owner.key().as_ref(),
+            position.tick_lower.to_le_bytes().as_ref(),
+            position.tick_upper.to_le_bytes().as_ref(),
+        ],
+        bump = position.bump,
+    )]
+    pub position: Account<'info, Position>,
+}
+
+impl<'info> ClosePosition<'info> {
+    // Closes an emptied position and returns its rent to the owner. Its ticks were freed
+    // for other positions when its liquidity went to zero; any fees have to be collected
+    // first. Works whatever state the pool is in
+    pub fn close_position(&mut self) -> Result<()> {
+        require!(
+            self.position.liquidity == 0
+                && self.position.fees_owed_x == 0
+                && self.position.fees_owed_y == 0,
+            AmmError::PositionNotEmpty
+        );
+
+        emit!(PositionClosed {
+            pool: self.position.pool,
+            position: self.position.key(),
+            owner: self.owner.key(),
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{events::ProtocolFeesCollected, state::Config};
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::token_program = token_program_x,
+    )]
+    pub treasury_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::token_program = token_program_y,
+    )]
+    pub treasury_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> CollectProtocolFees<'info> {
+    pub fn collect_protocol_fees(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+
+        let amount_x = self.config.protocol_fees_x;
+        let amount_y = self.config.protocol_fees_y;
+
+        // Reset the counters first; the curve reserves are untouched since they
+        // never included these amounts
+        self.config.protocol_fees_x = 0;
+        self.config.protocol_fees_y = 0;
+
+        if amount_x > 0 {
+            self.withdraw_tokens(true, amount_x)?;
+        }
+        if amount_y > 0 {
+            self.withdraw_tokens(false, amount_y)?;
+        }
+
+        emit!(ProtocolFeesCollected {
+            config: self.config.key(),
+            authority: self.authority.key(),
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                self.treasury_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                self.treasury_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -0,0 +1,136 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::FarmCreated,
+    state::{Config, Farm},
+    utils::transfer_inverse_fee,
+};
+
+#[derive(Accounts)]
+pub struct CreateFarm<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"farm", config.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+        space = Farm::DISCRIMINATOR.len() + Farm::INIT_SPACE,
+    )]
+    pub farm: Account<'info, Farm>,
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = mint_lp,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = authority,
+        token::token_program = token_program,
+    )]
+    pub authority_reward: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateFarm<'info> {
+    // Starts a reward schedule for the pool's LP stakers, funded up front with every
+    // reward it will ever emit
+    pub fn create_farm(
+        &mut self,
+        reward_per_second: u64,
+        start_time: i64,
+        end_time: i64,
+        bumps: CreateFarmBumps,
+    ) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(reward_per_second != 0, AmmError::InvalidAmount);
+        require!(
+            Clock::get()?.unix_timestamp <= start_time && start_time < end_time,
+            AmmError::InvalidTimestamp
+        );
+
+        self.farm.set_inner(Farm {
+            config: self.config.key(),
+            reward_mint: self.reward_mint.key(),
+            reward_per_second,
+            start_time,
+            end_time,
+            total_staked: 0,
+            rewards_owed: 0,
+            reward_per_share: 0,
+            last_update: start_time,
+            bump: bumps.farm,
+        });
+
+        // The vault has to receive the full schedule after any transfer fee
+        let rewards = self.farm.total_rewards()?;
+        let amount = rewards
+            .checked_add(transfer_inverse_fee(&self.reward_mint, rewards)?)
+            .ok_or(AmmError::Overflow)?;
+        self.deposit_rewards(amount)?;
+
+        // close_pool waits for close_farm to have closed every farm
+        self.config.farms = self.config.farms.checked_add(1).ok_or(AmmError::Overflow)?;
+
+        emit!(FarmCreated {
+            config: self.config.key(),
+            farm: self.farm.key(),
+            reward_mint: self.reward_mint.key(),
+            reward_per_second,
+            start_time,
+            end_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit_rewards(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.authority_reward.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_vault.to_account_info(),
+            authority: self.authority.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, self.reward_mint.decimals)
+    }
+}
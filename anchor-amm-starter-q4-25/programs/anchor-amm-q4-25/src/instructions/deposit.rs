@@ -1,63 +1,99 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer},
+    token_interface::{
+        mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
-use constant_product_curve::ConstantProduct;
 
-use crate::{errors::AmmError, state::Config};
+use crate::{
+    errors::AmmError,
+    events::LiquidityDeposited,
+    quote::{quote_deposit, quote_initial_deposit, quote_swap, Curve, MINIMUM_LIQUIDITY},
+    state::{Config, Oracle, PAUSE_DEPOSITS, PAUSE_SWAPS},
+    utils::{transfer_fee, transfer_inverse_fee},
+};
 
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
     #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
     #[account(
         mut,
         seeds = [b"lp", config.key().as_ref()],
         bump = config.lp_bump,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub mint_lp: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = config,
+        associated_token::token_program = token_program_x,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program_y,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = user,
+        associated_token::token_program = token_program_x,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program_y,
     )]
-    pub user_y: Account<'info, TokenAccount>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = mint_lp,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub user_lp: InterfaceAccount<'info, TokenAccount>,
+    // LP account owned by the system program, which nobody can sign for
+    #[account(
+        mut,
+        associated_token::mint = mint_lp,
+        associated_token::authority = system_program,
+        associated_token::token_program = token_program,
+    )]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    // The LP token's program, which X's and Y's don't have to match
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
@@ -65,81 +101,228 @@ pub struct Deposit<'info> {
 impl<'info> Deposit<'info> {
     pub fn deposit(
         &mut self,
-        amount: u64, // Amount of LP tokens that the user wants to "claim"
-        max_x: u64,  // Maximum amount of token X that the user is willing to deposit
-        max_y: u64,  // Maximum amount of token Y that the user is willing to deposit
+        lp_amount: u64, // LP tokens to mint, or on the first deposit the least LP accepted
+        max_x: u64,     // Most X the user pays, or on the first deposit exactly what it pays
+        max_y: u64,     // Most Y the user pays, or on the first deposit exactly what it pays
         expiration: i64,
     ) -> Result<()> {
-        require!(self.config.locked == false, AmmError::PoolLocked);
-        require!(amount != 0, AmmError::InvalidAmount);
+        self.config.check_allowed(PAUSE_DEPOSITS)?;
+        require!(lp_amount != 0, AmmError::InvalidAmount);
         require!(
             Clock::get()?.unix_timestamp < expiration,
             AmmError::OfferExpired
         );
 
-        let (x, y) = match self.mint_lp.supply == 0
-            && self.vault_x.amount == 0
-            && self.vault_y.amount == 0
-        {
-            true => (max_x, max_y),
-            false => {
-                let amounts = ConstantProduct::xy_deposit_amounts_from_l(
-                    self.vault_x.amount,
-                    self.vault_y.amount,
-                    self.mint_lp.supply,
-                    amount,
-                    6,
-                )
-                .map_err(|_| AmmError::CurveError)?;
-                (amounts.x, amounts.y)
-            }
-        };
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        // Accrue the price that held up to this change into the oracle
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+
+        if self.mint_lp.supply == 0 {
+            return self.deposit_initial(lp_amount, max_x, max_y);
+        }
+
+        let pool = self
+            .config
+            .pool_state(reserve_x, reserve_y, self.mint_lp.supply)?;
+        let quote = quote_deposit(&pool, lp_amount)?;
+
+        // The vaults must receive exactly (x, y), so gross the transfers up by any
+        // Token-2022 transfer fee the user pays on the way in
+        let x = quote
+            .amount_x
+            .checked_add(transfer_inverse_fee(&self.mint_x, quote.amount_x)?)
+            .ok_or(AmmError::Overflow)?;
+        let y = quote
+            .amount_y
+            .checked_add(transfer_inverse_fee(&self.mint_y, quote.amount_y)?)
+            .ok_or(AmmError::Overflow)?;
 
         require!(x <= max_x && y <= max_y, AmmError::SlippageExceeded);
+
+        // deposit token x
+        self.deposit_tokens(true, x)?;
+        // deposit token y
+        self.deposit_tokens(false, y)?;
+        // mint lp tokens
+        self.mint_lp_tokens(self.user_lp.to_account_info(), lp_amount)?;
+
+        self.emit_deposited(x, y, lp_amount, 0, 0)
+    }
+
+    // The first deposit sets the price from what reaches the vaults, and locks
+    // MINIMUM_LIQUIDITY forever (see quote_initial_deposit). `min_lp` is the least the
+    // user accepts
+    fn deposit_initial(&mut self, min_lp: u64, x: u64, y: u64) -> Result<()> {
         require!(
             x != 0 && y != 0,
             AmmError::LiquidityLessThanMinimum
         );
 
+        let received_x = x - transfer_fee(&self.mint_x, x)?;
+        let received_y = y - transfer_fee(&self.mint_y, y)?;
+        let pool = self.config.pool_state(0, 0, 0)?;
+        let lp = quote_initial_deposit(&pool, received_x, received_y)?.lp_amount;
+        require!(lp >= min_lp, AmmError::SlippageExceeded);
+
         // deposit token x
         self.deposit_tokens(true, x)?;
         // deposit token y
         self.deposit_tokens(false, y)?;
-        // mint lp tokens
-        self.mint_lp_tokens(amount)
+        // lock the minimum liquidity and mint the rest to the user
+        self.mint_lp_tokens(self.locked_lp.to_account_info(), MINIMUM_LIQUIDITY)?;
+        self.mint_lp_tokens(self.user_lp.to_account_info(), lp)?;
+
+        self.emit_deposited(x, y, lp, 0, 0)
+    }
+
+    pub fn deposit_single(
+        &mut self,
+        is_x: bool,      // Whether the user deposits only token X (true) or only token Y
+        amount_in: u64,  // Amount of that token the user deposits
+        min_lp_out: u64, // Minimum amount of LP tokens the user is willing to receive
+        expiration: i64,
+    ) -> Result<()> {
+        // Part of the deposit is swapped through the pool
+        self.config.check_allowed(PAUSE_DEPOSITS | PAUSE_SWAPS)?;
+        require!(amount_in != 0, AmmError::InvalidAmount);
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+        require!(
+            self.mint_lp.supply > 0 && reserve_x > 0 && reserve_y > 0,
+            AmmError::NoLiquidityInPool
+        );
+
+        // Accrue the price that held up to this change into the oracle
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+
+        let mint_in = match is_x {
+            true => &self.mint_x,
+            false => &self.mint_y,
+        };
+
+        // Everything the user sends stays in the vault: part of it is swapped through
+        // the curve and the rest is paired with the swap output as a balanced deposit
+        let received = amount_in - transfer_fee(mint_in, amount_in)?;
+        let pool = self
+            .config
+            .pool_state(reserve_x, reserve_y, self.mint_lp.supply)?;
+        let swap_amount = zap_swap_amount(pool.curve().map_err(AmmError::from)?, is_x, received)?;
+        require!(swap_amount != 0, AmmError::InvalidAmount);
+
+        let quote = quote_swap(&pool, is_x, swap_amount)?;
+        self.config.record_trade(
+            is_x,
+            quote.amount_in,
+            (reserve_x, reserve_y),
+            (quote.reserve_x, quote.reserve_y),
+        )?;
+        self.config
+            .credit_protocol_fee(pool.fee_in_x(is_x), quote.protocol_fee)?;
+
+        // Pool reserves right after the internal swap
+        let (pool_in, pool_out) = match is_x {
+            true => (quote.reserve_x as u128, quote.reserve_y as u128),
+            false => (quote.reserve_y as u128, quote.reserve_x as u128),
+        };
+
+        // LP for the balanced remainder, priced on whichever side binds
+        let supply = self.mint_lp.supply as u128;
+        let lp_in = (received - swap_amount) as u128 * supply / pool_in;
+        let lp_out = quote.amount_out as u128 * supply / pool_out;
+        let lp = u64::try_from(lp_in.min(lp_out)).map_err(|_| AmmError::Overflow)?;
+
+        require!(lp != 0, AmmError::LiquidityLessThanMinimum);
+        require!(lp >= min_lp_out, AmmError::SlippageExceeded);
+
+        self.deposit_tokens(is_x, amount_in)?;
+        self.mint_lp_tokens(self.user_lp.to_account_info(), lp)?;
+
+        let (amount_x, amount_y) = match is_x {
+            true => (amount_in, 0),
+            false => (0, amount_in),
+        };
+        self.emit_deposited(amount_x, amount_y, lp, quote.fee, quote.protocol_fee)
+    }
+
+    // Reports the deposit together with the reserves and LP supply it left behind
+    fn emit_deposited(
+        &mut self,
+        amount_x: u64,
+        amount_y: u64,
+        lp_amount: u64,
+        fee: u64,
+        protocol_fee: u64,
+    ) -> Result<()> {
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.mint_lp.reload()?;
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(LiquidityDeposited {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            fee,
+            protocol_fee,
+            reserve_x,
+            reserve_y,
+            lp_supply: self.mint_lp.supply,
+        });
+
+        Ok(())
     }
 
     pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+        let (from, to, mint, token_program) = match is_x {
             true => (
                 self.user_x.to_account_info(),
                 self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
             ),
             false => (
                 self.user_y.to_account_info(),
                 self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.user.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer(ctx, amount)
+        transfer_checked(ctx, amount, mint.decimals)
     }
 
-    pub fn mint_lp_tokens(&self, amount: u64) -> Result<()> {
+    pub fn mint_lp_tokens(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = MintTo {
             mint: self.mint_lp.to_account_info(),
-            to: self.user_lp.to_account_info(),
+            to,
             authority: self.config.to_account_info(),
         };
 
@@ -154,3 +337,33 @@ impl<'info> Deposit<'info> {
         mint_to(ctx, amount)
     }
 }
+
+// Portion of a one-sided deposit of `amount` to swap so that what is left over matches
+// the pool ratio after the swap. Binary search for the largest such amount, which keeps
+// any rounding dust on the input side
+fn zap_swap_amount(curve: Curve, is_x: bool, amount: u64) -> Result<u64> {
+    let (mut low, mut high) = (0u64, amount);
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+
+        // Price `mid` on a copy of the curve and look at the pool it leaves behind
+        let mut after = curve;
+        let out = after.swap(is_x, mid, 0).map_err(AmmError::from)?.withdraw as u128;
+        let (pool_in, pool_out) = match (is_x, after.reserves()) {
+            (true, (x, y)) => (x as u128, y as u128),
+            (false, (x, y)) => (y as u128, x as u128),
+        };
+
+        // Still holding more of the input token than the pool ratio asks for
+        let excess_in = ((amount - mid) as u128)
+            .checked_mul(pool_out)
+            .ok_or(AmmError::Overflow)?
+            >= out.checked_mul(pool_in).ok_or(AmmError::Overflow)?;
+        match excess_in {
+            true => low = mid,
+            false => high = mid - 1,
+        }
+    }
+
+    Ok(low)
+}
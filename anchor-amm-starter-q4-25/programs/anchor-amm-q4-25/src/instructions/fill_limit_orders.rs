@@ -0,0 +1,319 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{
+    errors::AmmError,
+    events::{LimitOrderFilled, Swapped},
+    quote::quote_swap,
+    state::{Config, LimitOrder, Oracle, PAUSE_SWAPS},
+    utils::transfer_fee,
+};
+
+// Accounts passed in remaining_accounts for every order, in this order:
+// order (mut), order_vault (mut), owner (mut), owner_out (mut)
+pub const FILL_ORDER_ACCOUNTS: usize = 4;
+
+#[derive(Accounts)]
+pub struct FillLimitOrders<'info> {
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> FillLimitOrders<'info> {
+    // Fills, one after the other, the given orders the pool price has crossed. Anyone can
+    // call it and collects the tips of the orders it fills. Orders that can't fill yet
+    // are skipped, but at least one has to
+    pub fn fill_limit_orders(&mut self, orders: &'info [AccountInfo<'info>]) -> Result<()> {
+        self.config.check_allowed(PAUSE_SWAPS)?;
+        require!(
+            !orders.is_empty() && orders.len() % FILL_ORDER_ACCOUNTS == 0,
+            AmmError::InvalidOrder
+        );
+
+        let mut filled = 0;
+        for accounts in orders.chunks(FILL_ORDER_ACCOUNTS) {
+            let order = Account::<LimitOrder>::try_from(&accounts[0])?;
+            let (order_vault, owner, owner_out) = (&accounts[1], &accounts[2], &accounts[3]);
+            require_keys_eq!(order.config, self.config.key(), AmmError::InvalidOrder);
+            require_keys_eq!(owner.key(), order.owner, AmmError::InvalidOrder);
+
+            let (mint_in, mint_out, program_in, program_out) = match order.is_x {
+                true => (
+                    &self.mint_x,
+                    &self.mint_y,
+                    &self.token_program_x,
+                    &self.token_program_y,
+                ),
+                false => (
+                    &self.mint_y,
+                    &self.mint_x,
+                    &self.token_program_y,
+                    &self.token_program_x,
+                ),
+            };
+            for (account, authority, mint, token_program) in [
+                (order_vault, order.key(), mint_in, program_in),
+                (owner_out, order.owner, mint_out, program_out),
+            ] {
+                require_keys_eq!(
+                    account.key(),
+                    get_associated_token_address_with_program_id(
+                        &authority,
+                        &mint.key(),
+                        &token_program.key(),
+                    ),
+                    AmmError::InvalidOrder
+                );
+            }
+
+            if self.fill(&order, order_vault, owner_out)? {
+                self.close_order(order, order_vault, owner)?;
+                filled += 1;
+            }
+        }
+
+        require!(filled > 0, AmmError::OrderNotFillable);
+        Ok(())
+    }
+
+    // Swaps everything in the order vault through the pool like Swap::swap, if that buys
+    // the owner enough. Returns whether it did
+    fn fill(
+        &mut self,
+        order: &Account<'info, LimitOrder>,
+        order_vault: &'info AccountInfo<'info>,
+        owner_out: &'info AccountInfo<'info>,
+    ) -> Result<bool> {
+        let (mint_in, mint_out) = match order.is_x {
+            true => (&self.mint_x, &self.mint_y),
+            false => (&self.mint_y, &self.mint_x),
+        };
+
+        // Anything sent to the vault on top of the order is sold with it
+        let amount_in = InterfaceAccount::<TokenAccount>::try_from(order_vault)?.amount;
+        let received = amount_in - transfer_fee(mint_in, amount_in)?;
+        if received == 0 {
+            return Ok(false);
+        }
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+        require!(reserve_x > 0 && reserve_y > 0, AmmError::NoLiquidityInPool);
+        let pool = self.config.pool_state(reserve_x, reserve_y, 0)?;
+        let quote = quote_swap(&pool, order.is_x, received)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let amount_out = quote.amount_out - transfer_fee(mint_out, quote.amount_out)?;
+        if !order.fills(amount_out, now) {
+            return Ok(false);
+        }
+
+        self.config.record_trade(
+            order.is_x,
+            quote.amount_in,
+            (pool.reserve_x, pool.reserve_y),
+            (quote.reserve_x, quote.reserve_y),
+        )?;
+        self.oracle.update(reserve_x, reserve_y, now)?;
+        self.config
+            .credit_protocol_fee(pool.fee_in_x(order.is_x), quote.protocol_fee)?;
+
+        self.deposit_tokens(order, order_vault, amount_in)?;
+        self.withdraw_tokens(owner_out, !order.is_x, quote.amount_out)?;
+
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user: order.owner,
+            is_x: order.is_x,
+            amount_in,
+            amount_out: quote.amount_out,
+            fee: quote.fee,
+            protocol_fee: quote.protocol_fee,
+            referral_fee: 0,
+            reserve_x,
+            reserve_y,
+        });
+        emit!(LimitOrderFilled {
+            config: self.config.key(),
+            order: order.key(),
+            owner: order.owner,
+            keeper: self.keeper.key(),
+            amount_in,
+            amount_out: quote.amount_out,
+            tip: order.tip,
+        });
+
+        Ok(true)
+    }
+
+    // Pays the tip to the keeper and returns the rent of the order and its vault to the
+    // owner
+    fn close_order(
+        &self,
+        order: Account<'info, LimitOrder>,
+        order_vault: &'info AccountInfo<'info>,
+        owner: &'info AccountInfo<'info>,
+    ) -> Result<()> {
+        let token_program = match order.is_x {
+            true => &self.token_program_x,
+            false => &self.token_program_y,
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: order_vault.clone(),
+            destination: owner.clone(),
+            authority: order.to_account_info(),
+        };
+
+        let id = order.id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            order.config.as_ref(),
+            order.owner.as_ref(),
+            &id,
+            &[order.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        close_account(ctx)?;
+
+        **order.to_account_info().try_borrow_mut_lamports()? -= order.tip;
+        **self.keeper.to_account_info().try_borrow_mut_lamports()? += order.tip;
+
+        order.close(owner.clone())
+    }
+
+    // Moves the escrowed input from the order vault into the pool
+    pub fn deposit_tokens(
+        &self,
+        order: &Account<'info, LimitOrder>,
+        order_vault: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let (to, mint, token_program) = match order.is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: order_vault.clone(),
+            mint: mint.to_account_info(),
+            to,
+            authority: order.to_account_info(),
+        };
+
+        // PDA signing - order vault is owned by the order PDA
+        let id = order.id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            order.config.as_ref(),
+            order.owner.as_ref(),
+            &id,
+            &[order.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    pub fn withdraw_tokens(&self, to: &AccountInfo<'info>, is_x: bool, amount: u64) -> Result<()> {
+        let (from, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to: to.clone(),
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -0,0 +1,261 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked,
+    },
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::{FlashLoanBorrowed, FlashLoanRepaid},
+    state::{Config, PAUSE_SWAPS},
+    utils::transfer_inverse_fee,
+};
+
+// Position of `config` in the FlashLoan accounts, used to match the repay instruction
+const CONFIG_ACCOUNT_INDEX: usize = 3;
+
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = borrower,
+        associated_token::token_program = token_program_x,
+    )]
+    pub borrower_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = borrower,
+        associated_token::token_program = token_program_y,
+    )]
+    pub borrower_y: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: the instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> FlashLoan<'info> {
+    // Lends `amount` of token X (is_x) or Y out of the vault. A repay_flash_loan for this
+    // pool has to follow later in the same transaction, and every other operation is
+    // rejected until it does so nothing can trade against the depleted vault. Borrowing
+    // through a CPI is fine, but the repayment has to be a top-level instruction after
+    // the one that borrows: check_repay only sees top-level instructions in the
+    // instructions sysvar, so a borrow that is repaid through a CPI, even within the same
+    // instruction, fails with FlashLoanNotRepaid
+    pub fn flash_loan(&mut self, is_x: bool, amount: u64) -> Result<()> {
+        self.config.check_allowed(PAUSE_SWAPS)?;
+        require!(amount != 0, AmmError::InvalidAmount);
+        let vault = match is_x {
+            true => &self.vault_x,
+            false => &self.vault_y,
+        };
+        require!(amount <= vault.amount, AmmError::InsufficientBalance);
+
+        self.check_repay()?;
+
+        // Same rate as a swap, rounded up, and paid entirely to the LPs
+        let fee = (amount as u128)
+            .checked_mul(self.config.swap_fee(Clock::get()?.unix_timestamp) as u128)
+            .ok_or(AmmError::Overflow)?
+            .div_ceil(10_000) as u64;
+
+        self.config.flash_loan_amount = amount;
+        self.config.flash_loan_fee = fee;
+        self.config.flash_loan_is_x = is_x;
+
+        self.withdraw_tokens(is_x, amount)?;
+
+        emit!(FlashLoanBorrowed {
+            config: self.config.key(),
+            borrower: self.borrower.key(),
+            is_x,
+            amount,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    // Pays back the outstanding loan plus its fee, reopening the pool. Anyone can repay
+    pub fn repay_flash_loan(&mut self) -> Result<()> {
+        let amount = self.config.flash_loan_amount;
+        require!(amount != 0, AmmError::NoFlashLoan);
+        let fee = self.config.flash_loan_fee;
+        let is_x = self.config.flash_loan_is_x;
+
+        let due = amount.checked_add(fee).ok_or(AmmError::Overflow)?;
+        let (mint, vault_before) = match is_x {
+            true => (&self.mint_x, self.vault_x.amount),
+            false => (&self.mint_y, self.vault_y.amount),
+        };
+        // The vault has to receive the full amount due after any transfer fee
+        let gross = due
+            .checked_add(transfer_inverse_fee(mint, due)?)
+            .ok_or(AmmError::Overflow)?;
+        self.deposit_tokens(is_x, gross)?;
+
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let vault_after = match is_x {
+            true => self.vault_x.amount,
+            false => self.vault_y.amount,
+        };
+        require!(
+            vault_after.saturating_sub(vault_before) >= due,
+            AmmError::FlashLoanUnderpaid
+        );
+
+        self.config.flash_loan_amount = 0;
+        self.config.flash_loan_fee = 0;
+        self.config.flash_loan_is_x = false;
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(FlashLoanRepaid {
+            config: self.config.key(),
+            borrower: self.borrower.key(),
+            is_x,
+            amount,
+            fee,
+            reserve_x,
+            reserve_y,
+        });
+
+        Ok(())
+    }
+
+    // Looks ahead in the transaction for a repay_flash_loan against this pool. In a CPI
+    // the current index is the outer instruction's, so only top-level repayments after
+    // it count; if it doesn't run or fails, the whole transaction with the loan reverts
+    fn check_repay(&self) -> Result<()> {
+        let instructions = self.instructions.to_account_info();
+        let mut index = load_current_index_checked(&instructions)? as usize + 1;
+
+        while let Ok(ix) = load_instruction_at_checked(index, &instructions) {
+            if ix.program_id == crate::ID
+                && ix
+                    .data
+                    .starts_with(crate::instruction::RepayFlashLoan::DISCRIMINATOR)
+                && ix
+                    .accounts
+                    .get(CONFIG_ACCOUNT_INDEX)
+                    .is_some_and(|meta| meta.pubkey == self.config.key())
+            {
+                return Ok(());
+            }
+            index += 1;
+        }
+
+        err!(AmmError::FlashLoanNotRepaid)
+    }
+
+    pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.borrower_x.to_account_info(),
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.borrower_y.to_account_info(),
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.borrower.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                self.borrower_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                self.borrower_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::state::{Config, Oracle, TwapPrice};
+
+#[derive(Accounts)]
+pub struct GetTwap<'info> {
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    #[account(
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> GetTwap<'info> {
+    pub fn get_twap(&self, window: u32) -> Result<TwapPrice> {
+        // Extrapolate the accumulators to now with the current reserves
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.oracle
+            .twap(reserve_x, reserve_y, Clock::get()?.unix_timestamp, window)
+    }
+}
@@ -1,41 +1,73 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Mint, Token, TokenAccount},
+    metadata::{
+        create_metadata_accounts_v3, mpl_token_metadata::types::DataV2, CreateMetadataAccountsV3,
+        Metadata, MetadataAccount,
+    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::{errors::AmmError, state::Config};
+use crate::{
+    errors::AmmError,
+    events::PoolInitialized,
+    quote::{lp_decimals, share_precision},
+    state::{
+        Config, CurveType, FeeSide, Observation, Oracle, CONFIG_VERSION, LP_SYMBOL, MAX_AMP,
+        MAX_FEE, MIN_AMP, OBSERVATIONS,
+    },
+    utils::has_transfer_hook,
+};
 
 #[derive(Accounts)]
 #[instruction(seed: u64)]
 pub struct Initialize<'info> {
     #[account(mut)]
     pub initializer: Signer<'info>,
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
     #[account(
         init,
         payer = initializer,
         seeds = [b"lp", config.key.as_ref()],
         bump,
-        mint::decimals = 6,
+        mint::decimals = lp_decimals(mint_x.decimals, mint_y.decimals),
         mint::authority = config,
+        mint::token_program = token_program,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub mint_lp: InterfaceAccount<'info, Mint>,
     #[account(
         init,
         payer = initializer,
         associated_token::mint = mint_x,
         associated_token::authority = config,
+        associated_token::token_program = token_program_x,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         init,
         payer = initializer,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program_y,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    // Holds the LP tokens locked on the first deposit; owned by the system program,
+    // which nobody can sign for
+    #[account(
+        init,
+        payer = initializer,
+        associated_token::mint = mint_lp,
+        associated_token::authority = system_program,
+        associated_token::token_program = token_program,
+    )]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
     #[account(
         init,
         payer = initializer,
@@ -44,36 +76,255 @@ pub struct Initialize<'info> {
         space = Config::DISCRIMINATOR.len() + Config::INIT_SPACE,
     )]
     pub config: Account<'info, Config>,
-    pub token_program: Program<'info, Token>,
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump,
+        space = Oracle::DISCRIMINATOR.len() + Oracle::INIT_SPACE,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    /// CHECK: token X's metadata, checked by address and only read for its symbol if
+    /// the token metadata program owns it
+    #[account(
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            mint_x.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub metadata_x: UncheckedAccount<'info>,
+    /// CHECK: token Y's metadata, like metadata_x
+    #[account(
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            mint_y.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub metadata_y: UncheckedAccount<'info>,
+    /// CHECK: the LP mint's metadata, checked by address and created by the token
+    /// metadata program
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            mint_lp.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub lp_metadata: UncheckedAccount<'info>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    // The LP token's program, which X's and Y's don't have to match
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_metadata_program: Program<'info, Metadata>,
     pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 impl<'info> Initialize<'info> {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         &mut self,
         seed: u64,
         fee: u16,
+        min_fee: u16,
+        max_fee: u16,
+        protocol_fee: u16,
+        fee_timelock: i64,
+        curve_type: CurveType,
+        amp: u64,
+        fee_side: FeeSide,
+        referral_fee: u16,
         authority: Option<Pubkey>,
         bumps: InitializeBumps,
     ) -> Result<()> {
-        require!(fee <= 10000, AmmError::InvalidFee);
+        require!(fee <= MAX_FEE, AmmError::InvalidFee);
+        // A max_fee of 0 keeps the fee fixed, otherwise it moves between min_fee and max_fee
+        require!(
+            match max_fee {
+                0 => min_fee == 0,
+                _ => min_fee <= fee && fee <= max_fee && max_fee <= MAX_FEE,
+            },
+            AmmError::InvalidFee
+        );
+        // Both are shares of the swap fee, whatever is left goes to the LPs
+        require!(
+            protocol_fee as u32 + referral_fee as u32 <= 10_000,
+            AmmError::InvalidFee
+        );
+        require!(fee_timelock >= 0, AmmError::InvalidTimestamp);
+        // Only StableSwap pools use the amplification coefficient
+        let amp = match curve_type {
+            CurveType::ConstantProduct => 0,
+            CurveType::StableSwap => {
+                require!(
+                    (MIN_AMP..=MAX_AMP).contains(&amp),
+                    AmmError::InvalidAmp
+                );
+                amp
+            }
+        };
         require!(
             self.mint_x.key() != self.mint_y.key(),
             AmmError::InvalidToken
         );
+        // The pool's transfers don't pass the extra accounts a hook program needs
+        require!(
+            !has_transfer_hook(&self.mint_x) && !has_transfer_hook(&self.mint_y),
+            AmmError::TransferHookMint
+        );
 
         self.config.set_inner(Config {
             seed,
             authority,
+            pending_authority: None,
             mint_x: self.mint_x.key(),
             mint_y: self.mint_y.key(),
             fee,
-            locked: false,
+            min_fee,
+            max_fee,
+            volatility: 0,
+            volatility_timestamp: 0,
+            fee_timelock,
+            pending_fee: None,
+            pending_fee_eta: 0,
+            protocol_fee,
+            pending_protocol_fee: None,
+            pending_protocol_fee_eta: 0,
+            protocol_fees_x: 0,
+            protocol_fees_y: 0,
+            concentrated_x: 0,
+            concentrated_y: 0,
+            flash_loan_amount: 0,
+            flash_loan_fee: 0,
+            flash_loan_is_x: false,
+            curve_type,
+            amp_initial: amp,
+            amp_target: amp,
+            amp_ramp_start: 0,
+            amp_ramp_end: 0,
+            max_trade: 0,
+            breaker_threshold: 0,
+            breaker_window: 0,
+            breaker_timestamp: 0,
+            breaker_reserve_x: 0,
+            breaker_reserve_y: 0,
+            breaker_tripped: false,
+            paused: 0,
             config_bump: bumps.config,
             lp_bump: bumps.mint_lp,
+            version: CONFIG_VERSION,
+            lp_decimals: self.mint_lp.decimals,
+            precision: share_precision(self.mint_x.decimals, self.mint_y.decimals),
+            fee_side,
+            referral_fee,
+            farms: 0,
+        });
+
+        self.create_lp_metadata()?;
+
+        self.oracle.set_inner(Oracle {
+            config: self.config.key(),
+            price_x_cumulative: 0,
+            price_y_cumulative: 0,
+            last_timestamp: Clock::get()?.unix_timestamp,
+            index: 0,
+            len: 0,
+            observations: [Observation::default(); OBSERVATIONS],
+            bump: bumps.oracle,
+        });
+
+        emit!(PoolInitialized {
+            config: self.config.key(),
+            initializer: self.initializer.key(),
+            authority,
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            mint_lp: self.mint_lp.key(),
+            fee,
+            min_fee,
+            max_fee,
+            protocol_fee,
+            fee_timelock,
+            curve_type,
+            amp,
+            fee_side,
+            referral_fee,
         });
 
         Ok(())
     }
+
+    // Names the LP token as the initializer asked, e.g. "USDC-SOL LP", so wallets don't
+    // show it as unknown; the pair's mints may have no metadata to derive it from. The
+    // config stays update authority; the URI is set with update_lp_uri
+    // Names the LP token after the pair, e.g. "SOL-USDC LP", so it can't be passed off as
+    // another pool's
+    fn create_lp_metadata(&self) -> Result<()> {
+        let name = format!(
+            "{}-{} LP",
+            mint_symbol(&self.mint_x.key(), &self.metadata_x),
+            mint_symbol(&self.mint_y.key(), &self.metadata_y),
+        );
+
+        let cpi_program = self.token_metadata_program.to_account_info();
+
+        let cpi_accounts = CreateMetadataAccountsV3 {
+            metadata: self.lp_metadata.to_account_info(),
+            mint: self.mint_lp.to_account_info(),
+            mint_authority: self.config.to_account_info(),
+            payer: self.initializer.to_account_info(),
+            update_authority: self.config.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            rent: self.rent.to_account_info(),
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        create_metadata_accounts_v3(
+            ctx,
+            DataV2 {
+                name,
+                symbol: LP_SYMBOL.to_string(),
+                uri: String::new(),
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            },
+            true,
+            true,
+            None,
+        )
+    }
+}
+
+// Symbol in a mint's token metadata, or the first 4 characters of its address when it
+// has none (Token-2022 metadata extensions aren't read). Metaplex caps symbols at 10
+// bytes, so the LP name always fits its 32
+fn mint_symbol(mint: &Pubkey, metadata: &UncheckedAccount) -> String {
+    if metadata.owner == &Metadata::id() {
+        if let Ok(metadata) = MetadataAccount::try_deserialize(&mut &metadata.data.borrow()[..]) {
+            let symbol = metadata.symbol.trim_end_matches('\0').trim();
+            if !symbol.is_empty() {
+                return symbol.to_string();
+            }
+        }
+    }
+    mint.to_string()[..4].to_string()
 }
@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    dynamic_fee::price_movement,
+    errors::AmmError,
+    events::ConcentratedPoolInitialized,
+    state::{ConcentratedPool, Config},
+    tick_math::{price_pair, sqrt_price_at_tick},
+};
+
+#[derive(Accounts)]
+pub struct InitializeConcentrated<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump,
+        space = ConcentratedPool::DISCRIMINATOR.len() + ConcentratedPool::INIT_SPACE,
+    )]
+    pub concentrated_pool: Box<Account<'info, ConcentratedPool>>,
+    #[account(
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConcentrated<'info> {
+    // Enables concentrated liquidity on the pool, starting at the price of `tick`. Once
+    // the pool has reserves, that has to be their price, give or take the swap fee; past
+    // it, the two curves sharing the vaults could be arbitraged against each other
+    pub fn initialize_concentrated(
+        &mut self,
+        tick_spacing: u16,
+        tick: i32,
+        bumps: InitializeConcentratedBumps,
+    ) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(tick_spacing != 0, AmmError::InvalidTick);
+        let sqrt_price = sqrt_price_at_tick(tick)?;
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+        if reserve_x > 0 && reserve_y > 0 {
+            let pool = self.config.pool_state(reserve_x, reserve_y, 0)?;
+            let spot_y = pool
+                .curve()
+                .and_then(|curve| curve.spot_amount_out(true, reserve_x))
+                .map_err(AmmError::from)?;
+            require!(
+                price_movement((reserve_x, spot_y), price_pair(sqrt_price)) <= pool.fee as u64,
+                AmmError::InvalidTick
+            );
+        }
+
+        self.concentrated_pool.set_inner(ConcentratedPool {
+            config: self.config.key(),
+            tick_spacing,
+            sqrt_price,
+            tick,
+            liquidity: 0,
+            fee_growth_global_x: 0,
+            fee_growth_global_y: 0,
+            bump: bumps.concentrated_pool,
+        });
+
+        emit!(ConcentratedPoolInitialized {
+            config: self.config.key(),
+            tick_spacing,
+            sqrt_price,
+            tick,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    events::TickArrayInitialized,
+    state::{ConcentratedPool, TickArray, TickState, TICK_ARRAY_SIZE},
+};
+
+#[derive(Accounts)]
+#[instruction(start_tick: i32)]
+pub struct InitializeTickArray<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"concentrated", concentrated_pool.config.as_ref()],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Box<Account<'info, ConcentratedPool>>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"tick_array",
+            concentrated_pool.key().as_ref(),
+            start_tick.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = TickArray::DISCRIMINATOR.len() + TickArray::INIT_SPACE,
+    )]
+    pub tick_array: Box<Account<'info, TickArray>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeTickArray<'info> {
+    // Creates the tick array starting at `start_tick`, which positions with a bound in it
+    // and swaps moving through it need. Anyone can create it; the payer's rent is what
+    // keeps the number of ticks in use from being a cheap resource to exhaust
+    pub fn initialize_tick_array(
+        &mut self,
+        start_tick: i32,
+        bumps: InitializeTickArrayBumps,
+    ) -> Result<()> {
+        require!(
+            start_tick == self.concentrated_pool.tick_array_start(start_tick),
+            AmmError::InvalidTick
+        );
+
+        self.tick_array.set_inner(TickArray {
+            pool: self.concentrated_pool.key(),
+            start_tick,
+            ticks: [TickState::default(); TICK_ARRAY_SIZE],
+            bump: bumps.tick_array,
+        });
+
+        emit!(TickArrayInitialized {
+            pool: self.concentrated_pool.key(),
+            tick_array: self.tick_array.key(),
+            start_tick,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    events::ConfigMigrated,
+    state::{Config, Observation, Oracle, OBSERVATIONS},
+};
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: a Config in any layout version, checked by owner and discriminator since it
+    /// can't be deserialized as the current one yet
+    #[account(mut, owner = crate::ID)]
+    pub config: UncheckedAccount<'info>,
+    // Pools created before the TWAP oracle get one here, since every swap updates it
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump,
+        space = Oracle::DISCRIMINATOR.len() + Oracle::INIT_SPACE,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    // ConfigV0 pools predate the locked minimum liquidity, but Deposit takes the account
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_lp,
+        associated_token::authority = system_program,
+        associated_token::token_program = token_program,
+    )]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> MigrateConfig<'info> {
+    // Upgrades a Config account to CONFIG_VERSION, growing it if needed. Anyone can pay
+    // for it, so pools without an authority can be migrated too
+    pub fn migrate_config(&mut self, bumps: MigrateConfigBumps) -> Result<()> {
+        let info = self.config.to_account_info();
+
+        let config = Config::migrate(&info.try_borrow_data()?)?;
+
+        if self.oracle.config == Pubkey::default() {
+            self.oracle.set_inner(Oracle {
+                config: info.key(),
+                price_x_cumulative: 0,
+                price_y_cumulative: 0,
+                last_timestamp: Clock::get()?.unix_timestamp,
+                index: 0,
+                len: 0,
+                observations: [Observation::default(); OBSERVATIONS],
+                bump: bumps.oracle,
+            });
+        }
+
+        let space = Config::DISCRIMINATOR.len() + Config::INIT_SPACE;
+        if info.data_len() < space {
+            let rent = Rent::get()?
+                .minimum_balance(space)
+                .saturating_sub(info.lamports());
+            if rent > 0 {
+                let cpi_accounts = Transfer {
+                    from: self.payer.to_account_info(),
+                    to: info.clone(),
+                };
+                let ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+                transfer(ctx, rent)?;
+            }
+            info.resize(space)?;
+        }
+
+        let mut data = info.try_borrow_mut_data()?;
+        config.serialize(&mut &mut data[Config::DISCRIMINATOR.len()..])?;
+
+        emit!(ConfigMigrated {
+            config: info.key(),
+            version: config.version,
+            paused: config.paused,
+        });
+
+        Ok(())
+    }
+}
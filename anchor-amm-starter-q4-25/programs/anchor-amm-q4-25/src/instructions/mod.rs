@@ -1,11 +1,53 @@
+pub mod accept_authority;
+pub mod cancel_limit_order;
+pub mod close_farm;
+pub mod close_pool;
+pub mod close_position;
+pub mod collect_protocol_fees;
+pub mod create_farm;
 pub mod deposit;
+pub mod fill_limit_orders;
+pub mod flash_loan;
+pub mod get_twap;
 pub mod initialize;
+pub mod initialize_concentrated;
+pub mod initialize_tick_array;
+pub mod migrate_config;
+pub mod modify_position;
+pub mod modify_stake;
+pub mod open_position;
+pub mod place_limit_order;
+pub mod register_pool;
+pub mod route_swap;
 pub mod swap;
+pub mod swap_concentrated;
 pub mod update;
+pub mod update_lp_metadata;
 pub mod withdraw;
 
+pub use accept_authority::*;
+pub use cancel_limit_order::*;
+pub use close_farm::*;
+pub use close_pool::*;
+pub use close_position::*;
+pub use collect_protocol_fees::*;
+pub use create_farm::*;
 pub use deposit::*;
+pub use fill_limit_orders::*;
+pub use flash_loan::*;
+pub use get_twap::*;
 pub use initialize::*;
+pub use initialize_concentrated::*;
+pub use initialize_tick_array::*;
+pub use migrate_config::*;
+pub use modify_position::*;
+pub use modify_stake::*;
+pub use open_position::*;
+pub use place_limit_order::*;
+pub use register_pool::*;
+pub use route_swap::*;
 pub use swap::*;
+pub use swap_concentrated::*;
 pub use update::*;
+pub use update_lp_metadata::*;
 pub use withdraw::*;
@@ -0,0 +1,316 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::{PositionFeesCollected, PositionLiquidityChanged},
+    state::{ConcentratedPool, Config, Position, TickArray, PAUSE_DEPOSITS, PAUSE_WITHDRAWALS},
+    tick_math::MAX_LIQUIDITY,
+    utils::{transfer_fee, transfer_inverse_fee},
+};
+
+#[derive(Accounts)]
+pub struct ModifyPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Box<Account<'info, ConcentratedPool>>,
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = position.pool == concentrated_pool.key() @ AmmError::InvalidAuthority,
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        mut,
+        seeds = [
+            b"tick_array",
+            concentrated_pool.key().as_ref(),
+            tick_array_lower.start_tick.to_le_bytes().as_ref(),
+        ],
+        bump = tick_array_lower.bump,
+    )]
+    pub tick_array_lower: Box<Account<'info, TickArray>>,
+    // The same account as tick_array_lower when it holds both of the position's ticks
+    #[account(
+        mut,
+        seeds = [
+            b"tick_array",
+            concentrated_pool.key().as_ref(),
+            tick_array_upper.start_tick.to_le_bytes().as_ref(),
+        ],
+        bump = tick_array_upper.bump,
+    )]
+    pub tick_array_upper: Box<Account<'info, TickArray>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program_x,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program_y,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> ModifyPosition<'info> {
+    pub fn increase_liquidity(
+        &mut self,
+        liquidity: u128, // Liquidity to add to the position
+        max_x: u64,      // Maximum amount of token X that the user is willing to deposit
+        max_y: u64,      // Maximum amount of token Y that the user is willing to deposit
+        expiration: i64,
+    ) -> Result<()> {
+        self.check(PAUSE_DEPOSITS, liquidity, expiration)?;
+
+        let (amount_x, amount_y) = self.modify_position(liquidity as i128)?;
+
+        // The vaults must receive exactly (x, y), so gross the transfers up by any
+        // Token-2022 transfer fee the user pays on the way in
+        let x = amount_x
+            .checked_add(transfer_inverse_fee(&self.mint_x, amount_x)?)
+            .ok_or(AmmError::Overflow)?;
+        let y = amount_y
+            .checked_add(transfer_inverse_fee(&self.mint_y, amount_y)?)
+            .ok_or(AmmError::Overflow)?;
+        require!(x <= max_x && y <= max_y, AmmError::SlippageExceeded);
+
+        self.config.update_concentrated(true, amount_x as i128)?;
+        self.config.update_concentrated(false, amount_y as i128)?;
+
+        if x > 0 {
+            self.deposit_tokens(true, x)?;
+        }
+        if y > 0 {
+            self.deposit_tokens(false, y)?;
+        }
+
+        self.emit_liquidity_changed(liquidity as i128, amount_x, amount_y);
+        Ok(())
+    }
+
+    pub fn decrease_liquidity(
+        &mut self,
+        liquidity: u128, // Liquidity to remove from the position
+        min_x: u64,      // Minimum amount of token X that the user wants to receive
+        min_y: u64,      // Minimum amount of token Y that the user wants to receive
+        expiration: i64,
+    ) -> Result<()> {
+        self.check(PAUSE_WITHDRAWALS, liquidity, expiration)?;
+
+        let (amount_x, amount_y) = self.modify_position(-(liquidity as i128))?;
+
+        // Slippage check against what actually lands in the user's accounts
+        let received_x = amount_x - transfer_fee(&self.mint_x, amount_x)?;
+        let received_y = amount_y - transfer_fee(&self.mint_y, amount_y)?;
+        require!(
+            received_x >= min_x && received_y >= min_y,
+            AmmError::SlippageExceeded
+        );
+
+        self.config.update_concentrated(true, -(amount_x as i128))?;
+        self.config
+            .update_concentrated(false, -(amount_y as i128))?;
+
+        if amount_x > 0 {
+            self.withdraw_tokens(true, amount_x)?;
+        }
+        if amount_y > 0 {
+            self.withdraw_tokens(false, amount_y)?;
+        }
+
+        self.emit_liquidity_changed(-(liquidity as i128), amount_x, amount_y);
+        Ok(())
+    }
+
+    pub fn collect_fees(&mut self) -> Result<()> {
+        self.config.check_allowed(PAUSE_WITHDRAWALS)?;
+
+        // Credit the fees earned since the last update first
+        self.modify_position(0)?;
+
+        let amount_x = self.position.fees_owed_x;
+        let amount_y = self.position.fees_owed_y;
+        self.position.fees_owed_x = 0;
+        self.position.fees_owed_y = 0;
+
+        self.config.update_concentrated(true, -(amount_x as i128))?;
+        self.config
+            .update_concentrated(false, -(amount_y as i128))?;
+
+        if amount_x > 0 {
+            self.withdraw_tokens(true, amount_x)?;
+        }
+        if amount_y > 0 {
+            self.withdraw_tokens(false, amount_y)?;
+        }
+
+        emit!(PositionFeesCollected {
+            config: self.config.key(),
+            position: self.position.key(),
+            owner: self.owner.key(),
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    // Applies the liquidity change to the pool and the position's tick arrays
+    fn modify_position(&mut self, delta: i128) -> Result<(u64, u64)> {
+        let mut arrays = vec![(**self.tick_array_lower).clone()];
+        if self.tick_array_upper.key() != self.tick_array_lower.key() {
+            arrays.push((**self.tick_array_upper).clone());
+        }
+
+        let amounts =
+            self.concentrated_pool
+                .modify_position(&mut arrays, &mut self.position, delta)?;
+
+        // Both accounts are written back, so a shared array must get the same data twice
+        self.tick_array_lower.set_inner(arrays[0].clone());
+        self.tick_array_upper
+            .set_inner(arrays[arrays.len() - 1].clone());
+        Ok(amounts)
+    }
+
+    // Shared pause/expiry/amount checks
+    fn check(&self, operation: u8, liquidity: u128, expiration: i64) -> Result<()> {
+        self.config.check_allowed(operation)?;
+        require!(
+            liquidity != 0 && liquidity <= MAX_LIQUIDITY,
+            AmmError::InvalidAmount
+        );
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+        Ok(())
+    }
+
+    fn emit_liquidity_changed(&self, liquidity_delta: i128, amount_x: u64, amount_y: u64) {
+        emit!(PositionLiquidityChanged {
+            config: self.config.key(),
+            position: self.position.key(),
+            owner: self.owner.key(),
+            liquidity_delta,
+            amount_x,
+            amount_y,
+            position_liquidity: self.position.liquidity,
+            pool_liquidity: self.concentrated_pool.liquidity,
+        });
+    }
+
+    pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.user_x.to_account_info(),
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.user_y.to_account_info(),
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.owner.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                self.user_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                self.user_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -0,0 +1,216 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::{RewardsHarvested, StakeChanged},
+    state::{Config, Farm, Stake},
+};
+
+#[derive(Accounts)]
+pub struct ModifyStake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+        mint::token_program = token_program,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = config,
+        has_one = reward_mint,
+        seeds = [b"farm", config.key().as_ref(), reward_mint.key().as_ref()],
+        bump = farm.bump,
+    )]
+    pub farm: Account<'info, Farm>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"stake", farm.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = Stake::DISCRIMINATOR.len() + Stake::INIT_SPACE,
+    )]
+    pub stake: Account<'info, Stake>,
+    #[account(
+        mut,
+        associated_token::mint = mint_lp,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_lp,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub user_lp: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = reward_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub user_reward: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ModifyStake<'info> {
+    pub fn stake_lp(&mut self, amount: u64, bumps: ModifyStakeBumps) -> Result<()> {
+        require!(
+            amount != 0 && amount <= i64::MAX as u64,
+            AmmError::InvalidAmount
+        );
+
+        // First stake into this farm
+        if self.stake.farm == Pubkey::default() {
+            self.stake.set_inner(Stake {
+                farm: self.farm.key(),
+                owner: self.owner.key(),
+                amount: 0,
+                reward_per_share: 0,
+                rewards_owed: 0,
+                bump: bumps.stake,
+            });
+        }
+
+        self.update(amount as i64)?;
+        self.deposit_lp(amount)?;
+
+        self.emit_stake_changed(amount as i64);
+        Ok(())
+    }
+
+    // Takes staked LP tokens back out. Earned rewards stay owed until harvested
+    pub fn unstake_lp(&mut self, amount: u64) -> Result<()> {
+        require!(
+            amount != 0 && amount <= i64::MAX as u64,
+            AmmError::InvalidAmount
+        );
+
+        self.update(-(amount as i64))?;
+        self.withdraw_tokens(true, amount)?;
+
+        self.emit_stake_changed(-(amount as i64));
+        Ok(())
+    }
+
+    pub fn harvest(&mut self) -> Result<()> {
+        // Credit the rewards earned since the last update first
+        self.update(0)?;
+
+        let amount = self.stake.rewards_owed;
+        self.stake.rewards_owed = 0;
+        self.farm.rewards_owed = self
+            .farm
+            .rewards_owed
+            .checked_sub(amount)
+            .ok_or(AmmError::Underflow)?;
+
+        if amount > 0 {
+            self.withdraw_tokens(false, amount)?;
+        }
+
+        emit!(RewardsHarvested {
+            farm: self.farm.key(),
+            owner: self.owner.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    // Brings the farm up to date and moves `delta` LP tokens in or out of the stake
+    fn update(&mut self, delta: i64) -> Result<()> {
+        self.farm.accrue(Clock::get()?.unix_timestamp)?;
+        self.farm.modify_stake(&mut self.stake, delta)
+    }
+
+    fn emit_stake_changed(&self, amount: i64) {
+        emit!(StakeChanged {
+            farm: self.farm.key(),
+            owner: self.owner.key(),
+            amount,
+            stake: self.stake.amount,
+            total_staked: self.farm.total_staked,
+        });
+    }
+
+    pub fn deposit_lp(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.user_lp.to_account_info(),
+            mint: self.mint_lp.to_account_info(),
+            to: self.lp_vault.to_account_info(),
+            authority: self.owner.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, self.mint_lp.decimals)
+    }
+
+    // Pays out staked LP tokens (is_lp) or rewards
+    pub fn withdraw_tokens(&self, is_lp: bool, amount: u64) -> Result<()> {
+        let (from, to, mint) = match is_lp {
+            true => (
+                self.lp_vault.to_account_info(),
+                self.user_lp.to_account_info(),
+                &self.mint_lp,
+            ),
+            false => (
+                self.reward_vault.to_account_info(),
+                self.user_reward.to_account_info(),
+                &self.reward_mint,
+            ),
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.farm.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by farm PDA
+        let config = self.config.key();
+        let reward_mint = self.reward_mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"farm",
+            config.as_ref(),
+            reward_mint.as_ref(),
+            &[self.farm.bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::PositionOpened,
+    state::{ConcentratedPool, Config, Position, PAUSE_DEPOSITS},
+};
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Box<Account<'info, ConcentratedPool>>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            b"position",
+            concentrated_pool.key().as_ref(),
+            owner.key().as_ref(),
+            tick_lower.to_le_bytes().as_ref(),
+            tick_upper.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = Position::DISCRIMINATOR.len() + Position::INIT_SPACE,
+    )]
+    pub position: Account<'info, Position>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> OpenPosition<'info> {
+    // Creates an empty position over [tick_lower, tick_upper); liquidity is added with
+    // increase_liquidity
+    pub fn open_position(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        bumps: OpenPositionBumps,
+    ) -> Result<()> {
+        self.config.check_allowed(PAUSE_DEPOSITS)?;
+        self.concentrated_pool.check_ticks(tick_lower, tick_upper)?;
+
+        self.position.set_inner(Position {
+            pool: self.concentrated_pool.key(),
+            owner: self.owner.key(),
+            tick_lower,
+            tick_upper,
+            liquidity: 0,
+            fee_growth_inside_x: 0,
+            fee_growth_inside_y: 0,
+            fees_owed_x: 0,
+            fees_owed_y: 0,
+            bump: bumps.position,
+        });
+
+        emit!(PositionOpened {
+            config: self.config.key(),
+            position: self.position.key(),
+            owner: self.owner.key(),
+            tick_lower,
+            tick_upper,
+        });
+
+        Ok(())
+    }
+}
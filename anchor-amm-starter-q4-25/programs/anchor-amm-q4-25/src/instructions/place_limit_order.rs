@@ -0,0 +1,169 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::LimitOrderPlaced,
+    state::{Config, LimitOrder},
+    utils::transfer_fee,
+};
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct PlaceLimitOrder<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_in,
+    )]
+    pub mint_in: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_out,
+    )]
+    pub mint_out: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            b"order",
+            config.key().as_ref(),
+            owner.key().as_ref(),
+            id.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = LimitOrder::DISCRIMINATOR.len() + LimitOrder::INIT_SPACE,
+    )]
+    pub order: Account<'info, LimitOrder>,
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = mint_in,
+        associated_token::authority = order,
+        associated_token::token_program = token_program_in,
+    )]
+    pub order_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_in,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program_in,
+    )]
+    pub owner_in: InterfaceAccount<'info, TokenAccount>,
+    // Created here so a fill never has to pay for it
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = mint_out,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program_out,
+    )]
+    pub owner_out: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_in: Interface<'info, TokenInterface>,
+    pub token_program_out: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PlaceLimitOrder<'info> {
+    // Escrows `amount_in` to be sold for at least `min_amount_out` once the pool price
+    // gets there. `tip` lamports go to whoever fills it
+    pub fn place_limit_order(
+        &mut self,
+        id: u64,
+        amount_in: u64,
+        min_amount_out: u64,
+        tip: u64,
+        expiration: i64,
+        bumps: PlaceLimitOrderBumps,
+    ) -> Result<()> {
+        require!(
+            amount_in != 0 && min_amount_out != 0,
+            AmmError::InvalidAmount
+        );
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+
+        let (mint_x, mint_y) = (self.config.mint_x, self.config.mint_y);
+        let is_x = match (self.mint_in.key(), self.mint_out.key()) {
+            pair if pair == (mint_x, mint_y) => true,
+            pair if pair == (mint_y, mint_x) => false,
+            _ => return err!(AmmError::InvalidToken),
+        };
+
+        // The order sells what actually arrives in its vault
+        let received = amount_in - transfer_fee(&self.mint_in, amount_in)?;
+        require!(received != 0, AmmError::InvalidAmount);
+
+        self.order.set_inner(LimitOrder {
+            config: self.config.key(),
+            owner: self.owner.key(),
+            id,
+            is_x,
+            amount_in: received,
+            min_amount_out,
+            tip,
+            expiration,
+            bump: bumps.order,
+        });
+
+        self.deposit_tokens(amount_in)?;
+        if tip > 0 {
+            self.deposit_tip(tip)?;
+        }
+
+        emit!(LimitOrderPlaced {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.owner.key(),
+            is_x,
+            amount_in: received,
+            min_amount_out,
+            tip,
+            expiration,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit_tokens(&self, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program_in.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.owner_in.to_account_info(),
+            mint: self.mint_in.to_account_info(),
+            to: self.order_vault.to_account_info(),
+            authority: self.owner.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, self.mint_in.decimals)
+    }
+
+    // The tip sits in the order account next to its rent
+    fn deposit_tip(&self, tip: u64) -> Result<()> {
+        let cpi_program = self.system_program.to_account_info();
+
+        let cpi_accounts = Transfer {
+            from: self.owner.to_account_info(),
+            to: self.order.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer(ctx, tip)
+    }
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    events::PoolRegistered,
+    state::{Config, PoolEntry, CONFIG_VERSION},
+};
+
+#[derive(Accounts)]
+pub struct RegisterPool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    // Registry entry for the sorted mint pair, fee tier and curve; `init` fails if
+    // another pool already claimed it
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"pool",
+            config.mint_x.min(config.mint_y).as_ref(),
+            config.mint_x.max(config.mint_y).as_ref(),
+            config.fee.to_le_bytes().as_ref(),
+            [config.curve_type as u8].as_ref(),
+        ],
+        bump,
+        space = PoolEntry::DISCRIMINATOR.len() + PoolEntry::INIT_SPACE,
+    )]
+    pub pool_entry: Account<'info, PoolEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RegisterPool<'info> {
+    // Anyone can register a pool, but only once nobody can pause it, change its fees or
+    // close it, so routers never get pointed at a pool someone still controls
+    pub fn register_pool(&mut self, bumps: RegisterPoolBumps) -> Result<()> {
+        require!(
+            self.config.version == CONFIG_VERSION,
+            AmmError::InvalidConfigVersion
+        );
+        require!(
+            self.config.authority.is_none(),
+            AmmError::AuthorityNotRenounced
+        );
+
+        self.pool_entry.set_inner(PoolEntry {
+            config: self.config.key(),
+            mint_x: self.config.mint_x,
+            mint_y: self.config.mint_y,
+            fee: self.config.fee,
+            curve_type: self.config.curve_type,
+            bump: bumps.pool_entry,
+        });
+
+        emit!(PoolRegistered {
+            config: self.config.key(),
+            pool_entry: self.pool_entry.key(),
+            mint_x: self.config.mint_x,
+            mint_y: self.config.mint_y,
+            fee: self.config.fee,
+            curve_type: self.config.curve_type,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::Swapped,
+    quote::quote_swap,
+    state::{Config, Oracle, PAUSE_SWAPS},
+    utils::transfer_fee,
+};
+
+// Accounts passed in remaining_accounts for every hop, in this order:
+// config (mut), oracle (mut), mint_x, mint_y, vault_x (mut), vault_y (mut)
+pub const ROUTE_HOP_ACCOUNTS: usize = 6;
+
+#[derive(Accounts)]
+pub struct RouteSwap<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_in,
+    )]
+    pub mint_in: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_out,
+    )]
+    pub mint_out: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_in,
+        associated_token::authority = user,
+        associated_token::token_program = token_program_in,
+    )]
+    pub user_in: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_out,
+        associated_token::authority = user,
+        associated_token::token_program = token_program_out,
+    )]
+    pub user_out: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_in: Interface<'info, TokenInterface>,
+    pub token_program_out: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RouteSwap<'info> {
+    pub fn route_swap(
+        &mut self,
+        hops: &'info [AccountInfo<'info>],
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        require!(amount_in != 0, AmmError::InvalidAmount);
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+        require!(
+            !hops.is_empty() && hops.len() % ROUTE_HOP_ACCOUNTS == 0,
+            AmmError::InvalidRoute
+        );
+
+        // Output of the previous hop still sitting in its vault: (pool, vault, mint, amount)
+        let mut pending: Option<(
+            Account<'info, Config>,
+            &'info AccountInfo<'info>,
+            InterfaceAccount<'info, Mint>,
+            u64,
+        )> = None;
+        let mut current_mint = self.mint_in.key();
+        let mut amount = amount_in;
+
+        for hop in hops.chunks(ROUTE_HOP_ACCOUNTS) {
+            let mut config = Account::<Config>::try_from(&hop[0])?;
+            let config_key = Pubkey::create_program_address(
+                &[
+                    b"config",
+                    &config.seed.to_le_bytes(),
+                    &[config.config_bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| AmmError::BumpError)?;
+            require_keys_eq!(config.key(), config_key, AmmError::InvalidRoute);
+            config.check_allowed(PAUSE_SWAPS)?;
+
+            let mut oracle = Account::<Oracle>::try_from(&hop[1])?;
+            require_keys_eq!(oracle.config, config.key(), AmmError::InvalidRoute);
+
+            require_keys_eq!(hop[2].key(), config.mint_x, AmmError::InvalidToken);
+            require_keys_eq!(hop[3].key(), config.mint_y, AmmError::InvalidToken);
+
+            // Each hop must consume the token produced by the previous one
+            let is_x = if current_mint == config.mint_x {
+                true
+            } else if current_mint == config.mint_y {
+                false
+            } else {
+                return err!(AmmError::InvalidRoute);
+            };
+
+            let (mint_in, mint_out, vault_in_info, vault_out_info) = match is_x {
+                true => (&hop[2], &hop[3], &hop[4], &hop[5]),
+                false => (&hop[3], &hop[2], &hop[5], &hop[4]),
+            };
+            let mint_in = InterfaceAccount::<Mint>::try_from(mint_in)?;
+            let mint_out = InterfaceAccount::<Mint>::try_from(mint_out)?;
+
+            for (vault, mint) in [(vault_in_info, &mint_in), (vault_out_info, &mint_out)] {
+                require_keys_eq!(
+                    vault.key(),
+                    get_associated_token_address_with_program_id(
+                        &config.key(),
+                        &mint.key(),
+                        self.token_program(mint)?.key,
+                    ),
+                    AmmError::InvalidRoute
+                );
+            }
+
+            // Move this hop's input into its vault, straight from the user on the first
+            // hop and from the previous pool's vault afterwards
+            match pending.take() {
+                None => self.deposit_tokens(vault_in_info, amount)?,
+                Some((prev_config, prev_vault, prev_mint, prev_amount)) => self.withdraw_tokens(
+                    &prev_config,
+                    prev_vault,
+                    &prev_mint,
+                    vault_in_info,
+                    prev_amount,
+                )?,
+            }
+            let received = amount - transfer_fee(&mint_in, amount)?;
+            require!(received != 0, AmmError::InvalidAmount);
+
+            // Load the vaults after the transfer and take the incoming amount back out
+            let vault_in = InterfaceAccount::<TokenAccount>::try_from(vault_in_info)?;
+            let vault_out = InterfaceAccount::<TokenAccount>::try_from(vault_out_info)?;
+            let vault_in_amount = vault_in
+                .amount
+                .checked_sub(received)
+                .ok_or(AmmError::Underflow)?;
+            let (vault_x_amount, vault_y_amount) = match is_x {
+                true => (vault_in_amount, vault_out.amount),
+                false => (vault_out.amount, vault_in_amount),
+            };
+
+            let (reserve_x, reserve_y) = config.reserves(vault_x_amount, vault_y_amount)?;
+            require!(
+                reserve_x > 0 && reserve_y > 0,
+                AmmError::NoLiquidityInPool
+            );
+
+            oracle.update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+            oracle.exit(&crate::ID)?;
+
+            let pool = config.pool_state(reserve_x, reserve_y, 0)?;
+            let quote = quote_swap(&pool, is_x, received)?;
+
+            // Slippage is only enforced on the final output
+            require!(quote.amount_out != 0, AmmError::InvalidAmount);
+            config.record_trade(
+                is_x,
+                quote.amount_in,
+                (reserve_x, reserve_y),
+                (quote.reserve_x, quote.reserve_y),
+            )?;
+
+            config.credit_protocol_fee(pool.fee_in_x(is_x), quote.protocol_fee)?;
+            config.exit(&crate::ID)?;
+
+            // The output is still in vault_out until the next transfer moves it on
+            let vault_out_amount = vault_out
+                .amount
+                .checked_sub(quote.amount_out)
+                .ok_or(AmmError::Underflow)?;
+            let (reserve_x, reserve_y) = match is_x {
+                true => config.reserves(vault_in.amount, vault_out_amount)?,
+                false => config.reserves(vault_out_amount, vault_in.amount)?,
+            };
+            emit!(Swapped {
+                config: config.key(),
+                user: self.user.key(),
+                is_x,
+                amount_in: amount,
+                amount_out: quote.amount_out,
+                fee: quote.fee,
+                protocol_fee: quote.protocol_fee,
+                referral_fee: 0,
+                reserve_x,
+                reserve_y,
+            });
+
+            current_mint = mint_out.key();
+            amount = quote.amount_out;
+            pending = Some((config, vault_out_info, mint_out, amount));
+        }
+
+        require_keys_eq!(current_mint, self.mint_out.key(), AmmError::InvalidRoute);
+
+        let (last_config, last_vault, last_mint, last_amount) =
+            pending.ok_or(AmmError::InvalidRoute)?;
+
+        // End-to-end slippage check on what the user actually receives
+        require!(
+            last_amount - transfer_fee(&last_mint, last_amount)? >= min_amount_out,
+            AmmError::SlippageExceeded
+        );
+
+        let user_out = self.user_out.to_account_info();
+        self.withdraw_tokens(&last_config, last_vault, &last_mint, &user_out, last_amount)
+    }
+
+    // The route only passes the programs of mint_in and mint_out, so the tokens in
+    // between have to be on one of those
+    fn token_program(&self, mint: &InterfaceAccount<'info, Mint>) -> Result<AccountInfo<'info>> {
+        let owner = mint.to_account_info().owner;
+        [&self.token_program_in, &self.token_program_out]
+            .into_iter()
+            .find(|program| program.key() == *owner)
+            .map(|program| program.to_account_info())
+            .ok_or(error!(AmmError::InvalidRoute))
+    }
+
+    pub fn deposit_tokens(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let cpi_program = self.token_program_in.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.user_in.to_account_info(),
+            mint: self.mint_in.to_account_info(),
+            to: to.clone(),
+            authority: self.user.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, self.mint_in.decimals)
+    }
+
+    pub fn withdraw_tokens(
+        &self,
+        config: &Account<'info, Config>,
+        from: &AccountInfo<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_program = self.token_program(mint)?;
+
+        let cpi_accounts = TransferChecked {
+            from: from.clone(),
+            mint: mint.to_account_info(),
+            to: to.clone(),
+            authority: config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by the hop's config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &config.seed.to_le_bytes(),
+            &[config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
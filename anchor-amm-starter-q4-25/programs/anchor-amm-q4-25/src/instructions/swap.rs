@@ -1,50 +1,77 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{transfer, Mint, Token, TokenAccount, Transfer},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
-use constant_product_curve::{ConstantProduct, LiquidityPair};
 
-use crate::{errors::AmmError, state::Config};
+use crate::{
+    errors::AmmError,
+    events::Swapped,
+    quote::{quote_swap, quote_swap_exact_out, PoolState, SwapQuote},
+    state::{Config, Oracle, PAUSE_SWAPS},
+    utils::{transfer_fee, transfer_inverse_fee},
+};
 
 #[derive(Accounts)]
 pub struct Swap<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
     #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = config,
+        associated_token::token_program = token_program_x,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program_y,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = user,
+        associated_token::token_program = token_program_x,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program_y,
     )]
-    pub user_y: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    // Token account of an aggregator or other referrer, in the token the pool takes its
+    // fee in. Gets Config::referral_fee of the swap fee
+    #[account(mut)]
+    pub referrer: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -57,86 +84,217 @@ impl<'info> Swap<'info> {
         min_amount_out: u64,
         expiration: i64,
     ) -> Result<()> {
-        require!(self.config.locked == false, AmmError::PoolLocked);
         require!(amount_in != 0, AmmError::InvalidAmount);
+        let pool = self.init_pool(expiration)?;
+
+        let (mint_in, mint_out) = match is_x {
+            true => (&self.mint_x, &self.mint_y),
+            false => (&self.mint_y, &self.mint_x),
+        };
+
+        // Price the swap on what the vault actually receives after any transfer fee
+        let received = amount_in - transfer_fee(mint_in, amount_in)?;
+        require!(received != 0, AmmError::InvalidAmount);
+
+        // Calculate swap output using the pool's curve
+        let quote = quote_swap(&pool, is_x, received)?;
+
+        // The user must still get at least min_amount_out once the outbound fee is withheld
         require!(
-            Clock::get()?.unix_timestamp < expiration,
-            AmmError::OfferExpired
-        );
-        require!(
-            self.vault_x.amount > 0 && self.vault_y.amount > 0,
-            AmmError::NoLiquidityInPool
+            quote.amount_out - transfer_fee(mint_out, quote.amount_out)? >= min_amount_out,
+            AmmError::SlippageExceeded
         );
 
-        // Initialize the constant product curve with current pool state
-        let mut curve = ConstantProduct::init(
-            self.vault_x.amount,
-            self.vault_y.amount,
-            0, // LP supply not needed for swap calculation
-            self.config.fee,
-            None,
-        )
-        .map_err(|_| AmmError::CurveError)?;
+        self.settle(&pool, is_x, amount_in, &quote)
+    }
+
+    pub fn swap_exact_out(
+        &mut self,
+        is_x: bool,
+        amount_out: u64,    // Amount of the output token the user wants to receive
+        max_amount_in: u64, // Maximum amount of the input token the user is willing to pay
+        expiration: i64,
+    ) -> Result<()> {
+        require!(amount_out != 0, AmmError::InvalidAmount);
+        let pool = self.init_pool(expiration)?;
 
-        // Determine swap direction using LiquidityPair enum
-        let pair = match is_x {
-            true => LiquidityPair::X,
-            false => LiquidityPair::Y,
+        let (mint_in, mint_out) = match is_x {
+            true => (&self.mint_x, &self.mint_y),
+            false => (&self.mint_y, &self.mint_x),
         };
 
-        // Calculate swap output using constant product curve
-        let result = curve
-            .swap(pair, amount_in, min_amount_out)
-            .map_err(|_| AmmError::CurveError)?;
+        // The vault has to send enough for the user to net amount_out after any transfer fee
+        let gross_out = amount_out
+            .checked_add(transfer_inverse_fee(mint_out, amount_out)?)
+            .ok_or(AmmError::Overflow)?;
+
+        // Input the vault needs to receive, and what the user sends to get it there
+        let quote = quote_swap_exact_out(&pool, is_x, gross_out)?;
+        let amount_in = quote
+            .amount_in
+            .checked_add(transfer_inverse_fee(mint_in, quote.amount_in)?)
+            .ok_or(AmmError::Overflow)?;
+        require!(amount_in <= max_amount_in, AmmError::SlippageExceeded);
+
+        self.settle(&pool, is_x, amount_in, &quote)
+    }
+
+    // Books a quoted swap against the pool and moves the tokens. `amount_in` is what the
+    // user sends, transfer fee included
+    fn settle(
+        &mut self,
+        pool: &PoolState,
+        is_x: bool,
+        amount_in: u64,
+        quote: &SwapQuote,
+    ) -> Result<()> {
+        self.config.record_trade(
+            is_x,
+            quote.amount_in,
+            (pool.reserve_x, pool.reserve_y),
+            (quote.reserve_x, quote.reserve_y),
+        )?;
+        self.update_oracle()?;
+        self.config
+            .credit_protocol_fee(pool.fee_in_x(is_x), quote.protocol_fee)?;
 
         // Deposit the input tokens from user to vault
-        self.deposit_tokens(is_x, result.deposit)?;
+        self.deposit_tokens(is_x, amount_in)?;
 
         // Withdraw the output tokens from vault to user
-        self.withdraw_tokens(!is_x, result.withdraw)
+        self.withdraw_tokens(!is_x, quote.amount_out)?;
+
+        if quote.referral_fee > 0 {
+            self.pay_referrer(pool.fee_in_x(is_x), quote.referral_fee)?;
+        }
+
+        self.emit_swapped(
+            is_x,
+            amount_in,
+            quote.amount_out,
+            quote.fee,
+            quote.protocol_fee,
+            quote.referral_fee,
+        )
+    }
+
+    // Reports the swap together with the reserves it left behind
+    fn emit_swapped(
+        &mut self,
+        is_x: bool,
+        amount_in: u64,
+        amount_out: u64,
+        fee: u64,
+        protocol_fee: u64,
+        referral_fee: u64,
+    ) -> Result<()> {
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user: self.user.key(),
+            is_x,
+            amount_in,
+            amount_out,
+            fee,
+            protocol_fee,
+            referral_fee,
+            reserve_x,
+            reserve_y,
+        });
+
+        Ok(())
+    }
+
+    // Accrue the price that held up to this swap into the oracle
+    fn update_oracle(&mut self) -> Result<()> {
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)
+    }
+
+    // Shared pause/expiry/liquidity checks, returning the pool over the LP reserves
+    fn init_pool(&self, expiration: i64) -> Result<PoolState> {
+        self.config.check_allowed(PAUSE_SWAPS)?;
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+        require!(
+            reserve_x > 0 && reserve_y > 0,
+            AmmError::NoLiquidityInPool
+        );
+
+        // Quote against the current pool state; the LP supply doesn't matter for swaps
+        let mut pool = self.config.pool_state(reserve_x, reserve_y, 0)?;
+        if self.referrer.is_some() {
+            pool.referral_fee = self.config.referral_fee;
+        }
+        Ok(pool)
     }
 
     pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+        let (from, to, mint, token_program) = match is_x {
             true => (
                 self.user_x.to_account_info(),
                 self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
             ),
             false => (
                 self.user_y.to_account_info(),
                 self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.user.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer(ctx, amount)
+        transfer_checked(ctx, amount, mint.decimals)
     }
 
     pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+        let (from, to, mint, token_program) = match is_x {
             true => (
                 self.vault_x.to_account_info(),
                 self.user_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
             ),
             false => (
                 self.vault_y.to_account_info(),
                 self.user_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.config.to_account_info(),
         };
@@ -150,6 +308,44 @@ impl<'info> Swap<'info> {
 
         let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        transfer(ctx, amount)
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    // Pays the referrer its share of the fee, held in vault_x (is_x) or vault_y
+    pub fn pay_referrer(&self, is_x: bool, amount: u64) -> Result<()> {
+        let referrer = self.referrer.as_ref().ok_or(AmmError::InvalidReferrer)?;
+        let (from, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+        require_keys_eq!(referrer.mint, mint.key(), AmmError::InvalidReferrer);
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to: referrer.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
     }
 }
@@ -0,0 +1,317 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    errors::AmmError,
+    events::ConcentratedSwapped,
+    state::{ConcentratedPool, Config, Oracle, TickArray, PAUSE_SWAPS},
+    utils::transfer_fee,
+};
+
+#[derive(Accounts)]
+pub struct SwapConcentrated<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint_x,
+        has_one = mint_y,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Box<Account<'info, ConcentratedPool>>,
+    #[account(
+        mut,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_x,
+    )]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program_y,
+    )]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = user,
+        associated_token::token_program = token_program_x,
+    )]
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = user,
+        associated_token::token_program = token_program_y,
+    )]
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
+    // Token account of an aggregator or other referrer, in the token the pool takes its
+    // fee in. Gets Config::referral_fee of the swap fee
+    #[account(mut)]
+    pub referrer: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> SwapConcentrated<'info> {
+    // Exact input swap against the concentrated positions only. The constant product
+    // reserves are not touched. `tick_arrays` are every tick array the price moves
+    // through, in any order
+    pub fn swap_concentrated(
+        &mut self,
+        tick_arrays: &'info [AccountInfo<'info>],
+        is_x: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        self.config.check_allowed(PAUSE_SWAPS)?;
+        require!(amount_in != 0, AmmError::InvalidAmount);
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+
+        let (mint_in, mint_out) = match is_x {
+            true => (&self.mint_x, &self.mint_y),
+            false => (&self.mint_y, &self.mint_x),
+        };
+
+        // Price the swap on what the vault actually receives after any transfer fee
+        let received = amount_in - transfer_fee(mint_in, amount_in)?;
+        require!(received != 0, AmmError::InvalidAmount);
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+        let reserve_in = match is_x {
+            true => reserve_x.checked_add(self.config.concentrated_x),
+            false => reserve_y.checked_add(self.config.concentrated_y),
+        }
+        .ok_or(AmmError::Overflow)?;
+
+        // Accrue the price that held up to this swap into the oracle
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+
+        // Each of the pool's tick arrays at most once, so none is written back stale
+        let mut accounts = Vec::with_capacity(tick_arrays.len());
+        let mut arrays: Vec<TickArray> = Vec::with_capacity(tick_arrays.len());
+        for info in tick_arrays {
+            let account = Box::new(Account::<TickArray>::try_from(info)?);
+            require!(
+                account.pool == self.concentrated_pool.key()
+                    && arrays
+                        .iter()
+                        .all(|array| array.start_tick != account.start_tick),
+                AmmError::InvalidTickArray
+            );
+            arrays.push((**account).clone());
+            accounts.push(account);
+        }
+
+        // Same fee, fee side and shares as a swap against the constant product reserves
+        let mut pool = self.config.pool_state(reserve_x, reserve_y, 0)?;
+        if self.referrer.is_some() {
+            pool.referral_fee = self.config.referral_fee;
+        }
+
+        let sqrt_price = self.concentrated_pool.sqrt_price;
+        let result = self
+            .concentrated_pool
+            .swap(&mut arrays, is_x, received, &pool)?;
+
+        for (mut account, array) in accounts.into_iter().zip(arrays) {
+            account.set_inner(array);
+            account.exit(&crate::ID)?;
+        }
+
+        // The user must still get at least min_amount_out once the outbound fee is withheld
+        require!(
+            result.amount_out - transfer_fee(mint_out, result.amount_out)? >= min_amount_out,
+            AmmError::SlippageExceeded
+        );
+
+        self.config.record_concentrated_trade(
+            received,
+            reserve_in,
+            sqrt_price,
+            self.concentrated_pool.sqrt_price,
+        )?;
+
+        // The LP share of the fee stays with the positions; the protocol cut is set aside
+        // and the referral fee leaves the vault
+        let fee_in_x = pool.fee_in_x(is_x);
+        self.config
+            .credit_protocol_fee(fee_in_x, result.protocol_fee)?;
+        self.config
+            .update_concentrated(is_x, result.amount_in as i128)?;
+        self.config
+            .update_concentrated(!is_x, -(result.amount_out as i128))?;
+        self.config.update_concentrated(
+            fee_in_x,
+            -((result.protocol_fee + result.referral_fee) as i128),
+        )?;
+
+        // Deposit the input tokens from user to vault
+        self.deposit_tokens(is_x, amount_in)?;
+
+        // Withdraw the output tokens from vault to user
+        if result.amount_out > 0 {
+            self.withdraw_tokens(!is_x, result.amount_out)?;
+        }
+
+        if result.referral_fee > 0 {
+            self.pay_referrer(fee_in_x, result.referral_fee)?;
+        }
+
+        emit!(ConcentratedSwapped {
+            config: self.config.key(),
+            user: self.user.key(),
+            is_x,
+            amount_in,
+            amount_out: result.amount_out,
+            fee: result.fee,
+            protocol_fee: result.protocol_fee,
+            referral_fee: result.referral_fee,
+            sqrt_price: self.concentrated_pool.sqrt_price,
+            tick: self.concentrated_pool.tick,
+            liquidity: self.concentrated_pool.liquidity,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.user_x.to_account_info(),
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.user_y.to_account_info(),
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.user.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                self.user_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                self.user_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+
+    // Pays the referrer its share of the fee, held in vault_x (is_x) or vault_y
+    pub fn pay_referrer(&self, is_x: bool, amount: u64) -> Result<()> {
+        let referrer = self.referrer.as_ref().ok_or(AmmError::InvalidReferrer)?;
+        let (from, mint, token_program) = match is_x {
+            true => (
+                self.vault_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            ),
+            false => (
+                self.vault_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
+            ),
+        };
+        require_keys_eq!(referrer.mint, mint.key(), AmmError::InvalidReferrer);
+
+        let cpi_program = token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to: referrer.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        // PDA signing - vault is owned by config PDA
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        transfer_checked(ctx, amount, mint.decimals)
+    }
+}
@@ -1,6 +1,14 @@
 use anchor_lang::prelude::*;
 
-use crate::{errors::AmmError, state::Config};
+use crate::{
+    errors::AmmError,
+    events::{
+        AmpRampStarted, AmpRampStopped, AuthorityProposed, AuthorityRenounced,
+        CircuitBreakerReset, FeeUpdateQueued, FeeUpdated, PoolUpdated, ProtocolFeeUpdateQueued,
+        ProtocolFeeUpdated, ReferralFeeUpdated, TradeLimitsUpdated,
+    },
+    state::{Config, CurveType, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_RAMP_DURATION, PAUSE_ALL},
+};
 
 #[derive(Accounts)]
 pub struct Update<'info> {
@@ -14,17 +22,310 @@ pub struct Update<'info> {
 }
 
 impl<'info> Update<'info> {
-    pub fn update(&mut self, locked: bool) -> Result<()> {
+    // Pauses the operations in `paused` (PAUSE_* bits) and resumes all others
+    pub fn update(&mut self, paused: u8) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(paused & !PAUSE_ALL == 0, AmmError::InvalidPauseFlags);
+
+        self.config.paused = paused;
+
+        emit!(PoolUpdated {
+            config: self.config.key(),
+            authority: self.authority.key(),
+            paused,
+        });
+
+        Ok(())
+    }
+
+    // First half of the two-step handover; the new authority has to accept it
+    pub fn propose_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+
+        self.config.pending_authority = Some(new_authority);
+
+        emit!(AuthorityProposed {
+            config: self.config.key(),
+            authority: self.authority.key(),
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    // Permanently gives up control of the pool, including anything still pending
+    pub fn renounce_authority(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        // Nobody could ever resume it or reset a tripped circuit breaker again
+        require!(self.config.paused == 0, AmmError::PoolLocked);
+        require!(
+            self.config.breaker_threshold == 0,
+            AmmError::InvalidTradeLimits
+        );
+
+        self.config.authority = None;
+        self.config.pending_authority = None;
+        self.config.pending_fee = None;
+        self.config.pending_fee_eta = 0;
+        self.config.pending_protocol_fee = None;
+        self.config.pending_protocol_fee_eta = 0;
+
+        emit!(AuthorityRenounced {
+            config: self.config.key(),
+            previous_authority: self.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    // Applies the fee straight away without a timelock, otherwise queues it
+    pub fn update_fee(&mut self, fee: u16) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.config.check_fee(fee)?;
+
+        if self.config.fee_timelock == 0 {
+            return self.set_fee(fee);
+        }
+
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(self.config.fee_timelock)
+            .ok_or(AmmError::Overflow)?;
+        self.config.pending_fee = Some(fee);
+        self.config.pending_fee_eta = eta;
+
+        emit!(FeeUpdateQueued {
+            config: self.config.key(),
+            fee,
+            eta,
+        });
+
+        Ok(())
+    }
+
+    // Applies a fee queued by update_fee once its timelock has passed
+    pub fn apply_fee(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        let fee = self.config.pending_fee.ok_or(AmmError::NoPendingFee)?;
+        require!(
+            Clock::get()?.unix_timestamp >= self.config.pending_fee_eta,
+            AmmError::TimelockNotExpired
+        );
+
+        self.config.pending_fee = None;
+        self.config.pending_fee_eta = 0;
+
+        self.set_fee(fee)
+    }
+
+    // Sets the protocol share of the swap fee, in basis points, behind the same timelock
+    // as update_fee. Together with the referral share it can't exceed the whole fee
+    pub fn update_protocol_fee(&mut self, protocol_fee: u16) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        self.check_protocol_fee(protocol_fee)?;
+
+        if self.config.fee_timelock == 0 {
+            return self.set_protocol_fee(protocol_fee);
+        }
+
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(self.config.fee_timelock)
+            .ok_or(AmmError::Overflow)?;
+        self.config.pending_protocol_fee = Some(protocol_fee);
+        self.config.pending_protocol_fee_eta = eta;
+
+        emit!(ProtocolFeeUpdateQueued {
+            config: self.config.key(),
+            protocol_fee,
+            eta,
+        });
+
+        Ok(())
+    }
+
+    // Applies a protocol fee queued by update_protocol_fee once its timelock has passed
+    pub fn apply_protocol_fee(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        let protocol_fee = self
+            .config
+            .pending_protocol_fee
+            .ok_or(AmmError::NoPendingFee)?;
+        require!(
+            Clock::get()?.unix_timestamp >= self.config.pending_protocol_fee_eta,
+            AmmError::TimelockNotExpired
+        );
+        // The referral share may have grown since it was queued
+        self.check_protocol_fee(protocol_fee)?;
+
+        self.config.pending_protocol_fee = None;
+        self.config.pending_protocol_fee_eta = 0;
+
+        self.set_protocol_fee(protocol_fee)
+    }
+
+    // Sets the share of the swap fee, in basis points, paid to the referrer of a swap.
+    // Together with the protocol share it can't exceed the whole fee
+    pub fn update_referral_fee(&mut self, referral_fee: u16) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(
+            self.config.protocol_fee as u32 + referral_fee as u32 <= 10_000,
+            AmmError::InvalidFee
+        );
+
+        let old_referral_fee = self.config.referral_fee;
+        self.config.referral_fee = referral_fee;
+
+        emit!(ReferralFeeUpdated {
+            config: self.config.key(),
+            old_referral_fee,
+            new_referral_fee: referral_fee,
+        });
+
+        Ok(())
+    }
+
+    // Moves the StableSwap amplification linearly from its current value to `target_amp`
+    // by `ramp_end`. Bounded in speed and size so LPs can't be rugged by a sudden change
+    pub fn ramp_amp(&mut self, target_amp: u64, ramp_end: i64) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
         require!(
-            self.config.authority.is_some(),
-            AmmError::NoAuthoritySet
+            self.config.curve_type == CurveType::StableSwap,
+            AmmError::InvalidCurve
         );
         require!(
-            self.config.authority.unwrap() == self.authority.key(),
-            AmmError::InvalidAuthority
+            (MIN_AMP..=MAX_AMP).contains(&target_amp),
+            AmmError::InvalidAmp
         );
 
-        self.config.locked = locked;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ramp_end >= now.saturating_add(MIN_RAMP_DURATION),
+            AmmError::InvalidRamp
+        );
+
+        let amp = self.config.amp(now);
+        require!(
+            target_amp <= amp.saturating_mul(MAX_AMP_CHANGE)
+                && amp <= target_amp.saturating_mul(MAX_AMP_CHANGE),
+            AmmError::InvalidRamp
+        );
+
+        self.config.amp_initial = amp;
+        self.config.amp_target = target_amp;
+        self.config.amp_ramp_start = now;
+        self.config.amp_ramp_end = ramp_end;
+
+        emit!(AmpRampStarted {
+            config: self.config.key(),
+            initial_amp: amp,
+            target_amp,
+            ramp_start: now,
+            ramp_end,
+        });
+
+        Ok(())
+    }
+
+    // Freezes the amplification at its current value
+    pub fn stop_ramp_amp(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(
+            self.config.curve_type == CurveType::StableSwap,
+            AmmError::InvalidCurve
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let amp = self.config.amp(now);
+        self.config.amp_initial = amp;
+        self.config.amp_target = amp;
+        self.config.amp_ramp_start = now;
+        self.config.amp_ramp_end = now;
+
+        emit!(AmpRampStopped {
+            config: self.config.key(),
+            amp,
+        });
+
+        Ok(())
+    }
+
+    // Caps swaps at `max_trade` bps of the input reserve and trips the circuit breaker
+    // when the price moves more than `breaker_threshold` bps within `breaker_window`
+    // seconds. 0 turns either off
+    pub fn update_trade_limits(
+        &mut self,
+        max_trade: u16,
+        breaker_threshold: u16,
+        breaker_window: i64,
+    ) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(
+            max_trade <= 10_000 && (breaker_threshold == 0 || breaker_window > 0),
+            AmmError::InvalidTradeLimits
+        );
+
+        self.config.max_trade = max_trade;
+        self.config.breaker_threshold = breaker_threshold;
+        self.config.breaker_window = breaker_window;
+        // The next swap opens a fresh window
+        self.config.breaker_timestamp = 0;
+
+        emit!(TradeLimitsUpdated {
+            config: self.config.key(),
+            max_trade,
+            breaker_threshold,
+            breaker_window,
+        });
+
+        Ok(())
+    }
+
+    // Resumes swaps after the circuit breaker tripped, measuring from the current price
+    pub fn reset_circuit_breaker(&mut self) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+
+        self.config.breaker_tripped = false;
+        self.config.breaker_timestamp = 0;
+
+        emit!(CircuitBreakerReset {
+            config: self.config.key(),
+            authority: self.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    fn check_protocol_fee(&self, protocol_fee: u16) -> Result<()> {
+        require!(
+            protocol_fee as u32 + self.config.referral_fee as u32 <= 10_000,
+            AmmError::InvalidFee
+        );
+        Ok(())
+    }
+
+    fn set_protocol_fee(&mut self, protocol_fee: u16) -> Result<()> {
+        let old_protocol_fee = self.config.protocol_fee;
+        self.config.protocol_fee = protocol_fee;
+
+        emit!(ProtocolFeeUpdated {
+            config: self.config.key(),
+            old_protocol_fee,
+            new_protocol_fee: protocol_fee,
+        });
+
+        Ok(())
+    }
+
+    fn set_fee(&mut self, fee: u16) -> Result<()> {
+        let old_fee = self.config.fee;
+        self.config.fee = fee;
+
+        emit!(FeeUpdated {
+            config: self.config.key(),
+            old_fee,
+            new_fee: fee,
+        });
 
         Ok(())
     }
@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    metadata::{
+        mpl_token_metadata::{types::DataV2, MAX_URI_LENGTH},
+        update_metadata_accounts_v2, Metadata, MetadataAccount, UpdateMetadataAccountsV2,
+    },
+    token_interface::Mint,
+};
+
+use crate::{errors::AmmError, events::LpUriUpdated, state::Config};
+
+#[derive(Accounts)]
+pub struct UpdateLpMetadata<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            mint_lp.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub lp_metadata: Box<Account<'info, MetadataAccount>>,
+    pub token_metadata_program: Program<'info, Metadata>,
+}
+
+impl<'info> UpdateLpMetadata<'info> {
+    // Points the LP token at new off-chain metadata, keeping the name and symbol
+    pub fn update_lp_uri(&mut self, uri: String) -> Result<()> {
+        self.config.check_authority(&self.authority.key())?;
+        require!(uri.len() <= MAX_URI_LENGTH, AmmError::InvalidUri);
+
+        let cpi_program = self.token_metadata_program.to_account_info();
+
+        let cpi_accounts = UpdateMetadataAccountsV2 {
+            metadata: self.lp_metadata.to_account_info(),
+            update_authority: self.config.to_account_info(),
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"config",
+            &self.config.seed.to_le_bytes(),
+            &[self.config.config_bump],
+        ]];
+
+        let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        // The metadata program pads stored strings with zeros
+        update_metadata_accounts_v2(
+            ctx,
+            None,
+            Some(DataV2 {
+                name: self.lp_metadata.name.trim_end_matches('\0').to_string(),
+                symbol: self.lp_metadata.symbol.trim_end_matches('\0').to_string(),
+                uri: uri.clone(),
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            }),
+            None,
+            None,
+        )?;
+
+        emit!(LpUriUpdated {
+            config: self.config.key(),
+            authority: self.authority.key(),
+            uri,
+        });
+
+        Ok(())
+    }
+}
@@ -1,62 +1,90 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{burn, transfer, Burn, Mint, Token, TokenAccount, Transfer},
+    token_interface::{
+        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
-use constant_product_curve::ConstantProduct;
 
-use crate::{errors::AmmError, state::Config};
+use crate::{
+    errors::AmmError,
+    events::LiquidityWithdrawn,
+    quote::{quote_swap, quote_withdraw},
+    state::{Config, Oracle, PAUSE_SWAPS, PAUSE_WITHDRAWALS},
+    utils::transfer_fee,
+};
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
-    pub mint_x: Account<'info, Mint>,
-    pub mint_y: Account<'info, Mint>,
     #[account(
+        mint::token_program = token_program_x,
+    )]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program_y,
+    )]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
         has_one = mint_x,
         has_one = mint_y,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"oracle", config.key().as_ref()],
+        bump = oracle.bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
     #[account(
         mut,
         seeds = [b"lp", config.key().as_ref()],
         bump = config.lp_bump,
     )]
-    pub mint_lp: Account<'info, Mint>,
+    pub mint_lp: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = config,
+        associated_token::token_program = token_program_x,
     )]
-    pub vault_x: Account<'info, TokenAccount>,
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = config,
+        associated_token::token_program = token_program_y,
     )]
-    pub vault_y: Account<'info, TokenAccount>,
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_x,
         associated_token::authority = user,
+        associated_token::token_program = token_program_x,
     )]
-    pub user_x: Account<'info, TokenAccount>,
+    pub user_x: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_y,
         associated_token::authority = user,
+        associated_token::token_program = token_program_y,
     )]
-    pub user_y: Account<'info, TokenAccount>,
+    pub user_y: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = mint_lp,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub user_lp: InterfaceAccount<'info, TokenAccount>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    // The LP token's program, which X's and Y's don't have to match
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
@@ -69,52 +97,167 @@ impl<'info> Withdraw<'info> {
         min_y: u64,  // Minimum amount of token Y that the user wants to receive
         expiration: i64,
     ) -> Result<()> {
-        require!(self.config.locked == false, AmmError::PoolLocked);
+        self.config.check_allowed(PAUSE_WITHDRAWALS)?;
         require!(amount != 0, AmmError::InvalidAmount);
         require!(
             Clock::get()?.unix_timestamp < expiration,
             AmmError::OfferExpired
         );
 
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        // Accrue the price that held up to this change into the oracle
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+
         // Calculate how much X and Y the user gets for burning `amount` LP tokens
-        let amounts = ConstantProduct::xy_withdraw_amounts_from_l(
-            self.vault_x.amount,
-            self.vault_y.amount,
-            self.mint_lp.supply,
-            amount,
-            6,
-        )
-        .map_err(|_| AmmError::CurveError)?;
-
-        // Slippage check
-        require!(amounts.x >= min_x && amounts.y >= min_y, AmmError::SlippageExceeded);
+        let pool = self
+            .config
+            .pool_state(reserve_x, reserve_y, self.mint_lp.supply)?;
+        let amounts = quote_withdraw(&pool, amount)?;
+
+        // Slippage check against what actually lands in the user's accounts
+        let received_x = amounts.amount_x - transfer_fee(&self.mint_x, amounts.amount_x)?;
+        let received_y = amounts.amount_y - transfer_fee(&self.mint_y, amounts.amount_y)?;
+        require!(received_x >= min_x && received_y >= min_y, AmmError::SlippageExceeded);
 
         // Burn the LP tokens first
         self.burn_lp_tokens(amount)?;
 
         // Withdraw token X to user
-        self.withdraw_tokens(true, amounts.x)?;
+        self.withdraw_tokens(true, amounts.amount_x)?;
 
         // Withdraw token Y to user
-        self.withdraw_tokens(false, amounts.y)
+        self.withdraw_tokens(false, amounts.amount_y)?;
+
+        self.emit_withdrawn(amounts.amount_x, amounts.amount_y, amount, 0, 0)
+    }
+
+    pub fn withdraw_single(
+        &mut self,
+        is_x: bool,   // Whether the user wants to receive only token X (true) or only token Y
+        amount: u64,  // Amount of LP tokens that the user wants to "burn"
+        min_out: u64, // Minimum amount of the chosen token that the user wants to receive
+        expiration: i64,
+    ) -> Result<()> {
+        // The unwanted side is swapped back into the pool
+        self.config.check_allowed(PAUSE_WITHDRAWALS | PAUSE_SWAPS)?;
+        require!(amount != 0, AmmError::InvalidAmount);
+        require!(
+            Clock::get()?.unix_timestamp < expiration,
+            AmmError::OfferExpired
+        );
+
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        // Accrue the price that held up to this change into the oracle
+        self.oracle
+            .update(reserve_x, reserve_y, Clock::get()?.unix_timestamp)?;
+
+        // Same math as a regular withdrawal
+        let mut pool = self
+            .config
+            .pool_state(reserve_x, reserve_y, self.mint_lp.supply)?;
+        let amounts = quote_withdraw(&pool, amount)?;
+
+        // Swap the unwanted side back into the pool, priced on the post-withdrawal reserves
+        let (amount_kept, amount_swapped, mint_out) = match is_x {
+            true => (amounts.amount_x, amounts.amount_y, &self.mint_x),
+            false => (amounts.amount_y, amounts.amount_x, &self.mint_y),
+        };
+        pool.reserve_x -= amounts.amount_x;
+        pool.reserve_y -= amounts.amount_y;
+        let quote = quote_swap(&pool, !is_x, amount_swapped)?;
+        self.config.record_trade(
+            !is_x,
+            quote.amount_in,
+            (pool.reserve_x, pool.reserve_y),
+            (quote.reserve_x, quote.reserve_y),
+        )?;
+
+        self.config
+            .credit_protocol_fee(pool.fee_in_x(!is_x), quote.protocol_fee)?;
+
+        let total_out = amount_kept
+            .checked_add(quote.amount_out)
+            .ok_or(AmmError::Overflow)?;
+
+        // Slippage check against what actually lands in the user's account
+        require!(
+            total_out - transfer_fee(mint_out, total_out)? >= min_out,
+            AmmError::SlippageExceeded
+        );
+
+        // Burn the LP tokens first
+        self.burn_lp_tokens(amount)?;
+
+        // Only the chosen token leaves the pool
+        self.withdraw_tokens(is_x, total_out)?;
+
+        let (amount_x, amount_y) = match is_x {
+            true => (total_out, 0),
+            false => (0, total_out),
+        };
+        self.emit_withdrawn(amount_x, amount_y, amount, quote.fee, quote.protocol_fee)
+    }
+
+    // Reports the withdrawal together with the reserves and LP supply it left behind
+    fn emit_withdrawn(
+        &mut self,
+        amount_x: u64,
+        amount_y: u64,
+        lp_amount: u64,
+        fee: u64,
+        protocol_fee: u64,
+    ) -> Result<()> {
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.mint_lp.reload()?;
+        let (reserve_x, reserve_y) = self
+            .config
+            .reserves(self.vault_x.amount, self.vault_y.amount)?;
+
+        emit!(LiquidityWithdrawn {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            fee,
+            protocol_fee,
+            reserve_x,
+            reserve_y,
+            lp_supply: self.mint_lp.supply,
+        });
+
+        Ok(())
     }
 
     pub fn withdraw_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
-        let (from, to) = match is_x {
+        let (from, to, mint, token_program) = match is_x {
             true => (
                 self.vault_x.to_account_info(),
                 self.user_x.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
             ),
             false => (
                 self.vault_y.to_account_info(),
                 self.user_y.to_account_info(),
+                &self.mint_y,
+                &self.token_program_y,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.config.to_account_info(),
         };
@@ -128,7 +271,7 @@ impl<'info> Withdraw<'info> {
 
         let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        transfer(ctx, amount)
+        transfer_checked(ctx, amount, mint.decimals)
     }
 
     pub fn burn_lp_tokens(&self, amount: u64) -> Result<()> {
@@ -1,33 +1,75 @@
 use anchor_lang::prelude::*;
 
-mod errors;
+mod dynamic_fee;
+pub mod errors;
+mod events;
 mod instructions;
-mod state;
+pub mod quote;
+pub mod state;
+mod tick_math;
+mod utils;
 
 use instructions::*;
+use state::{CurveType, FeeSide, TwapPrice};
 declare_id!("9JvV2zdErckn9GHps21TZmve8gLF7ChiPGBMG8wnGrKS");
 
 #[program]
 pub mod anchor_amm_q4_25 {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         ctx: Context<Initialize>,
         seed: u64,
         fee: u16,
+        min_fee: u16,
+        max_fee: u16,
+        protocol_fee: u16,
+        fee_timelock: i64,
+        curve_type: CurveType,
+        amp: u64,
+        fee_side: FeeSide,
+        referral_fee: u16,
         authority: Option<Pubkey>,
     ) -> Result<()> {
-        ctx.accounts.init(seed, fee, authority, ctx.bumps)
+        ctx.accounts.init(
+            seed,
+            fee,
+            min_fee,
+            max_fee,
+            protocol_fee,
+            fee_timelock,
+            curve_type,
+            amp,
+            fee_side,
+            referral_fee,
+            authority,
+            ctx.bumps,
+        )
     }
 
+    /// Deposits X and Y in the pool ratio for `lp_amount` LP tokens, paying at most
+    /// `max_x` and `max_y`. The first deposit into an empty pool sets the price instead:
+    /// it pays exactly `max_x` and `max_y`, and `lp_amount` is the least LP the depositor
+    /// accepts once MINIMUM_LIQUIDITY is locked
     pub fn deposit(
         ctx: Context<Deposit>,
-        amount: u64,
+        lp_amount: u64,
         max_x: u64,
         max_y: u64,
         expiration: i64,
     ) -> Result<()> {
-        ctx.accounts.deposit(amount, max_x, max_y, expiration)
+        ctx.accounts.deposit(lp_amount, max_x, max_y, expiration)
+    }
+
+    pub fn deposit_single(
+        ctx: Context<Deposit>,
+        is_x: bool,
+        amount_in: u64,
+        min_lp_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.deposit_single(is_x, amount_in, min_lp_out, expiration)
     }
 
     pub fn withdraw(
@@ -40,6 +82,16 @@ pub mod anchor_amm_q4_25 {
         ctx.accounts.withdraw(amount, min_x, min_y, expiration)
     }
 
+    pub fn withdraw_single(
+        ctx: Context<Withdraw>,
+        is_x: bool,
+        amount: u64,
+        min_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.withdraw_single(is_x, amount, min_out, expiration)
+    }
+
     pub fn swap(
         ctx: Context<Swap>,
         is_x: bool,
@@ -50,7 +102,240 @@ pub mod anchor_amm_q4_25 {
         ctx.accounts.swap(is_x, amount_in, min_amount_out, expiration)
     }
 
-    pub fn update(ctx: Context<Update>, locked: bool) -> Result<()> {
-        ctx.accounts.update(locked)
+    pub fn swap_exact_out(
+        ctx: Context<Swap>,
+        is_x: bool,
+        amount_out: u64,
+        max_amount_in: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.swap_exact_out(is_x, amount_out, max_amount_in, expiration)
+    }
+
+    pub fn route_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .route_swap(ctx.remaining_accounts, amount_in, min_amount_out, expiration)
+    }
+
+    /// Lends `amount` of X (is_x) or Y out of the vault, for the pool's swap fee. The
+    /// transaction must repay it with a later top-level repay_flash_loan instruction for
+    /// the same pool; a repayment made through a CPI isn't seen, so the borrow fails with
+    /// FlashLoanNotRepaid. The borrow itself may come through a CPI
+    pub fn flash_loan(ctx: Context<FlashLoan>, is_x: bool, amount: u64) -> Result<()> {
+        ctx.accounts.flash_loan(is_x, amount)
+    }
+
+    /// Repays the outstanding flash loan plus its fee. Must be a top-level instruction,
+    /// see flash_loan
+    pub fn repay_flash_loan(ctx: Context<FlashLoan>) -> Result<()> {
+        ctx.accounts.repay_flash_loan()
+    }
+
+    pub fn update(ctx: Context<Update>, paused: u8) -> Result<()> {
+        ctx.accounts.update(paused)
+    }
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        ctx.accounts.migrate_config(ctx.bumps)
+    }
+
+    pub fn propose_authority(ctx: Context<Update>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.propose_authority(new_authority)
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        ctx.accounts.accept_authority()
+    }
+
+    pub fn renounce_authority(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.renounce_authority()
+    }
+
+    pub fn update_fee(ctx: Context<Update>, fee: u16) -> Result<()> {
+        ctx.accounts.update_fee(fee)
+    }
+
+    pub fn update_referral_fee(ctx: Context<Update>, referral_fee: u16) -> Result<()> {
+        ctx.accounts.update_referral_fee(referral_fee)
+    }
+
+    pub fn apply_fee(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.apply_fee()
+    }
+
+    pub fn update_protocol_fee(ctx: Context<Update>, protocol_fee: u16) -> Result<()> {
+        ctx.accounts.update_protocol_fee(protocol_fee)
+    }
+
+    pub fn apply_protocol_fee(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.apply_protocol_fee()
+    }
+
+    pub fn ramp_amp(ctx: Context<Update>, target_amp: u64, ramp_end: i64) -> Result<()> {
+        ctx.accounts.ramp_amp(target_amp, ramp_end)
+    }
+
+    pub fn stop_ramp_amp(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.stop_ramp_amp()
+    }
+
+    pub fn update_trade_limits(
+        ctx: Context<Update>,
+        max_trade: u16,
+        breaker_threshold: u16,
+        breaker_window: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .update_trade_limits(max_trade, breaker_threshold, breaker_window)
+    }
+
+    pub fn reset_circuit_breaker(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.reset_circuit_breaker()
+    }
+
+    pub fn update_lp_uri(ctx: Context<UpdateLpMetadata>, uri: String) -> Result<()> {
+        ctx.accounts.update_lp_uri(uri)
+    }
+
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        ctx.accounts.collect_protocol_fees()
+    }
+
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        ctx.accounts.close_pool()
+    }
+
+    pub fn get_twap(ctx: Context<GetTwap>, window: u32) -> Result<TwapPrice> {
+        ctx.accounts.get_twap(window)
+    }
+
+    pub fn register_pool(ctx: Context<RegisterPool>) -> Result<()> {
+        ctx.accounts.register_pool(ctx.bumps)
+    }
+
+    pub fn initialize_concentrated(
+        ctx: Context<InitializeConcentrated>,
+        tick_spacing: u16,
+        tick: i32,
+    ) -> Result<()> {
+        ctx.accounts
+            .initialize_concentrated(tick_spacing, tick, ctx.bumps)
+    }
+
+    pub fn initialize_tick_array(ctx: Context<InitializeTickArray>, start_tick: i32) -> Result<()> {
+        ctx.accounts.initialize_tick_array(start_tick, ctx.bumps)
+    }
+
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        ctx.accounts.open_position(tick_lower, tick_upper, ctx.bumps)
+    }
+
+    pub fn increase_liquidity(
+        ctx: Context<ModifyPosition>,
+        liquidity: u128,
+        max_x: u64,
+        max_y: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .increase_liquidity(liquidity, max_x, max_y, expiration)
+    }
+
+    pub fn decrease_liquidity(
+        ctx: Context<ModifyPosition>,
+        liquidity: u128,
+        min_x: u64,
+        min_y: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .decrease_liquidity(liquidity, min_x, min_y, expiration)
+    }
+
+    pub fn collect_fees(ctx: Context<ModifyPosition>) -> Result<()> {
+        ctx.accounts.collect_fees()
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        ctx.accounts.close_position()
+    }
+
+    /// Swaps against the concentrated positions only, with the fee taken like swap takes
+    /// it: on the pool's fee side, the protocol share set aside and the referral share paid
+    /// to `referrer`. The concentrated price starts at the constant product one but moves
+    /// on its own after that; once the two are further apart than the fee, arbitrage
+    /// between them pays and is what brings them back together. The remaining accounts
+    /// are the tick arrays the price moves through
+    pub fn swap_concentrated<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        is_x: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.swap_concentrated(
+            ctx.remaining_accounts,
+            is_x,
+            amount_in,
+            min_amount_out,
+            expiration,
+        )
+    }
+
+    pub fn create_farm(
+        ctx: Context<CreateFarm>,
+        reward_per_second: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .create_farm(reward_per_second, start_time, end_time, ctx.bumps)
+    }
+
+    pub fn stake_lp(ctx: Context<ModifyStake>, amount: u64) -> Result<()> {
+        ctx.accounts.stake_lp(amount, ctx.bumps)
+    }
+
+    pub fn unstake_lp(ctx: Context<ModifyStake>, amount: u64) -> Result<()> {
+        ctx.accounts.unstake_lp(amount)
+    }
+
+    pub fn harvest(ctx: Context<ModifyStake>) -> Result<()> {
+        ctx.accounts.harvest()
+    }
+
+    pub fn close_farm(ctx: Context<CloseFarm>) -> Result<()> {
+        ctx.accounts.close_farm()
+    }
+
+    pub fn place_limit_order(
+        ctx: Context<PlaceLimitOrder>,
+        id: u64,
+        amount_in: u64,
+        min_amount_out: u64,
+        tip: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .place_limit_order(id, amount_in, min_amount_out, tip, expiration, ctx.bumps)
+    }
+
+    pub fn fill_limit_orders<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FillLimitOrders<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.fill_limit_orders(ctx.remaining_accounts)
+    }
+
+    pub fn cancel_limit_order(ctx: Context<CancelLimitOrder>) -> Result<()> {
+        ctx.accounts.cancel_limit_order()
     }
 }
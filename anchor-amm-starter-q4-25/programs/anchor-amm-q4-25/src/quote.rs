@@ -0,0 +1,56 @@
+pub use amm_quote::{
+    fee_share, initial_lp, lp_decimals, share_precision, Curve, DepositQuote, PoolState, SwapQuote,
+    WithdrawQuote, MAX_PRECISION, MINIMUM_LIQUIDITY,
+};
+
+use crate::{errors::AmmError, state};
+
+// The quoting math lives in the amm-quote crate, which has no dependencies so that
+// off-chain clients can use it without Anchor. The wrappers below map its errors to
+// AmmError for the instructions; clients can call amm_quote directly
+
+pub fn quote_swap(pool: &PoolState, is_x: bool, amount_in: u64) -> Result<SwapQuote, AmmError> {
+    amm_quote::quote_swap(pool, is_x, amount_in).map_err(AmmError::from)
+}
+
+pub fn quote_swap_exact_out(
+    pool: &PoolState,
+    is_x: bool,
+    amount_out: u64,
+) -> Result<SwapQuote, AmmError> {
+    amm_quote::quote_swap_exact_out(pool, is_x, amount_out).map_err(AmmError::from)
+}
+
+pub fn quote_deposit(pool: &PoolState, lp_amount: u64) -> Result<DepositQuote, AmmError> {
+    amm_quote::quote_deposit(pool, lp_amount).map_err(AmmError::from)
+}
+
+pub fn quote_initial_deposit(
+    pool: &PoolState,
+    amount_x: u64,
+    amount_y: u64,
+) -> Result<DepositQuote, AmmError> {
+    amm_quote::quote_initial_deposit(pool, amount_x, amount_y).map_err(AmmError::from)
+}
+
+pub fn quote_withdraw(pool: &PoolState, lp_amount: u64) -> Result<WithdrawQuote, AmmError> {
+    amm_quote::quote_withdraw(pool, lp_amount).map_err(AmmError::from)
+}
+
+impl From<state::CurveType> for amm_quote::CurveType {
+    fn from(curve_type: state::CurveType) -> amm_quote::CurveType {
+        match curve_type {
+            state::CurveType::ConstantProduct => amm_quote::CurveType::ConstantProduct,
+            state::CurveType::StableSwap => amm_quote::CurveType::StableSwap,
+        }
+    }
+}
+
+impl From<state::FeeSide> for amm_quote::FeeSide {
+    fn from(fee_side: state::FeeSide) -> amm_quote::FeeSide {
+        match fee_side {
+            state::FeeSide::Input => amm_quote::FeeSide::Input,
+            state::FeeSide::Output => amm_quote::FeeSide::Output,
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use anchor_lang::prelude::*;
+
+use amm_quote::mul_div;
+
+use crate::{
+    errors::AmmError,
+    quote::{fee_share, PoolState},
+    tick_math::{
+        amount_x_delta, amount_y_delta, sqrt_price_at_tick, swap_step, tick_at_sqrt_price,
+        MAX_LIQUIDITY, MAX_TICK, MIN_TICK,
+    },
+};
+
+// Number of usable ticks, tick_spacing apart, in one tick array
+pub const TICK_ARRAY_SIZE: usize = 32;
+
+// Smallest liquidity a position can hold other than none, so that positions tie up real
+// capital rather than dust
+pub const MIN_POSITION_LIQUIDITY: u128 = 1_000_000;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TickState {
+    pub liquidity_net: i128,   // Liquidity added when crossing the tick upwards
+    pub liquidity_gross: u128, // Liquidity of all positions using this tick, 0 if unused
+    pub fee_growth_outside_x: u128, // Fee growth on the other side of the tick, Q64.64
+    pub fee_growth_outside_y: u128, // Fee growth on the other side of the tick, Q64.64
+}
+
+// Concentrated liquidity state of a pool, living next to its constant product reserves
+// in the same vaults. Its ticks are kept in tick arrays
+#[account]
+#[derive(InitSpace)]
+pub struct ConcentratedPool {
+    pub config: Pubkey,            // Pool this state belongs to
+    pub tick_spacing: u16,         // Ticks used by positions must be multiples of this
+    pub sqrt_price: u128,          // Current sqrt(Y per X), Q64.64
+    pub tick: i32,                 // Largest tick at or below the current price
+    pub liquidity: u128,           // Liquidity of the positions in range
+    pub fee_growth_global_x: u128, // LP fees per unit of liquidity in token X, Q64.64
+    pub fee_growth_global_y: u128, // LP fees per unit of liquidity in token Y, Q64.64
+    pub bump: u8,                  // Bump seed for the concentrated pool account
+}
+
+// TICK_ARRAY_SIZE consecutive usable ticks of a concentrated pool. Whoever first needs
+// one creates it and pays its rent, so how many ticks a pool uses is unbounded
+#[account]
+#[derive(InitSpace)]
+pub struct TickArray {
+    pub pool: Pubkey,                        // Concentrated pool the ticks belong to
+    pub start_tick: i32,                     // First tick, a multiple of the array's span
+    pub ticks: [TickState; TICK_ARRAY_SIZE], // Tick `i` is start_tick + i * tick_spacing
+    pub bump: u8,                            // Bump seed for the tick array account
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub pool: Pubkey,              // Concentrated pool the position belongs to
+    pub owner: Pubkey,             // Only the owner can modify the position
+    pub tick_lower: i32,           // Lower bound of the price range
+    pub tick_upper: i32,           // Upper bound of the price range
+    pub liquidity: u128,           // Liquidity provided over the range
+    pub fee_growth_inside_x: u128, // Fee growth inside the range at the last update
+    pub fee_growth_inside_y: u128, // Fee growth inside the range at the last update
+    pub fees_owed_x: u64,          // Collectable fees in token X
+    pub fees_owed_y: u64,          // Collectable fees in token Y
+    pub bump: u8,                  // Bump seed for the position account
+}
+
+pub struct ConcentratedSwap {
+    pub amount_in: u64,    // Input consumed, fee included
+    pub amount_out: u64,   // Output owed to the swapper, fee excluded
+    pub fee: u64,          // Total swap fee, in the token PoolState::fee_in_x picks
+    pub protocol_fee: u64, // Protocol's cut of the fee, not credited to LPs
+    pub referral_fee: u64, // Referrer's cut of the fee, not credited to LPs
+}
+
+impl ConcentratedPool {
+    pub fn check_ticks(&self, tick_lower: i32, tick_upper: i32) -> Result<()> {
+        let spacing = self.tick_spacing as i32;
+        require!(
+            tick_lower < tick_upper
+                && tick_lower >= MIN_TICK
+                && tick_upper <= MAX_TICK
+                && tick_lower % spacing == 0
+                && tick_upper % spacing == 0,
+            AmmError::InvalidTick
+        );
+        Ok(())
+    }
+
+    // Number of ticks a tick array covers
+    pub fn tick_array_span(&self) -> i32 {
+        self.tick_spacing as i32 * TICK_ARRAY_SIZE as i32
+    }
+
+    // First tick of the tick array holding `tick`
+    pub fn tick_array_start(&self, tick: i32) -> i32 {
+        let span = self.tick_array_span();
+        tick.div_euclid(span) * span
+    }
+
+    // Returns which of `arrays` holds `tick`, a multiple of the tick spacing, and where
+    fn find_tick(&self, arrays: &[TickArray], tick: i32) -> Result<(usize, usize)> {
+        let start = self.tick_array_start(tick);
+        let array = arrays
+            .iter()
+            .position(|array| array.start_tick == start)
+            .ok_or(AmmError::InvalidTickArray)?;
+        Ok((array, ((tick - start) / self.tick_spacing as i32) as usize))
+    }
+
+    // Fee growth below a tick put to use is assumed to have happened below it, as in
+    // Uniswap v3
+    fn update_tick(
+        &self,
+        arrays: &mut [TickArray],
+        index: i32,
+        delta: i128,
+        upper: bool,
+    ) -> Result<()> {
+        let (array, slot) = self.find_tick(arrays, index)?;
+        let tick = &mut arrays[array].ticks[slot];
+
+        if tick.liquidity_gross == 0 && index <= self.tick {
+            tick.fee_growth_outside_x = self.fee_growth_global_x;
+            tick.fee_growth_outside_y = self.fee_growth_global_y;
+        }
+        tick.liquidity_gross = tick
+            .liquidity_gross
+            .checked_add_signed(delta)
+            .filter(|gross| *gross <= MAX_LIQUIDITY)
+            .ok_or(AmmError::Overflow)?;
+        // Crossing a lower bound upwards enters the range, crossing an upper bound leaves it
+        tick.liquidity_net = match upper {
+            true => tick.liquidity_net.checked_sub(delta),
+            false => tick.liquidity_net.checked_add(delta),
+        }
+        .ok_or(AmmError::Overflow)?;
+        Ok(())
+    }
+
+    // Resets a tick no position references anymore
+    fn clear_tick(&self, arrays: &mut [TickArray], index: i32) -> Result<()> {
+        let (array, slot) = self.find_tick(arrays, index)?;
+        let tick = &mut arrays[array].ticks[slot];
+        if tick.liquidity_gross == 0 {
+            *tick = TickState::default();
+        }
+        Ok(())
+    }
+
+    // Fee growth per unit of liquidity accrued between two ticks
+    fn fee_growth_inside(
+        &self,
+        arrays: &[TickArray],
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<(u128, u128)> {
+        let (global_x, global_y) = (self.fee_growth_global_x, self.fee_growth_global_y);
+        let (array, slot) = self.find_tick(arrays, tick_lower)?;
+        let lower = arrays[array].ticks[slot];
+        let (array, slot) = self.find_tick(arrays, tick_upper)?;
+        let upper = arrays[array].ticks[slot];
+
+        let (below_x, below_y) = match self.tick >= tick_lower {
+            true => (lower.fee_growth_outside_x, lower.fee_growth_outside_y),
+            false => (
+                global_x.wrapping_sub(lower.fee_growth_outside_x),
+                global_y.wrapping_sub(lower.fee_growth_outside_y),
+            ),
+        };
+        let (above_x, above_y) = match self.tick < tick_upper {
+            true => (upper.fee_growth_outside_x, upper.fee_growth_outside_y),
+            false => (
+                global_x.wrapping_sub(upper.fee_growth_outside_x),
+                global_y.wrapping_sub(upper.fee_growth_outside_y),
+            ),
+        };
+
+        Ok((
+            global_x.wrapping_sub(below_x).wrapping_sub(above_x),
+            global_y.wrapping_sub(below_y).wrapping_sub(above_y),
+        ))
+    }
+
+    // Adds (delta > 0) or removes liquidity from a position, crediting the fees it earned
+    // so far. `arrays` must hold both of its ticks. Returns the token amounts to move,
+    // rounded up when they are paid into the pool and down when they are paid out
+    pub fn modify_position(
+        &mut self,
+        arrays: &mut [TickArray],
+        position: &mut Position,
+        delta: i128,
+    ) -> Result<(u64, u64)> {
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+
+        let liquidity = position
+            .liquidity
+            .checked_add_signed(delta)
+            .filter(|liquidity| *liquidity <= MAX_LIQUIDITY)
+            .ok_or(AmmError::InsufficientBalance)?;
+        require!(
+            delta == 0 || liquidity == 0 || liquidity >= MIN_POSITION_LIQUIDITY,
+            AmmError::LiquidityLessThanMinimum
+        );
+
+        if delta != 0 {
+            self.update_tick(arrays, tick_lower, delta, false)?;
+            self.update_tick(arrays, tick_upper, delta, true)?;
+        }
+
+        // Settle the fees earned at the old liquidity
+        let (inside_x, inside_y) = self.fee_growth_inside(arrays, tick_lower, tick_upper)?;
+        let earned = |inside: u128, last: u128| -> Result<u64> {
+            let fees = mul_div(
+                position.liquidity,
+                inside.wrapping_sub(last),
+                1 << 64,
+                false,
+            )
+            .ok_or(AmmError::Overflow)?;
+            u64::try_from(fees).map_err(|_| AmmError::Overflow.into())
+        };
+        let earned_x = earned(inside_x, position.fee_growth_inside_x)?;
+        let earned_y = earned(inside_y, position.fee_growth_inside_y)?;
+        position.fees_owed_x = position
+            .fees_owed_x
+            .checked_add(earned_x)
+            .ok_or(AmmError::Overflow)?;
+        position.fees_owed_y = position
+            .fees_owed_y
+            .checked_add(earned_y)
+            .ok_or(AmmError::Overflow)?;
+        position.fee_growth_inside_x = inside_x;
+        position.fee_growth_inside_y = inside_y;
+        position.liquidity = liquidity;
+
+        if delta < 0 {
+            self.clear_tick(arrays, tick_lower)?;
+            self.clear_tick(arrays, tick_upper)?;
+        }
+
+        // Token amounts for the liquidity delta at the current price
+        let round_up = delta > 0;
+        let amount = delta.unsigned_abs();
+        let (price_lower, price_upper) = (
+            sqrt_price_at_tick(tick_lower)?,
+            sqrt_price_at_tick(tick_upper)?,
+        );
+        let amounts = if self.tick < tick_lower {
+            (
+                amount_x_delta(price_lower, price_upper, amount, round_up)?,
+                0,
+            )
+        } else if self.tick < tick_upper {
+            self.liquidity = self
+                .liquidity
+                .checked_add_signed(delta)
+                .filter(|liquidity| *liquidity <= MAX_LIQUIDITY)
+                .ok_or(AmmError::Overflow)?;
+            (
+                amount_x_delta(self.sqrt_price, price_upper, amount, round_up)?,
+                amount_y_delta(price_lower, self.sqrt_price, amount, round_up)?,
+            )
+        } else {
+            (
+                0,
+                amount_y_delta(price_lower, price_upper, amount, round_up)?,
+            )
+        };
+
+        Ok(amounts)
+    }
+
+    // Next tick a swap of X (is_x, moving down) or Y stops at, and whether it is in use.
+    // Only the tick array the search starts in is looked at; without a tick in use there,
+    // the swap stops at its edge and carries on in the next one
+    fn next_tick(&self, arrays: &[TickArray], is_x: bool) -> Result<(i32, bool)> {
+        let spacing = self.tick_spacing as i32;
+        // Moving down, the current tick itself is next; moving up, the one right above it
+        let from = match is_x {
+            true => self.tick.div_euclid(spacing) * spacing,
+            false => (self.tick.div_euclid(spacing) + 1) * spacing,
+        };
+        let (array, slot) = self.find_tick(arrays, from)?;
+        let (start, ticks) = (arrays[array].start_tick, &arrays[array].ticks);
+
+        let used = match is_x {
+            true => (0..=slot).rev().find(|i| ticks[*i].liquidity_gross > 0),
+            false => (slot..TICK_ARRAY_SIZE).find(|i| ticks[*i].liquidity_gross > 0),
+        };
+        Ok(match (used, is_x) {
+            (Some(i), _) => (start + i as i32 * spacing, true),
+            (None, true) => (start, false),
+            (None, false) => (start + (TICK_ARRAY_SIZE as i32 - 1) * spacing, false),
+        })
+    }
+
+    // Exact input swap of X (is_x) or Y across as many ticks as it takes. `amount` is
+    // what reached the vault. The fee is taken on the side `fees` sets, like a constant
+    // product swap, with its protocol and referral shares. `arrays` must hold every tick
+    // array the price moves through
+    pub fn swap(
+        &mut self,
+        arrays: &mut [TickArray],
+        is_x: bool,
+        amount: u64,
+        fees: &PoolState,
+    ) -> Result<ConcentratedSwap> {
+        let mut result = ConcentratedSwap {
+            amount_in: 0,
+            amount_out: 0,
+            fee: 0,
+            protocol_fee: 0,
+            referral_fee: 0,
+        };
+        let fee_on_input = fees.fee_in_x(is_x) == is_x;
+        let mut remaining = amount;
+
+        while remaining > 0 {
+            // Ran out of liquidity in this direction
+            require!(
+                match is_x {
+                    true => self.tick >= MIN_TICK,
+                    false => self.tick < MAX_TICK,
+                },
+                AmmError::NoLiquidityInPool
+            );
+            let (index, used) = self.next_tick(arrays, is_x)?;
+            let index = index.clamp(MIN_TICK, MAX_TICK);
+            let target = sqrt_price_at_tick(index)?;
+
+            let step = swap_step(
+                self.sqrt_price,
+                target,
+                self.liquidity,
+                remaining,
+                match fee_on_input {
+                    true => fees.fee,
+                    false => 0,
+                },
+                is_x,
+            )?;
+            remaining -= step.amount_in + step.fee;
+            result.amount_in += step.amount_in + step.fee;
+
+            // An output side fee comes off what the step pays out
+            let (fee, amount_out) = match fee_on_input {
+                true => (step.fee, step.amount_out),
+                false => {
+                    let fee = fee_share(step.amount_out, fees.fee).map_err(AmmError::from)?;
+                    (fee, step.amount_out - fee)
+                }
+            };
+            result.amount_out = result
+                .amount_out
+                .checked_add(amount_out)
+                .ok_or(AmmError::Overflow)?;
+
+            // Credit the LP share of the fee to the liquidity in range
+            let protocol_cut = fee_share(fee, fees.protocol_fee).map_err(AmmError::from)?;
+            let referral_cut = fee_share(fee, fees.referral_fee).map_err(AmmError::from)?;
+            result.fee += fee;
+            result.protocol_fee += protocol_cut;
+            result.referral_fee += referral_cut;
+            let lp_fee = ((fee - protocol_cut - referral_cut) as u128) << 64;
+            if let Some(growth) = lp_fee.checked_div(self.liquidity) {
+                match fees.fee_in_x(is_x) {
+                    true => {
+                        self.fee_growth_global_x = self.fee_growth_global_x.wrapping_add(growth)
+                    }
+                    false => {
+                        self.fee_growth_global_y = self.fee_growth_global_y.wrapping_add(growth)
+                    }
+                }
+            }
+
+            self.sqrt_price = step.sqrt_price;
+            if step.sqrt_price == target {
+                if used {
+                    self.cross(arrays, index, is_x)?;
+                }
+                self.tick = match is_x {
+                    true => index - 1,
+                    false => index,
+                };
+            } else {
+                self.tick = tick_at_sqrt_price(self.sqrt_price)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    // Moves the price across a tick in use, updating the liquidity in range
+    fn cross(&mut self, arrays: &mut [TickArray], index: i32, is_x: bool) -> Result<()> {
+        let (array, slot) = self.find_tick(arrays, index)?;
+        let tick = &mut arrays[array].ticks[slot];
+        tick.fee_growth_outside_x = self
+            .fee_growth_global_x
+            .wrapping_sub(tick.fee_growth_outside_x);
+        tick.fee_growth_outside_y = self
+            .fee_growth_global_y
+            .wrapping_sub(tick.fee_growth_outside_y);
+
+        self.liquidity = match is_x {
+            // Moving down leaves the ranges the tick opens and enters the ones it closes
+            true => self.liquidity.checked_add_signed(-tick.liquidity_net),
+            false => self.liquidity.checked_add_signed(tick.liquidity_net),
+        }
+        .ok_or(AmmError::Overflow)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(tick: i32) -> ConcentratedPool {
+        ConcentratedPool {
+            config: Pubkey::default(),
+            tick_spacing: 10,
+            sqrt_price: sqrt_price_at_tick(tick).unwrap(),
+            tick,
+            liquidity: 0,
+            fee_growth_global_x: 0,
+            fee_growth_global_y: 0,
+            bump: 0,
+        }
+    }
+
+    // Every tick array of `pool` holding a tick in [from, to]
+    fn tick_arrays(pool: &ConcentratedPool, from: i32, to: i32) -> Vec<TickArray> {
+        (pool.tick_array_start(from)..=to)
+            .step_by(pool.tick_array_span() as usize)
+            .map(|start_tick| TickArray {
+                pool: Pubkey::default(),
+                start_tick,
+                ticks: [TickState::default(); TICK_ARRAY_SIZE],
+                bump: 0,
+            })
+            .collect()
+    }
+
+    fn unused(arrays: &[TickArray]) -> bool {
+        arrays
+            .iter()
+            .all(|array| array.ticks.iter().all(|tick| tick.liquidity_gross == 0))
+    }
+
+    // The swap fee, on the input, and its protocol share
+    fn fees(fee: u16, protocol_fee: u16) -> PoolState {
+        PoolState {
+            curve_type: amm_quote::CurveType::ConstantProduct,
+            reserve_x: 0,
+            reserve_y: 0,
+            lp_supply: 0,
+            fee,
+            protocol_fee,
+            amp: 0,
+            precision: 0,
+            fee_side: amm_quote::FeeSide::Input,
+            referral_fee: 0,
+        }
+    }
+
+    fn position(tick_lower: i32, tick_upper: i32) -> Position {
+        Position {
+            pool: Pubkey::default(),
+            owner: Pubkey::default(),
+            tick_lower,
+            tick_upper,
+            liquidity: 0,
+            fee_growth_inside_x: 0,
+            fee_growth_inside_y: 0,
+            fees_owed_x: 0,
+            fees_owed_y: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn positions_deposit_by_range() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -200, 200);
+        let (x, y) = pool
+            .modify_position(&mut arrays, &mut position(-100, 100), 1_000_000_000)
+            .unwrap();
+        assert!(x > 0 && y > 0 && x.abs_diff(y) <= 1);
+        assert_eq!(pool.liquidity, 1_000_000_000);
+
+        // Entirely above the price is all X, entirely below is all Y
+        let (x, y) = pool
+            .modify_position(&mut arrays, &mut position(100, 200), 1_000_000_000)
+            .unwrap();
+        assert!(x > 0 && y == 0);
+        let (x, y) = pool
+            .modify_position(&mut arrays, &mut position(-200, -100), 1_000_000_000)
+            .unwrap();
+        assert!(x == 0 && y > 0);
+        assert_eq!(pool.liquidity, 1_000_000_000);
+    }
+
+    #[test]
+    fn removing_liquidity_never_returns_more_than_was_added() {
+        let mut pool = pool(5);
+        let mut arrays = tick_arrays(&pool, -60, 120);
+        let mut position = position(-60, 120);
+        let (x_in, y_in) = pool
+            .modify_position(&mut arrays, &mut position, 123_456_789)
+            .unwrap();
+        let (x_out, y_out) = pool
+            .modify_position(&mut arrays, &mut position, -123_456_789)
+            .unwrap();
+        assert!(x_out <= x_in && y_out <= y_in);
+        assert_eq!(pool.liquidity, 0);
+        assert!(unused(&arrays));
+        assert!(pool
+            .modify_position(&mut arrays, &mut position, -1)
+            .is_err());
+    }
+
+    #[test]
+    fn swaps_cross_ticks_and_pay_fees_to_the_range() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -1_000, 1_000);
+        let mut wide = position(-1_000, 1_000);
+        let mut narrow = position(-100, 100);
+        pool.modify_position(&mut arrays, &mut wide, 1_000_000_000_000)
+            .unwrap();
+        pool.modify_position(&mut arrays, &mut narrow, 1_000_000_000_000)
+            .unwrap();
+        assert_eq!(pool.liquidity, 2_000_000_000_000);
+
+        // Large enough to push the price below the narrow range
+        let result = pool
+            .swap(&mut arrays, true, 20_000_000_000, &fees(30, 1_000))
+            .unwrap();
+        assert_eq!(result.amount_in, 20_000_000_000);
+        assert!(result.amount_out > 0 && result.amount_out < result.amount_in);
+        assert!(pool.tick < -100);
+        assert_eq!(pool.liquidity, 1_000_000_000_000);
+        assert_eq!(result.protocol_fee, result.fee / 10);
+
+        // Both positions earned fees, the wide one more since it stayed in range
+        pool.modify_position(&mut arrays, &mut wide, 0).unwrap();
+        pool.modify_position(&mut arrays, &mut narrow, 0).unwrap();
+        assert!(narrow.fees_owed_x > 0 && wide.fees_owed_x > narrow.fees_owed_x);
+        assert!(wide.fees_owed_x + narrow.fees_owed_x <= result.fee - result.protocol_fee);
+        assert_eq!(wide.fees_owed_y + narrow.fees_owed_y, 0);
+
+        // Swapping back re-enters the narrow range
+        pool.swap(&mut arrays, false, 20_000_000_000, &fees(30, 1_000))
+            .unwrap();
+        assert!(pool.tick >= -100);
+        assert_eq!(pool.liquidity, 2_000_000_000_000);
+    }
+
+    #[test]
+    fn swaps_take_the_fee_on_the_pool_fee_side() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -1_000, 1_000);
+        let mut position = position(-1_000, 1_000);
+        pool.modify_position(&mut arrays, &mut position, 1_000_000_000_000)
+            .unwrap();
+
+        // Off the output, in Y for an X swap, with the referrer's share left out too
+        let fees = PoolState {
+            fee_side: amm_quote::FeeSide::Output,
+            referral_fee: 2_000,
+            ..fees(30, 1_000)
+        };
+        let result = pool.swap(&mut arrays, true, 1_000_000, &fees).unwrap();
+        assert_eq!(result.amount_in, 1_000_000);
+        assert_eq!(result.fee, (result.amount_out + result.fee) * 30 / 10_000);
+        assert_eq!(result.protocol_fee, result.fee / 10);
+        assert_eq!(result.referral_fee, result.fee / 5);
+
+        pool.modify_position(&mut arrays, &mut position, 0).unwrap();
+        assert_eq!(position.fees_owed_x, 0);
+        let lp_fee = result.fee - result.protocol_fee - result.referral_fee;
+        assert!(position.fees_owed_y <= lp_fee && position.fees_owed_y + 1 >= lp_fee);
+    }
+
+    #[test]
+    fn swaps_need_every_tick_array_they_move_through() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -400, 400);
+        pool.modify_position(&mut arrays, &mut position(-400, 400), 1_000_000_000)
+            .unwrap();
+
+        // Without the array below, the price can't leave the one it is in
+        let mut current: Vec<TickArray> = arrays
+            .iter()
+            .filter(|array| array.start_tick >= 0)
+            .cloned()
+            .collect();
+        assert!(pool
+            .clone()
+            .swap(&mut current, true, 100_000, &fees(30, 0))
+            .is_err());
+        pool.swap(&mut arrays, true, 100_000, &fees(30, 0)).unwrap();
+        assert!(pool.tick < 0);
+    }
+
+    #[test]
+    fn swaps_fail_past_the_last_tick() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -10, 10);
+        pool.modify_position(&mut arrays, &mut position(-10, 10), 1_000_000)
+            .unwrap();
+        assert!(pool
+            .swap(&mut arrays, true, u64::MAX / 4, &fees(30, 0))
+            .is_err());
+    }
+
+    #[test]
+    fn ticks_are_not_capped() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, 0, 10_000);
+        for lower in (10..10_000).step_by(20) {
+            pool.modify_position(
+                &mut arrays,
+                &mut position(lower, lower + 10),
+                MIN_POSITION_LIQUIDITY as i128,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn positions_hold_the_minimum_liquidity_or_none() {
+        let mut pool = pool(0);
+        let mut arrays = tick_arrays(&pool, -10, 10);
+        let mut position = position(-10, 10);
+        assert!(pool
+            .modify_position(
+                &mut arrays,
+                &mut position,
+                MIN_POSITION_LIQUIDITY as i128 - 1
+            )
+            .is_err());
+        pool.modify_position(&mut arrays, &mut position, MIN_POSITION_LIQUIDITY as i128)
+            .unwrap();
+
+        // Can't be drained down to dust, only emptied, which frees its ticks
+        assert!(pool
+            .modify_position(&mut arrays, &mut position, -1)
+            .is_err());
+        pool.modify_position(
+            &mut arrays,
+            &mut position,
+            -(MIN_POSITION_LIQUIDITY as i128),
+        )
+        .unwrap();
+        assert!(unused(&arrays));
+    }
+}
@@ -1,8 +1,100 @@
 use anchor_lang::prelude::*;
 
+use crate::{
+    dynamic_fee::{decayed_volatility, dynamic_fee, price_movement},
+    errors::AmmError,
+    events::CircuitBreakerTripped,
+    quote::{PoolState, MAX_PRECISION},
+    tick_math::price_pair,
+};
+
+// Highest swap fee a pool can be created with or updated to, in basis points
+pub const MAX_FEE: u16 = 1_000;
+
+// Symbol of every pool's LP token; its name, derived from the pair, tells them apart
+pub const LP_SYMBOL: &str = "LP";
+
+// Bounds on the StableSwap amplification coefficient and on how fast the authority can
+// move it
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 1_000_000;
+pub const MAX_AMP_CHANGE: u64 = 10;
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+
+// Bits of Config::paused, each halting one kind of operation
+pub const PAUSE_SWAPS: u8 = 1 << 0;
+pub const PAUSE_DEPOSITS: u8 = 1 << 1;
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 2;
+pub const PAUSE_ALL: u8 = PAUSE_SWAPS | PAUSE_DEPOSITS | PAUSE_WITHDRAWALS;
+
+// Current Config layout; migrate_config upgrades ConfigV0, the only older one deployed
+pub const CONFIG_VERSION: u8 = 1;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveType {
+    ConstantProduct, // x * y = k
+    StableSwap,      // Curve-style invariant for pegged pairs, see amm_quote::StableSwap
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum FeeSide {
+    Input,  // Swap fee taken from the input before it is swapped
+    Output, // Swap fee taken from the output after the swap
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Config {
+    pub seed: u64,                         // Seed to be able to create different pools / configs
+    pub authority: Option<Pubkey>,         // If we want an authority to lock the config account
+    pub pending_authority: Option<Pubkey>, // Proposed authority that still has to accept
+    pub mint_x: Pubkey,                    // Token X
+    pub mint_y: Pubkey,                    // Token Y
+    pub fee: u16,                          // Swap fee in basis points, base fee in dynamic mode
+    pub min_fee: u16,                      // Lowest dynamic swap fee in basis points
+    pub max_fee: u16,                      // Highest dynamic swap fee, 0 = fixed fee mode
+    pub volatility: u64,                   // Recent price movement in basis points, see dynamic_fee.rs
+    pub volatility_timestamp: i64,         // Time volatility was last updated
+    pub fee_timelock: i64,                 // Delay before a fee update applies, 0 = immediate
+    pub pending_fee: Option<u16>,          // Fee queued by update_fee
+    pub pending_fee_eta: i64,              // Time from which pending_fee can be applied
+    pub protocol_fee: u16,                 // Protocol share of the swap fee, in basis points
+    pub pending_protocol_fee: Option<u16>, // Protocol share queued by update_protocol_fee
+    pub pending_protocol_fee_eta: i64,     // Time from which pending_protocol_fee can be applied
+    pub protocol_fees_x: u64,              // Accrued protocol fees in token X, held in vault_x
+    pub protocol_fees_y: u64,              // Accrued protocol fees in token Y, held in vault_y
+    pub concentrated_x: u64,               // Token X in vault_x owned by concentrated positions
+    pub concentrated_y: u64,               // Token Y in vault_y owned by concentrated positions
+    pub flash_loan_amount: u64,            // Outstanding flash loan, 0 when there is none
+    pub flash_loan_fee: u64,               // Fee owed on top of the outstanding flash loan
+    pub flash_loan_is_x: bool,             // If the outstanding flash loan is in token X
+    pub curve_type: CurveType,             // Curve used to price swaps
+    pub amp_initial: u64,                  // StableSwap amplification when the ramp started
+    pub amp_target: u64,                   // StableSwap amplification once the ramp ends
+    pub amp_ramp_start: i64,               // Time the amplification ramp started
+    pub amp_ramp_end: i64,                 // Time the amplification ramp ends
+    pub max_trade: u16,                    // Max swap input as a share of its reserve in bps, 0 = no cap
+    pub breaker_threshold: u16,            // Price move in bps in a window that trips the breaker, 0 = off
+    pub breaker_window: i64,               // Length of a circuit breaker window in seconds
+    pub breaker_timestamp: i64,            // Start of the current circuit breaker window
+    pub breaker_reserve_x: u64,            // Reserves of X at the start of the window
+    pub breaker_reserve_y: u64,            // Reserves of Y at the start of the window
+    pub breaker_tripped: bool,             // Swaps are halted until the authority resets the breaker
+    pub paused: u8,                        // PAUSE_* bits of the operations currently halted
+    pub config_bump: u8,                   // Bump seed for the config account
+    pub lp_bump: u8,                       // Bump seed for the LP token
+    pub version: u8,                       // Layout version, CONFIG_VERSION once migrated
+    pub lp_decimals: u8,                   // Decimals of the LP mint, see quote::lp_decimals
+    pub precision: u8,                     // Decimal precision of the LP share math, see quote::share_precision
+    pub fee_side: FeeSide,                 // Side of the curve swaps the fee is taken from
+    pub referral_fee: u16,                 // Referrer share of the swap fee in basis points
+    pub farms: u16,                        // Farms on the LP token that close_farm hasn't closed
+}
+
+// Config as first deployed, before it had a layout version. Stored under the same
+// discriminator, so it is told apart by its size. Only read by Config::migrate
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct ConfigV0 {
     pub seed: u64,                 // Seed to be able to create different pools / configs
     pub authority: Option<Pubkey>, // If we want an authority to lock the config account
     pub mint_x: Pubkey,            // Token X
@@ -12,3 +104,363 @@ pub struct Config {
     pub config_bump: u8,           // Bump seed for the config account
     pub lp_bump: u8,               // Bump seed for the LP token
 }
+
+impl From<ConfigV0> for Config {
+    // Everything added since starts out off or empty, as in a fresh pool with the
+    // same fee. `locked` halted every operation
+    fn from(config: ConfigV0) -> Config {
+        Config {
+            seed: config.seed,
+            authority: config.authority,
+            pending_authority: None,
+            mint_x: config.mint_x,
+            mint_y: config.mint_y,
+            fee: config.fee,
+            min_fee: 0,
+            max_fee: 0,
+            volatility: 0,
+            volatility_timestamp: 0,
+            fee_timelock: 0,
+            pending_fee: None,
+            pending_fee_eta: 0,
+            protocol_fee: 0,
+            pending_protocol_fee: None,
+            pending_protocol_fee_eta: 0,
+            protocol_fees_x: 0,
+            protocol_fees_y: 0,
+            concentrated_x: 0,
+            concentrated_y: 0,
+            flash_loan_amount: 0,
+            flash_loan_fee: 0,
+            flash_loan_is_x: false,
+            curve_type: CurveType::ConstantProduct,
+            amp_initial: 0,
+            amp_target: 0,
+            amp_ramp_start: 0,
+            amp_ramp_end: 0,
+            max_trade: 0,
+            breaker_threshold: 0,
+            breaker_window: 0,
+            breaker_timestamp: 0,
+            breaker_reserve_x: 0,
+            breaker_reserve_y: 0,
+            breaker_tripped: false,
+            paused: match config.locked {
+                true => PAUSE_ALL,
+                false => 0,
+            },
+            config_bump: config.config_bump,
+            lp_bump: config.lp_bump,
+            version: CONFIG_VERSION,
+            // Every LP mint used to have 6 decimals. The pool mints aren't at hand here,
+            // so the share math gets the finest precision rather than one derived from them
+            lp_decimals: 6,
+            precision: MAX_PRECISION,
+            fee_side: FeeSide::Input,
+            referral_fee: 0,
+            farms: 0,
+        }
+    }
+}
+
+impl Config {
+    // Decodes the data of a ConfigV0 account and upgrades it to CONFIG_VERSION. Accounts
+    // in the current layout are rejected
+    pub fn migrate(data: &[u8]) -> Result<Config> {
+        require!(
+            data.starts_with(Config::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        let bytes = &data[Config::DISCRIMINATOR.len()..];
+        require!(
+            bytes.len() == ConfigV0::INIT_SPACE,
+            AmmError::InvalidConfigVersion
+        );
+
+        Ok(Config::from(ConfigV0::deserialize(&mut &bytes[..])?))
+    }
+
+    pub fn check_authority(&self, authority: &Pubkey) -> Result<()> {
+        require!(
+            self.version == CONFIG_VERSION,
+            AmmError::InvalidConfigVersion
+        );
+        require!(self.authority.is_some(), AmmError::NoAuthoritySet);
+        require!(
+            self.authority.unwrap() == *authority,
+            AmmError::InvalidAuthority
+        );
+        Ok(())
+    }
+
+    // If every operation in `operations` (PAUSE_* bits) is currently allowed. Nothing is
+    // while a flash loan is outstanding, since the vault is short of the borrowed tokens
+    pub fn check_allowed(&self, operations: u8) -> Result<()> {
+        require!(
+            self.version == CONFIG_VERSION,
+            AmmError::InvalidConfigVersion
+        );
+        require!(self.paused & operations == 0, AmmError::PoolLocked);
+        require!(
+            self.flash_loan_amount == 0,
+            AmmError::FlashLoanActive
+        );
+        Ok(())
+    }
+
+    // Vault balances minus accrued protocol fees and concentrated liquidity, i.e. the
+    // reserves backing the LP tokens
+    pub fn reserves(&self, vault_x: u64, vault_y: u64) -> Result<(u64, u64)> {
+        Ok((
+            vault_x
+                .checked_sub(self.protocol_fees_x)
+                .and_then(|x| x.checked_sub(self.concentrated_x))
+                .ok_or(AmmError::Underflow)?,
+            vault_y
+                .checked_sub(self.protocol_fees_y)
+                .and_then(|y| y.checked_sub(self.concentrated_y))
+                .ok_or(AmmError::Underflow)?,
+        ))
+    }
+
+    // Sets aside a protocol cut in token X (is_x) or Y. The tokens stay in the vault but
+    // are excluded from the reserves used for pricing
+    pub fn credit_protocol_fee(&mut self, is_x: bool, amount: u64) -> Result<()> {
+        match is_x {
+            true => {
+                self.protocol_fees_x = self
+                    .protocol_fees_x
+                    .checked_add(amount)
+                    .ok_or(AmmError::Overflow)?
+            }
+            false => {
+                self.protocol_fees_y = self
+                    .protocol_fees_y
+                    .checked_add(amount)
+                    .ok_or(AmmError::Overflow)?
+            }
+        }
+        Ok(())
+    }
+
+    // Moves token X (is_x) or Y in (delta > 0) or out of the concentrated liquidity
+    pub fn update_concentrated(&mut self, is_x: bool, delta: i128) -> Result<()> {
+        let reserve = match is_x {
+            true => &mut self.concentrated_x,
+            false => &mut self.concentrated_y,
+        };
+        *reserve = i128::from(*reserve)
+            .checked_add(delta)
+            .and_then(|reserve| u64::try_from(reserve).ok())
+            .ok_or(AmmError::InsufficientBalance)?;
+        Ok(())
+    }
+
+    // StableSwap amplification at `now`, moving linearly from amp_initial to amp_target
+    // over the ramp
+    pub fn amp(&self, now: i64) -> u64 {
+        if now >= self.amp_ramp_end || self.amp_ramp_end <= self.amp_ramp_start {
+            return self.amp_target;
+        }
+
+        let elapsed = (now.max(self.amp_ramp_start) - self.amp_ramp_start) as u128;
+        let duration = (self.amp_ramp_end - self.amp_ramp_start) as u128;
+        let (initial, target) = (self.amp_initial as u128, self.amp_target as u128);
+        match target >= initial {
+            true => (initial + (target - initial) * elapsed / duration) as u64,
+            false => (initial - (initial - target) * elapsed / duration) as u64,
+        }
+    }
+
+    // Swap fee at `now`: the fixed fee, or in dynamic mode the fee implied by the recent
+    // volatility
+    pub fn swap_fee(&self, now: i64) -> u16 {
+        match self.max_fee {
+            0 => self.fee,
+            _ => dynamic_fee(
+                self.fee,
+                self.min_fee,
+                self.max_fee,
+                decayed_volatility(self.volatility, self.volatility_timestamp, now),
+            ),
+        }
+    }
+
+    // If `fee` can be used as the fixed or base fee of this pool
+    pub fn check_fee(&self, fee: u16) -> Result<()> {
+        require!(
+            fee <= MAX_FEE && (self.max_fee == 0 || (self.min_fee..=self.max_fee).contains(&fee)),
+            AmmError::InvalidFee
+        );
+        Ok(())
+    }
+
+    // Checks a swap of `amount_in` X (is_x) or Y moving the reserves from `before` to
+    // `after` against the trade cap and circuit breaker, and feeds its price move into the
+    // volatility in dynamic mode. The swap that trips the breaker still goes through, the
+    // trade cap bounds how far it can move the price
+    pub fn record_trade(
+        &mut self,
+        is_x: bool,
+        amount_in: u64,
+        before: (u64, u64),
+        after: (u64, u64),
+    ) -> Result<()> {
+        let reserve_in = match is_x {
+            true => before.0,
+            false => before.1,
+        };
+        self.record_price_move(amount_in, reserve_in, before, after)
+    }
+
+    // Same checks for a swap against the concentrated liquidity, which moves its sqrt
+    // price rather than the reserves. `reserve_in` is all of the input token backing the
+    // pool's liquidity. Both kinds of swap share the breaker window, since arbitrage keeps
+    // the two prices in line
+    pub fn record_concentrated_trade(
+        &mut self,
+        amount_in: u64,
+        reserve_in: u64,
+        sqrt_price_before: u128,
+        sqrt_price_after: u128,
+    ) -> Result<()> {
+        self.record_price_move(
+            amount_in,
+            reserve_in,
+            price_pair(sqrt_price_before),
+            price_pair(sqrt_price_after),
+        )
+    }
+
+    // `before` and `after` are reserves, or any pair with the same Y / X ratio
+    fn record_price_move(
+        &mut self,
+        amount_in: u64,
+        reserve_in: u64,
+        before: (u64, u64),
+        after: (u64, u64),
+    ) -> Result<()> {
+        require!(!self.breaker_tripped, AmmError::CircuitBreakerTripped);
+
+        if self.max_trade != 0 {
+            require!(
+                amount_in as u128 * 10_000 <= reserve_in as u128 * self.max_trade as u128,
+                AmmError::TradeTooLarge
+            );
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+
+        if self.max_fee != 0 {
+            self.volatility = decayed_volatility(self.volatility, self.volatility_timestamp, now)
+                .saturating_add(price_movement(before, after));
+            self.volatility_timestamp = now;
+        }
+
+        if self.breaker_threshold != 0 {
+            // Measure against the price at the start of the window, opening a new one
+            // once it has run out
+            if self.breaker_timestamp == 0
+                || now.saturating_sub(self.breaker_timestamp) >= self.breaker_window
+            {
+                self.breaker_timestamp = now;
+                (self.breaker_reserve_x, self.breaker_reserve_y) = before;
+            }
+
+            let movement = price_movement((self.breaker_reserve_x, self.breaker_reserve_y), after);
+            if movement > self.breaker_threshold as u64 {
+                self.breaker_tripped = true;
+
+                // Same address the instruction was given, derived so callers don't have to
+                let config = Pubkey::create_program_address(
+                    &[
+                        b"config",
+                        &self.seed.to_le_bytes(),
+                        &[self.config_bump],
+                    ],
+                    &crate::ID,
+                )
+                .map_err(|_| AmmError::BumpError)?;
+                emit!(CircuitBreakerTripped {
+                    config,
+                    price_movement: movement,
+                    window_start: self.breaker_timestamp,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Pool as seen by the quote functions, with the current fee and amplification
+    pub fn pool_state(&self, reserve_x: u64, reserve_y: u64, lp_supply: u64) -> Result<PoolState> {
+        let now = Clock::get()?.unix_timestamp;
+        Ok(PoolState {
+            curve_type: self.curve_type.into(),
+            reserve_x,
+            reserve_y,
+            lp_supply,
+            fee: self.swap_fee(now),
+            protocol_fee: self.protocol_fee,
+            amp: self.amp(now),
+            precision: self.precision,
+            fee_side: self.fee_side.into(),
+            // Only swaps with a referrer pay one, see Swap
+            referral_fee: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Config account exactly as the first deployed program wrote it
+    fn baseline_account(locked: bool) -> Vec<u8> {
+        let mut data = Config::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&7u64.to_le_bytes()); // seed
+        data.push(1); // authority: Some
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&[2; 32]); // mint_x
+        data.extend_from_slice(&[3; 32]); // mint_y
+        data.extend_from_slice(&30u16.to_le_bytes()); // fee
+        data.push(locked as u8);
+        data.push(254); // config_bump
+        data.push(253); // lp_bump
+        data
+    }
+
+    #[test]
+    fn migrates_a_baseline_account() {
+        let data = baseline_account(false);
+        assert_eq!(data.len(), 118);
+
+        let config = Config::migrate(&data).unwrap();
+        assert_eq!(config.seed, 7);
+        assert_eq!(config.authority, Some(Pubkey::new_from_array([1; 32])));
+        assert_eq!(config.pending_authority, None);
+        assert_eq!(config.mint_x, Pubkey::new_from_array([2; 32]));
+        assert_eq!(config.mint_y, Pubkey::new_from_array([3; 32]));
+        assert_eq!((config.fee, config.min_fee, config.max_fee), (30, 0, 0));
+        assert_eq!((config.protocol_fee, config.fee_timelock), (0, 0));
+        assert_eq!((config.config_bump, config.lp_bump), (254, 253));
+        assert_eq!(config.curve_type, CurveType::ConstantProduct);
+        assert_eq!((config.max_trade, config.breaker_threshold), (0, 0));
+        assert_eq!(config.paused, 0);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!((config.lp_decimals, config.precision), (6, MAX_PRECISION));
+        assert_eq!((config.fee_side, config.referral_fee), (FeeSide::Input, 0));
+
+        // A locked pool stays halted until the authority resumes it
+        let config = Config::migrate(&baseline_account(true)).unwrap();
+        assert_eq!(config.paused, PAUSE_ALL);
+    }
+
+    #[test]
+    fn migrates_once() {
+        let config = Config::migrate(&baseline_account(false)).unwrap();
+        let mut data = Config::DISCRIMINATOR.to_vec();
+        config.serialize(&mut data).unwrap();
+        assert!(Config::migrate(&data).is_err());
+    }
+}
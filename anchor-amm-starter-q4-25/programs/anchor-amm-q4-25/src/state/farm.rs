@@ -0,0 +1,217 @@
+use anchor_lang::prelude::*;
+
+use amm_quote::mul_div;
+
+use crate::errors::AmmError;
+
+// Liquidity mining for a pool's LP token. The farm emits `reward_per_second` reward
+// tokens between start_time and end_time, split between the stakers pro rata through a
+// per-share accumulator. Time with nothing staked doesn't count towards the schedule, so
+// every reward the farm is funded with goes to a staker
+#[account]
+#[derive(InitSpace)]
+pub struct Farm {
+    pub config: Pubkey,         // Pool whose LP tokens are staked
+    pub reward_mint: Pubkey,    // Token paid out as rewards
+    pub reward_per_second: u64, // Emission rate over the schedule
+    pub start_time: i64,        // Emissions start, pushed back while nothing is staked
+    pub end_time: i64,          // Emissions stop, pushed back while nothing is staked
+    pub total_staked: u64,      // LP tokens held in the farm vault
+    pub rewards_owed: u64,      // Rewards credited to stakes and not harvested yet
+    pub reward_per_share: u128, // Rewards per staked LP token since start, Q64.64
+    pub last_update: i64,       // Last time reward_per_share was brought up to date
+    pub bump: u8,               // Bump seed for the farm account
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Stake {
+    pub farm: Pubkey,           // Farm the LP tokens are staked in
+    pub owner: Pubkey,          // Only the owner can unstake or harvest
+    pub amount: u64,            // Staked LP tokens
+    pub reward_per_share: u128, // Farm reward_per_share at the last update
+    pub rewards_owed: u64,      // Harvestable rewards
+    pub bump: u8,               // Bump seed for the stake account
+}
+
+impl Farm {
+    // Rewards emitted over the whole schedule, which the farm is funded with up front
+    pub fn total_rewards(&self) -> Result<u64> {
+        let duration = (self.end_time - self.start_time) as u64;
+        Ok(self
+            .reward_per_second
+            .checked_mul(duration)
+            .ok_or(AmmError::Overflow)?)
+    }
+
+    // Accrues the rewards emitted since the last update into reward_per_share. With
+    // nothing staked the schedule is pushed back instead, keeping what was left of it
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        let from = self.last_update.max(self.start_time);
+        let to = now.min(self.end_time);
+
+        if to > from {
+            match self.total_staked {
+                0 => {
+                    let idle = now - from;
+                    self.start_time = self
+                        .start_time
+                        .checked_add(idle)
+                        .ok_or(AmmError::Overflow)?;
+                    self.end_time = self
+                        .end_time
+                        .checked_add(idle)
+                        .ok_or(AmmError::Overflow)?;
+                }
+                total_staked => {
+                    let rewards = (to - from) as u128 * self.reward_per_second as u128;
+                    let growth = mul_div(rewards, 1 << 64, total_staked as u128, false)
+                        .ok_or(AmmError::Overflow)?;
+                    // Wraps like the concentrated fee growth; only differences are ever used
+                    self.reward_per_share = self.reward_per_share.wrapping_add(growth);
+                }
+            }
+        }
+        self.last_update = self.last_update.max(now);
+
+        Ok(())
+    }
+
+    // Credits the rewards the stake earned at its old amount, then stakes (delta > 0) or
+    // unstakes (delta < 0) LP tokens. The farm has to be accrued first
+    pub fn modify_stake(&mut self, stake: &mut Stake, delta: i64) -> Result<()> {
+        let amount = stake
+            .amount
+            .checked_add_signed(delta)
+            .ok_or(AmmError::InsufficientBalance)?;
+        let total_staked = self
+            .total_staked
+            .checked_add_signed(delta)
+            .ok_or(AmmError::Overflow)?;
+
+        let earned = mul_div(
+            stake.amount as u128,
+            self.reward_per_share.wrapping_sub(stake.reward_per_share),
+            1 << 64,
+            false,
+        )
+        .and_then(|earned| u64::try_from(earned).ok())
+        .ok_or(AmmError::Overflow)?;
+        stake.rewards_owed = stake
+            .rewards_owed
+            .checked_add(earned)
+            .ok_or(AmmError::Overflow)?;
+        self.rewards_owed = self
+            .rewards_owed
+            .checked_add(earned)
+            .ok_or(AmmError::Overflow)?;
+        stake.reward_per_share = self.reward_per_share;
+
+        stake.amount = amount;
+        self.total_staked = total_staked;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn farm() -> Farm {
+        Farm {
+            config: Pubkey::default(),
+            reward_mint: Pubkey::default(),
+            reward_per_second: 100,
+            start_time: 1_000,
+            end_time: 2_000,
+            total_staked: 0,
+            rewards_owed: 0,
+            reward_per_share: 0,
+            last_update: 0,
+            bump: 0,
+        }
+    }
+
+    fn stake() -> Stake {
+        Stake {
+            farm: Pubkey::default(),
+            owner: Pubkey::default(),
+            amount: 0,
+            reward_per_share: 0,
+            rewards_owed: 0,
+            bump: 0,
+        }
+    }
+
+    fn update(farm: &mut Farm, stake: &mut Stake, now: i64, delta: i64) {
+        farm.accrue(now).unwrap();
+        farm.modify_stake(stake, delta).unwrap();
+    }
+
+    #[test]
+    fn rewards_are_split_pro_rata() {
+        let mut farm = farm();
+        let (mut alice, mut bob) = (stake(), stake());
+
+        // Nothing accrues before the start
+        update(&mut farm, &mut alice, 500, 1_000);
+        update(&mut farm, &mut alice, 1_000, 0);
+        assert_eq!(alice.rewards_owed, 0);
+
+        // Alice alone for 100s, then Bob joins with three times her stake
+        update(&mut farm, &mut bob, 1_100, 3_000);
+        update(&mut farm, &mut alice, 1_100, 0);
+        assert_eq!(alice.rewards_owed, 10_000);
+        assert_eq!(bob.rewards_owed, 0);
+
+        // Shared 1:3 for 200s
+        update(&mut farm, &mut alice, 1_300, 0);
+        update(&mut farm, &mut bob, 1_300, 0);
+        assert_eq!(alice.rewards_owed, 15_000);
+        assert_eq!(bob.rewards_owed, 15_000);
+
+        // Emissions stop at the end of the schedule
+        update(&mut farm, &mut alice, 5_000, -1_000);
+        update(&mut farm, &mut bob, 5_000, -3_000);
+        let total = alice.rewards_owed + bob.rewards_owed;
+        assert_eq!(farm.rewards_owed, total);
+        assert!(total <= farm.total_rewards().unwrap());
+        assert!(total + 2 >= farm.total_rewards().unwrap());
+        assert_eq!(farm.total_staked, 0);
+    }
+
+    #[test]
+    fn idle_time_pushes_the_schedule_back() {
+        let mut farm = farm();
+        let mut alice = stake();
+
+        // The first 500s pass with nothing staked, so the schedule starts with Alice
+        update(&mut farm, &mut alice, 1_500, 1_000);
+        assert_eq!((farm.start_time, farm.end_time), (1_500, 2_500));
+        update(&mut farm, &mut alice, 2_000, 0);
+        assert_eq!(alice.rewards_owed, 50_000);
+
+        // She leaves for 1000s, during which nothing is emitted
+        update(&mut farm, &mut alice, 2_100, -1_000);
+        update(&mut farm, &mut alice, 3_100, 1_000);
+        assert_eq!(farm.end_time, 3_500);
+        update(&mut farm, &mut alice, 9_000, 0);
+        assert_eq!(alice.rewards_owed, farm.total_rewards().unwrap());
+    }
+
+    #[test]
+    fn unstake_is_bounded_by_the_stake() {
+        let mut farm = farm();
+        let (mut alice, mut bob) = (stake(), stake());
+        update(&mut farm, &mut alice, 1_000, 1_000);
+        update(&mut farm, &mut bob, 1_000, 1_000);
+
+        farm.accrue(1_500).unwrap();
+        assert!(farm.modify_stake(&mut alice, -1_001).is_err());
+        assert_eq!((alice.amount, farm.total_staked), (1_000, 2_000));
+        farm.modify_stake(&mut alice, -1_000).unwrap();
+        assert_eq!((alice.amount, alice.rewards_owed), (0, 25_000));
+        assert_eq!(farm.total_staked, 1_000);
+    }
+}
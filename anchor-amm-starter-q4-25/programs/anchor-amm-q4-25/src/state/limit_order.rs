@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+
+// A resting order selling `amount_in` of one pool token for at least `min_amount_out` of
+// the other, i.e. at a limit price of min_amount_out / amount_in. The input is escrowed
+// in the order's own token account until a keeper fills it against the pool or the
+// owner cancels it. Orders fill in full or not at all
+#[account]
+#[derive(InitSpace)]
+pub struct LimitOrder {
+    pub config: Pubkey,      // Pool the order fills against
+    pub owner: Pubkey,       // Receives the output; the only one who can cancel
+    pub id: u64,             // Chosen by the owner to tell their orders apart
+    pub is_x: bool,          // Sells token X for Y, otherwise Y for X
+    pub amount_in: u64,      // Input tokens escrowed in the order vault
+    pub min_amount_out: u64, // Least output amount_in has to buy
+    pub tip: u64,            // Lamports on top of rent paid to the keeper that fills it
+    pub expiration: i64,     // Time from which the order can no longer be filled
+    pub bump: u8,            // Bump seed for the order account
+}
+
+impl LimitOrder {
+    // If a fill paying the owner `amount_out` at `now` honours the order
+    pub fn fills(&self, amount_out: u64, now: i64) -> bool {
+        now < self.expiration && amount_out >= self.min_amount_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> LimitOrder {
+        LimitOrder {
+            config: Pubkey::default(),
+            owner: Pubkey::default(),
+            id: 0,
+            is_x: true,
+            amount_in: 1_000,
+            min_amount_out: 2_000,
+            tip: 0,
+            expiration: 100,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn fills_at_or_above_the_limit_price() {
+        let order = order();
+        assert!(!order.fills(1_999, 0));
+        assert!(order.fills(2_000, 0));
+        assert!(order.fills(u64::MAX, 0));
+    }
+
+    #[test]
+    fn expired_orders_never_fill() {
+        let order = order();
+        assert!(order.fills(2_000, 99));
+        assert!(!order.fills(2_000, 100));
+        assert!(!order.fills(u64::MAX, 1_000));
+    }
+}
@@ -1,3 +1,13 @@
+pub mod concentrated;
 pub mod config;
+pub mod farm;
+pub mod limit_order;
+pub mod oracle;
+pub mod registry;
 
+pub use concentrated::*;
 pub use config::*;
+pub use farm::*;
+pub use limit_order::*;
+pub use oracle::*;
+pub use registry::*;
@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::AmmError;
+
+// Size of the observation ring buffer and minimum spacing between two observations
+pub const OBSERVATIONS: usize = 64;
+pub const OBSERVATION_INTERVAL: i64 = 60;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct Observation {
+    pub timestamp: i64,           // Time the observation was written
+    pub price_x_cumulative: u128, // price_x_cumulative at that time
+    pub price_y_cumulative: u128, // price_y_cumulative at that time
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Oracle {
+    pub config: Pubkey,                               // Pool this oracle belongs to
+    pub price_x_cumulative: u128,                     // Sum of (Y per X, Q64.64) * seconds
+    pub price_y_cumulative: u128,                     // Sum of (X per Y, Q64.64) * seconds
+    pub last_timestamp: i64,                          // Last time the accumulators were updated
+    pub index: u16,                                   // Slot of the most recent observation
+    pub len: u16,                                     // Number of slots written so far
+    pub observations: [Observation; OBSERVATIONS],    // Ring buffer of observations
+    pub bump: u8,                                     // Bump seed for the oracle account
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct TwapPrice {
+    pub price_x: u128, // Average price of X in Y over the window, Q64.64
+    pub price_y: u128, // Average price of Y in X over the window, Q64.64
+    pub window: u32,   // Seconds actually covered, >= the requested window
+}
+
+impl Oracle {
+    // Accrues the price implied by the reserves *before* a pool state change and
+    // records an observation at most once every OBSERVATION_INTERVAL seconds
+    pub fn update(&mut self, reserve_x: u64, reserve_y: u64, now: i64) -> Result<()> {
+        let (price_x_cumulative, price_y_cumulative) =
+            self.cumulatives_at(reserve_x, reserve_y, now)?;
+        self.price_x_cumulative = price_x_cumulative;
+        self.price_y_cumulative = price_y_cumulative;
+        self.last_timestamp = now;
+
+        let latest = self.observations[self.index as usize];
+        if self.len == 0 || now - latest.timestamp >= OBSERVATION_INTERVAL {
+            if self.len != 0 {
+                self.index = ((self.index as usize + 1) % OBSERVATIONS) as u16;
+            }
+            self.len = (self.len + 1).min(OBSERVATIONS as u16);
+            self.observations[self.index as usize] = Observation {
+                timestamp: now,
+                price_x_cumulative,
+                price_y_cumulative,
+            };
+        }
+
+        Ok(())
+    }
+
+    // Time-weighted average prices over at least the last `window` seconds
+    pub fn twap(&self, reserve_x: u64, reserve_y: u64, now: i64, window: u32) -> Result<TwapPrice> {
+        require!(window != 0, AmmError::InvalidAmount);
+        let (price_x_cumulative, price_y_cumulative) =
+            self.cumulatives_at(reserve_x, reserve_y, now)?;
+
+        // Newest observation that is at least `window` seconds old
+        let target = now - window as i64;
+        let observation = (0..self.len as usize)
+            .map(|i| self.observations[(self.index as usize + OBSERVATIONS - i) % OBSERVATIONS])
+            .find(|observation| observation.timestamp <= target)
+            .ok_or(AmmError::OracleWindowTooLong)?;
+
+        let elapsed = (now - observation.timestamp) as u128;
+        Ok(TwapPrice {
+            price_x: price_x_cumulative.wrapping_sub(observation.price_x_cumulative) / elapsed,
+            price_y: price_y_cumulative.wrapping_sub(observation.price_y_cumulative) / elapsed,
+            window: elapsed as u32,
+        })
+    }
+
+    // Accumulators extrapolated to `now` with the given reserves
+    fn cumulatives_at(&self, reserve_x: u64, reserve_y: u64, now: i64) -> Result<(u128, u128)> {
+        require!(now >= self.last_timestamp, AmmError::InvalidTimestamp);
+        let elapsed = (now - self.last_timestamp) as u128;
+
+        // No price while the pool is empty
+        if elapsed == 0 || reserve_x == 0 || reserve_y == 0 {
+            return Ok((self.price_x_cumulative, self.price_y_cumulative));
+        }
+
+        // Overflow of the accumulators is expected and harmless since readers only
+        // ever look at wrapping differences between two points in time
+        let price_x = ((reserve_y as u128) << 64) / reserve_x as u128;
+        let price_y = ((reserve_x as u128) << 64) / reserve_y as u128;
+        Ok((
+            self.price_x_cumulative
+                .wrapping_add(price_x.wrapping_mul(elapsed)),
+            self.price_y_cumulative
+                .wrapping_add(price_y.wrapping_mul(elapsed)),
+        ))
+    }
+}
@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+use crate::state::CurveType;
+
+// One entry per (mint pair, fee tier, curve), at
+// [b"pool", min(mint_x, mint_y), max(mint_x, mint_y), fee, curve_type], so routers can
+// derive the canonical pool for a pair without knowing its seed and fetch this account
+// for its config. register_pool only takes pools whose authority was renounced, so the
+// first one registered keeps the slot and its fee can no longer change
+#[account]
+#[derive(InitSpace)]
+pub struct PoolEntry {
+    pub config: Pubkey,        // Pool registered for this pair, fee tier and curve
+    pub mint_x: Pubkey,        // Token X of that pool
+    pub mint_y: Pubkey,        // Token Y of that pool
+    pub fee: u16,              // Fee tier, the pool's fee in basis points
+    pub curve_type: CurveType, // Curve the pool was created with
+    pub bump: u8,              // Bump seed for the registry entry
+}
@@ -0,0 +1,273 @@
+use amm_quote::mul_div;
+use anchor_lang::prelude::*;
+
+use crate::errors::AmmError;
+
+// Concentrated liquidity math. Prices are sqrt(Y per X) in Q64.64 and tick i sits at
+// price 1.0001^i, as in Uniswap v3. Liquidity is capped at u64::MAX so that L << 64
+// always fits in a u128
+
+pub const MIN_TICK: i32 = -443_636;
+pub const MAX_TICK: i32 = 443_636;
+pub const MAX_LIQUIDITY: u128 = u64::MAX as u128;
+
+// sqrt(1.0001)^(2^i) in Q64.64, one factor per bit of |tick|
+const SQRT_FACTORS: [u128; 19] = [
+    18_447_666_387_855_959_850,
+    18_448_588_748_116_922_571,
+    18_450_433_606_991_734_263,
+    18_454_123_878_217_468_680,
+    18_461_506_635_090_006_701,
+    18_476_281_010_653_910_144,
+    18_505_865_242_158_250_041,
+    18_565_175_891_880_433_522,
+    18_684_368_066_214_940_582,
+    18_925_053_041_275_764_671,
+    19_415_764_168_677_886_926,
+    20_435_687_552_633_177_494,
+    22_639_080_592_224_303_007,
+    27_784_196_929_998_399_742,
+    41_848_122_137_994_986_128,
+    94_936_283_578_220_370_716,
+    488_590_176_327_622_479_860,
+    12_941_056_668_319_229_769_860,
+    9_078_618_265_828_848_800_676_189,
+];
+
+const Q64: u128 = 1 << 64;
+
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128> {
+    require!((MIN_TICK..=MAX_TICK).contains(&tick), AmmError::InvalidTick);
+
+    let abs_tick = tick.unsigned_abs();
+    let mut price = Q64;
+    for (i, factor) in SQRT_FACTORS.iter().enumerate() {
+        if abs_tick & (1 << i) != 0 {
+            price = mul_div(price, *factor, Q64, false).ok_or(AmmError::Overflow)?;
+        }
+    }
+
+    match tick < 0 {
+        true => Ok(u128::MAX / price),
+        false => Ok(price),
+    }
+}
+
+// Largest tick whose sqrt price is at or below `sqrt_price`
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Result<i32> {
+    require!(
+        sqrt_price >= sqrt_price_at_tick(MIN_TICK)?,
+        AmmError::InvalidTick
+    );
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        match sqrt_price_at_tick(mid)? <= sqrt_price {
+            true => low = mid,
+            false => high = mid - 1,
+        }
+    }
+
+    Ok(low)
+}
+
+// Two amounts whose ratio Y / X is the price at `sqrt_price`, like reserves at that
+// price, for the checks that measure price moves on reserves: 2^128 / s and s, scaled
+// down together until both fit in a u64
+pub fn price_pair(sqrt_price: u128) -> (u64, u64) {
+    let (x, y) = (u128::MAX / sqrt_price.max(1), sqrt_price);
+    let shift = (128 - x.max(y).leading_zeros()).saturating_sub(64);
+    ((x >> shift) as u64, (y >> shift) as u64)
+}
+
+// Amount of X backing `liquidity` between two sqrt prices: L * (b - a) / (a * b)
+pub fn amount_x_delta(
+    price_a: u128,
+    price_b: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u64> {
+    let (lower, upper) = (price_a.min(price_b), price_a.max(price_b));
+    require!(lower > 0, AmmError::InvalidTick);
+
+    let numerator =
+        mul_div(liquidity << 64, upper - lower, upper, round_up).ok_or(AmmError::Overflow)?;
+    let amount = match round_up {
+        true => numerator.div_ceil(lower),
+        false => numerator / lower,
+    };
+
+    u64::try_from(amount).map_err(|_| AmmError::Overflow.into())
+}
+
+// Amount of Y backing `liquidity` between two sqrt prices: L * (b - a)
+pub fn amount_y_delta(
+    price_a: u128,
+    price_b: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u64> {
+    let (lower, upper) = (price_a.min(price_b), price_a.max(price_b));
+
+    let amount = mul_div(liquidity, upper - lower, Q64, round_up).ok_or(AmmError::Overflow)?;
+
+    u64::try_from(amount).map_err(|_| AmmError::Overflow.into())
+}
+
+// Price after adding `amount` of X (price goes down) or Y (price goes up) to the
+// liquidity in range, rounded so the pool never gives away more than it received
+pub fn next_sqrt_price(sqrt_price: u128, liquidity: u128, amount: u64, is_x: bool) -> Result<u128> {
+    require!(liquidity > 0, AmmError::NoLiquidityInPool);
+
+    match is_x {
+        // L / (L / P + x), rounded up
+        true => {
+            let numerator = liquidity << 64;
+            let denominator = (numerator / sqrt_price)
+                .checked_add(amount as u128)
+                .ok_or(AmmError::Overflow)?;
+            Ok(numerator.div_ceil(denominator))
+        }
+        // P + y / L, rounded down
+        false => sqrt_price
+            .checked_add(((amount as u128) << 64) / liquidity)
+            .ok_or(AmmError::Overflow.into()),
+    }
+}
+
+pub struct SwapStep {
+    pub sqrt_price: u128, // Price reached by this step
+    pub amount_in: u64,   // Input consumed, excluding the fee
+    pub amount_out: u64,  // Output produced
+    pub fee: u64,         // Swap fee taken on top of amount_in
+}
+
+// Swaps as much of `amount_remaining` (fee included) as possible without moving the
+// price past `sqrt_price_target`
+pub fn swap_step(
+    sqrt_price: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee: u16,
+    is_x: bool,
+) -> Result<SwapStep> {
+    let remaining_less_fee = (amount_remaining as u128 * (10_000 - fee as u128) / 10_000) as u64;
+
+    let amount_to_target = match is_x {
+        true => amount_x_delta(sqrt_price_target, sqrt_price, liquidity, true)?,
+        false => amount_y_delta(sqrt_price, sqrt_price_target, liquidity, true)?,
+    };
+
+    let reaches_target = remaining_less_fee >= amount_to_target;
+    // Rounding can't be allowed to move the price backwards or past the target
+    let next_price = match (reaches_target, is_x) {
+        (true, _) => sqrt_price_target,
+        (false, true) => next_sqrt_price(sqrt_price, liquidity, remaining_less_fee, true)?
+            .clamp(sqrt_price_target, sqrt_price),
+        (false, false) => next_sqrt_price(sqrt_price, liquidity, remaining_less_fee, false)?
+            .clamp(sqrt_price, sqrt_price_target),
+    };
+
+    let (amount_in, amount_out) = match is_x {
+        true => (
+            amount_x_delta(next_price, sqrt_price, liquidity, true)?,
+            amount_y_delta(next_price, sqrt_price, liquidity, false)?,
+        ),
+        false => (
+            amount_y_delta(sqrt_price, next_price, liquidity, true)?,
+            amount_x_delta(sqrt_price, next_price, liquidity, false)?,
+        ),
+    };
+
+    // A partial step keeps the whole remainder, so the fee is whatever isn't swapped
+    let max_fee = amount_remaining
+        .checked_sub(amount_in)
+        .ok_or(AmmError::Underflow)?;
+    let fee = match reaches_target {
+        true => {
+            ((amount_in as u128 * fee as u128).div_ceil(10_000 - fee as u128) as u64).min(max_fee)
+        }
+        false => max_fee,
+    };
+
+    Ok(SwapStep {
+        sqrt_price: next_price,
+        amount_in,
+        amount_out,
+        fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqrt_price_follows_the_tick() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), Q64);
+        // 1.0001^(100/2) and 1.0001^(-100/2)
+        let up = sqrt_price_at_tick(100).unwrap() as f64 / Q64 as f64;
+        let down = sqrt_price_at_tick(-100).unwrap() as f64 / Q64 as f64;
+        assert!((up - 1.0001f64.powf(50.0)).abs() < 1e-12);
+        assert!((down - 1.0001f64.powf(-50.0)).abs() < 1e-12);
+
+        let mut previous = sqrt_price_at_tick(MIN_TICK).unwrap();
+        for tick in (MIN_TICK + 1..=MAX_TICK).step_by(997) {
+            let price = sqrt_price_at_tick(tick).unwrap();
+            assert!(price > previous, "{tick}");
+            previous = price;
+        }
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    }
+
+    #[test]
+    fn price_pair_keeps_the_price() {
+        assert_eq!(price_pair(Q64), (u64::MAX >> 1, 1 << 63));
+        for tick in [-200_000, -100, 0, 100, 200_000] {
+            let (x, y) = price_pair(sqrt_price_at_tick(tick).unwrap());
+            let price = y as f64 / x as f64;
+            assert!((price / 1.0001f64.powi(tick) - 1.0).abs() < 1e-9, "{tick}");
+        }
+        for tick in [MIN_TICK, MAX_TICK] {
+            let (x, y) = price_pair(sqrt_price_at_tick(tick).unwrap());
+            assert!(x > 0 && y > 0, "{tick}");
+        }
+    }
+
+    #[test]
+    fn tick_at_sqrt_price_inverts_sqrt_price_at_tick() {
+        for tick in [MIN_TICK, -200_000, -1, 0, 1, 60, 12_345, MAX_TICK] {
+            let price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(price).unwrap(), tick);
+            if tick < MAX_TICK {
+                assert_eq!(tick_at_sqrt_price(price + 1).unwrap(), tick);
+            }
+            if tick > MIN_TICK {
+                assert_eq!(tick_at_sqrt_price(price - 1).unwrap(), tick - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn swap_step_rounds_in_favour_of_the_pool() {
+        let liquidity = 1_000_000_000_000u128;
+        let (price, target) = (Q64, sqrt_price_at_tick(-1_000).unwrap());
+
+        // Partial step: everything is consumed
+        let step = swap_step(price, target, liquidity, 1_000_000, 30, true).unwrap();
+        assert!(step.sqrt_price > target && step.sqrt_price < price);
+        assert_eq!(step.amount_in + step.fee, 1_000_000);
+        assert!(step.amount_out < 1_000_000 - step.fee);
+
+        // Full step: stops at the target and leaves the rest
+        let step = swap_step(price, target, liquidity, u64::MAX / 2, 30, true).unwrap();
+        assert_eq!(step.sqrt_price, target);
+        assert!(step.amount_in + step.fee < u64::MAX / 2);
+
+        // Going back up with what came out never yields more than went in
+        let back = swap_step(step.sqrt_price, price, liquidity, step.amount_out, 0, false).unwrap();
+        assert!(back.amount_out <= step.amount_in);
+    }
+}
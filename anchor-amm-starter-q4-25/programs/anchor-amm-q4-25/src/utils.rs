@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022_extensions::transfer_fee::{
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+    },
+    token_interface::{
+        get_mint_extension_data,
+        spl_token_2022::extension::{transfer_fee::TransferFeeConfig, transfer_hook::TransferHook},
+        Mint, TokenInterface,
+    },
+};
+
+use crate::errors::AmmError;
+
+// Returns the TransferFeeConfig of a Token-2022 mint, or None for legacy mints
+// and Token-2022 mints without the transfer fee extension
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Option<TransferFeeConfig> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != anchor_spl::token_2022::ID {
+        return None;
+    }
+    get_mint_extension_data::<TransferFeeConfig>(&mint_info).ok()
+}
+
+// Whether a Token-2022 mint has the transfer hook extension, with a hook program set or
+// not: the hook authority can set one at any time
+pub fn has_transfer_hook(mint: &InterfaceAccount<Mint>) -> bool {
+    let mint_info = mint.to_account_info();
+    *mint_info.owner == anchor_spl::token_2022::ID
+        && get_mint_extension_data::<TransferHook>(&mint_info).is_ok()
+}
+
+// Fee withheld by the token program when `amount` is transferred
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint) {
+        Some(config) => Ok(config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(AmmError::Overflow)?),
+        None => Ok(0),
+    }
+}
+
+// Fee to add on top of `amount` so that the destination receives exactly `amount`
+pub fn transfer_inverse_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint) {
+        Some(config) => Ok(config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(AmmError::Overflow)?),
+        None => Ok(0),
+    }
+}
+
+// Moves the transfer fees withheld in `account` to its mint. Token-2022 won't close an
+// account that still holds some, so vaults of transfer fee mints go through this first
+pub fn harvest_withheld_fees<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    account: AccountInfo<'info>,
+) -> Result<()> {
+    if transfer_fee_config(mint).is_none() {
+        return Ok(());
+    }
+
+    let cpi_program = token_program.to_account_info();
+
+    let cpi_accounts = HarvestWithheldTokensToMint {
+        token_program_id: token_program.to_account_info(),
+        mint: mint.to_account_info(),
+    };
+
+    let ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    harvest_withheld_tokens_to_mint(ctx, vec![account])
+}
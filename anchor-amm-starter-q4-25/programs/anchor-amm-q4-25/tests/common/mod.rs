@@ -0,0 +1,992 @@
+// In-process harness for the AMM program on LiteSVM. The tests load the program built by
+// `anchor build`, so run that first; `cargo test` alone doesn't rebuild it. They also need
+// the token metadata program, see METADATA_PROGRAM_PATH
+#![allow(dead_code)]
+
+use anchor_amm_q4_25::{
+    accounts,
+    errors::AmmError,
+    instruction,
+    state::{CurveType, FeeSide},
+    ID,
+};
+use anchor_lang::{
+    prelude::{Clock, Pubkey},
+    solana_program::{
+        instruction::{error::InstructionError, AccountMeta, Instruction},
+        program_option::COption,
+        program_pack::Pack,
+        sysvar,
+    },
+    system_program, AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, get_associated_token_address, get_associated_token_address_with_program_id,
+    },
+    metadata::{
+        self,
+        mpl_token_metadata::{
+            self, types::Key, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
+        },
+    },
+    token::{
+        self,
+        spl_token::state::{Account as TokenAccount, AccountState, Mint},
+    },
+    token_2022::{
+        self,
+        spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                transfer_hook::TransferHook,
+                BaseState, BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensions, StateWithExtensionsMut,
+            },
+            state::{
+                Account as TokenAccount2022, AccountState as AccountState2022, Mint as Mint2022,
+            },
+        },
+    },
+};
+use litesvm::{types::TransactionResult, LiteSVM};
+use solana_account::Account;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+pub const PROGRAM_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../target/deploy/anchor_amm_q4_25.so"
+);
+
+// Dumped from mainnet rather than checked in; fetch it once with `anchor run fetch-fixtures`
+pub const METADATA_PROGRAM_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/mpl_token_metadata.so"
+);
+
+// Clock the tests start at, so timestamps look like real ones
+pub const START_TIME: i64 = 1_700_000_000;
+
+// Reserves the seeded pool starts with, priced at 2 Y per X
+pub const SEED_X: u64 = 1_000_000_000;
+pub const SEED_Y: u64 = 2_000_000_000;
+
+pub struct Env {
+    pub svm: LiteSVM,
+    pub authority: Keypair, // Pays for and controls the pools the tests create
+}
+
+impl Env {
+    pub fn new() -> Env {
+        let mut svm = LiteSVM::new();
+        svm.add_program_from_file(ID, PROGRAM_PATH)
+            .expect("program not built, run `anchor build` first");
+        svm.add_program_from_file(metadata::ID, METADATA_PROGRAM_PATH)
+            .expect("token metadata program missing, run `anchor run fetch-fixtures` first");
+
+        let mut clock = svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = START_TIME;
+        svm.set_sysvar(&clock);
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 100_000_000_000).unwrap();
+
+        Env { svm, authority }
+    }
+
+    // A funded keypair for a user other than the authority
+    pub fn user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        self.svm.airdrop(&user.pubkey(), 10_000_000_000).unwrap();
+        user
+    }
+
+    pub fn now(&self) -> i64 {
+        self.svm.get_sysvar::<Clock>().unix_timestamp
+    }
+
+    // Deadline far enough out for any instruction sent now
+    pub fn expiration(&self) -> i64 {
+        self.now() + 60
+    }
+
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp += seconds;
+        clock.slot += 1;
+        self.svm.set_sysvar(&clock);
+    }
+
+    // Sends `instructions` in one transaction paid by the first signer. The blockhash is
+    // refreshed every time so identical transactions aren't rejected as duplicates
+    pub fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> TransactionResult {
+        self.svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            self.svm.latest_blockhash(),
+        );
+        self.svm.send_transaction(tx)
+    }
+
+    // A classic SPL mint whose supply the tests never need to keep consistent
+    pub fn create_mint(&mut self) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        let state = Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 0,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        self.set_packed(mint, state, token::ID);
+        mint
+    }
+
+    // A Token-2022 mint withholding `fee_bps` of every transfer, without a cap
+    pub fn create_transfer_fee_mint(&mut self, fee_bps: u16) -> Pubkey {
+        self.create_mint_2022(ExtensionType::TransferFeeConfig, |state| {
+            let fee = TransferFee {
+                epoch: 0.into(),
+                maximum_fee: u64::MAX.into(),
+                transfer_fee_basis_points: fee_bps.into(),
+            };
+            let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+            config.older_transfer_fee = fee;
+            config.newer_transfer_fee = fee;
+        })
+    }
+
+    // A Token-2022 mint with a transfer hook, which pools don't accept
+    pub fn create_transfer_hook_mint(&mut self) -> Pubkey {
+        self.create_mint_2022(ExtensionType::TransferHook, |state| {
+            state.init_extension::<TransferHook>(true).unwrap();
+        })
+    }
+
+    // A Token-2022 mint with the one extension `init` sets up
+    fn create_mint_2022(
+        &mut self,
+        extension: ExtensionType,
+        init: impl FnOnce(&mut StateWithExtensionsMut<Mint2022>),
+    ) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        let state = Mint2022 {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            decimals: 6,
+            is_initialized: true,
+            ..Mint2022::default()
+        };
+        self.set_packed_2022(mint, state, &[extension], init);
+        mint
+    }
+
+    // Creates `owner`'s associated token account for `mint` if needed and adds `amount`.
+    // Under Token-2022 it gets the account extensions the mint's extensions require
+    pub fn fund(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let mint_account = self.svm.get_account(mint).unwrap();
+        let address =
+            get_associated_token_address_with_program_id(owner, mint, &mint_account.owner);
+        let balance = self.balance(&address);
+        match mint_account.owner == token_2022::ID {
+            true => {
+                let mint_extensions = StateWithExtensions::<Mint2022>::unpack(&mint_account.data)
+                    .unwrap()
+                    .get_extension_types()
+                    .unwrap();
+                let extensions =
+                    ExtensionType::get_required_init_account_extensions(&mint_extensions);
+                let state = TokenAccount2022 {
+                    mint: *mint,
+                    owner: *owner,
+                    amount: balance + amount,
+                    state: AccountState2022::Initialized,
+                    ..TokenAccount2022::default()
+                };
+                self.set_packed_2022(address, state, &extensions, |state| {
+                    for extension in &extensions {
+                        state.init_account_extension_from_type(*extension).unwrap();
+                    }
+                });
+            }
+            false => {
+                let state = TokenAccount {
+                    mint: *mint,
+                    owner: *owner,
+                    amount: balance + amount,
+                    state: AccountState::Initialized,
+                    ..TokenAccount::default()
+                };
+                self.set_packed(address, state, token::ID);
+            }
+        }
+        address
+    }
+
+    // Token balance of either token program, or 0 if the account doesn't exist
+    pub fn balance(&self, address: &Pubkey) -> u64 {
+        self.svm.get_account(address).map_or(0, |account| {
+            StateWithExtensions::<TokenAccount2022>::unpack(&account.data)
+                .unwrap()
+                .base
+                .amount
+        })
+    }
+
+    // Lamports held by an account, 0 once it is closed
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub fn supply(&self, mint: &Pubkey) -> u64 {
+        let account = self.svm.get_account(mint).unwrap();
+        Mint::unpack(&account.data).unwrap().supply
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.svm.get_account(address).unwrap();
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    // Gives `mint` token metadata with `symbol`, laid out and padded like the metadata
+    // program does
+    pub fn set_metadata(&mut self, mint: &Pubkey, symbol: &str) {
+        let mut data = vec![Key::MetadataV1 as u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref()); // update_authority
+        data.extend_from_slice(mint.as_ref());
+        // name, symbol and uri
+        for (value, len) in [
+            ("", MAX_NAME_LENGTH),
+            (symbol, MAX_SYMBOL_LENGTH),
+            ("", MAX_URI_LENGTH),
+        ] {
+            let mut padded = value.as_bytes().to_vec();
+            padded.resize(len, 0);
+            data.extend_from_slice(&(len as u32).to_le_bytes());
+            data.extend_from_slice(&padded);
+        }
+        data.extend_from_slice(&0u16.to_le_bytes()); // seller_fee_basis_points
+                                                     // No creators, primary_sale_happened, is_mutable, then none of the optional fields
+        data.extend_from_slice(&[0, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: metadata::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let address = mpl_token_metadata::accounts::Metadata::find_pda(mint).0;
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    fn set_packed<T: Pack>(&mut self, address: Pubkey, state: T, owner: Pubkey) {
+        let mut data = vec![0; T::LEN];
+        state.pack_into_slice(&mut data);
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(T::LEN),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    // Writes a Token-2022 account or mint with room for `extensions`, which `init` sets up
+    fn set_packed_2022<S: BaseState + Pack>(
+        &mut self,
+        address: Pubkey,
+        base: S,
+        extensions: &[ExtensionType],
+        init: impl FnOnce(&mut StateWithExtensionsMut<S>),
+    ) {
+        let len = ExtensionType::try_calculate_account_len::<S>(extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<S>::unpack_uninitialized(&mut data).unwrap();
+        init(&mut state);
+        state.base = base;
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(len),
+            data,
+            owner: token_2022::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    // Initializes a pool over two new mints
+    pub fn create_pool(&mut self, args: instruction::Initialize) -> Pool {
+        let pool = Pool::new(
+            self.create_mint(),
+            self.create_mint(),
+            args.seed,
+            args.fee,
+            args.curve_type,
+        );
+        let authority = self.authority.insecure_clone();
+        self.send(&[pool.initialize(&authority.pubkey(), args)], &[&authority])
+            .unwrap();
+        pool
+    }
+
+    // A constant product pool with the default arguments and the SEED_X/SEED_Y liquidity
+    // deposited by the authority
+    pub fn seeded_pool(&mut self) -> Pool {
+        let pool = self.create_pool(init_args(1, Some(self.authority.pubkey())));
+        self.seed_pool(&pool);
+        pool
+    }
+
+    // Deposits the SEED_X/SEED_Y liquidity into an empty pool as the authority
+    pub fn seed_pool(&mut self, pool: &Pool) {
+        let authority = self.authority.insecure_clone();
+        self.fund(&pool.mint_x, &authority.pubkey(), SEED_X);
+        self.fund(&pool.mint_y, &authority.pubkey(), SEED_Y);
+        let deposit = pool.deposit(&authority.pubkey(), 1, SEED_X, SEED_Y, self.expiration());
+        self.send(&[deposit], &[&authority]).unwrap();
+    }
+}
+
+// Constant product pool with a fixed 0.3% fee, no protocol fee and no fee timelock
+pub fn init_args(seed: u64, authority: Option<Pubkey>) -> instruction::Initialize {
+    instruction::Initialize {
+        seed,
+        fee: 30,
+        min_fee: 0,
+        max_fee: 0,
+        protocol_fee: 0,
+        fee_timelock: 0,
+        curve_type: CurveType::ConstantProduct,
+        amp: 0,
+        fee_side: FeeSide::Input,
+        referral_fee: 0,
+        authority,
+    }
+}
+
+pub fn assert_error(result: TransactionResult, error: AmmError) {
+    let failed = result.expect_err("transaction should have failed");
+    match failed.err {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(
+                code,
+                u32::from(error),
+                "expected {error:?}, logs: {:#?}",
+                failed.meta.logs
+            )
+        }
+        err => panic!(
+            "expected {error:?}, got {err:?}, logs: {:#?}",
+            failed.meta.logs
+        ),
+    }
+}
+
+// Addresses of a pool and builders for the instructions the tests send to it
+pub struct Pool {
+    pub seed: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub config: Pubkey,
+    pub oracle: Pubkey,
+    pub mint_lp: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub locked_lp: Pubkey,
+    pub pool_entry: Pubkey,
+    pub lp_metadata: Pubkey,
+    pub concentrated_pool: Pubkey,
+    pub token_program_x: Pubkey,
+    pub token_program_y: Pubkey,
+}
+
+impl Pool {
+    pub fn new(mint_x: Pubkey, mint_y: Pubkey, seed: u64, fee: u16, curve_type: CurveType) -> Pool {
+        let config = Pubkey::find_program_address(&[b"config", &seed.to_le_bytes()], &ID).0;
+        let mint_lp = Pubkey::find_program_address(&[b"lp", config.as_ref()], &ID).0;
+        let pool_entry = Pubkey::find_program_address(
+            &[
+                b"pool",
+                mint_x.min(mint_y).as_ref(),
+                mint_x.max(mint_y).as_ref(),
+                &fee.to_le_bytes(),
+                &[curve_type as u8],
+            ],
+            &ID,
+        )
+        .0;
+
+        Pool {
+            seed,
+            mint_x,
+            mint_y,
+            config,
+            oracle: Pubkey::find_program_address(&[b"oracle", config.as_ref()], &ID).0,
+            mint_lp,
+            vault_x: get_associated_token_address(&config, &mint_x),
+            vault_y: get_associated_token_address(&config, &mint_y),
+            locked_lp: get_associated_token_address(&system_program::ID, &mint_lp),
+            pool_entry,
+            lp_metadata: mpl_token_metadata::accounts::Metadata::find_pda(&mint_lp).0,
+            concentrated_pool: Pubkey::find_program_address(
+                &[b"concentrated", config.as_ref()],
+                &ID,
+            )
+            .0,
+            token_program_x: token::ID,
+            token_program_y: token::ID,
+        }
+    }
+
+    // The same pool with X and Y on the given token programs instead of the classic one
+    pub fn with_token_programs(self, token_program_x: Pubkey, token_program_y: Pubkey) -> Pool {
+        Pool {
+            vault_x: get_associated_token_address_with_program_id(
+                &self.config,
+                &self.mint_x,
+                &token_program_x,
+            ),
+            vault_y: get_associated_token_address_with_program_id(
+                &self.config,
+                &self.mint_y,
+                &token_program_y,
+            ),
+            token_program_x,
+            token_program_y,
+            ..self
+        }
+    }
+
+    // Mint and token program of X (is_x) or Y
+    fn token(&self, is_x: bool) -> (Pubkey, Pubkey) {
+        match is_x {
+            true => (self.mint_x, self.token_program_x),
+            false => (self.mint_y, self.token_program_y),
+        }
+    }
+
+    // `owner`'s associated token account for X (is_x) or Y
+    fn associated(&self, owner: &Pubkey, is_x: bool) -> Pubkey {
+        let (mint, token_program) = self.token(is_x);
+        get_associated_token_address_with_program_id(owner, &mint, &token_program)
+    }
+
+    pub fn user_lp(&self, user: &Pubkey) -> Pubkey {
+        get_associated_token_address(user, &self.mint_lp)
+    }
+
+    pub fn user_x(&self, user: &Pubkey) -> Pubkey {
+        self.associated(user, true)
+    }
+
+    pub fn user_y(&self, user: &Pubkey) -> Pubkey {
+        self.associated(user, false)
+    }
+
+    pub fn initialize(&self, initializer: &Pubkey, args: instruction::Initialize) -> Instruction {
+        let accounts = accounts::Initialize {
+            initializer: *initializer,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            mint_lp: self.mint_lp,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            locked_lp: self.locked_lp,
+            config: self.config,
+            oracle: self.oracle,
+            metadata_x: mpl_token_metadata::accounts::Metadata::find_pda(&self.mint_x).0,
+            metadata_y: mpl_token_metadata::accounts::Metadata::find_pda(&self.mint_y).0,
+            lp_metadata: self.lp_metadata,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            token_metadata_program: metadata::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        };
+        instruction_for(accounts, args)
+    }
+
+    fn deposit_accounts(&self, user: &Pubkey) -> accounts::Deposit {
+        accounts::Deposit {
+            user: *user,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            mint_lp: self.mint_lp,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            user_x: self.user_x(user),
+            user_y: self.user_y(user),
+            user_lp: self.user_lp(user),
+            locked_lp: self.locked_lp,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            token_program: token::ID,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        }
+    }
+
+    pub fn deposit(
+        &self,
+        user: &Pubkey,
+        lp_amount: u64,
+        max_x: u64,
+        max_y: u64,
+        expiration: i64,
+    ) -> Instruction {
+        instruction_for(
+            self.deposit_accounts(user),
+            instruction::Deposit {
+                lp_amount,
+                max_x,
+                max_y,
+                expiration,
+            },
+        )
+    }
+
+    pub fn withdraw(
+        &self,
+        user: &Pubkey,
+        amount: u64,
+        min_x: u64,
+        min_y: u64,
+        expiration: i64,
+    ) -> Instruction {
+        let accounts = accounts::Withdraw {
+            user: *user,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            mint_lp: self.mint_lp,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            user_x: self.user_x(user),
+            user_y: self.user_y(user),
+            user_lp: self.user_lp(user),
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            token_program: token::ID,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        };
+        instruction_for(
+            accounts,
+            instruction::Withdraw {
+                amount,
+                min_x,
+                min_y,
+                expiration,
+            },
+        )
+    }
+
+    pub fn swap(
+        &self,
+        user: &Pubkey,
+        is_x: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Instruction {
+        self.referred_swap(user, None, is_x, amount_in, min_amount_out, expiration)
+    }
+
+    // Swap paying part of the fee to `referrer`, a token account in the fee token
+    pub fn referred_swap(
+        &self,
+        user: &Pubkey,
+        referrer: Option<Pubkey>,
+        is_x: bool,
+        amount_in: u64,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Instruction {
+        let accounts = accounts::Swap {
+            user: *user,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            user_x: self.user_x(user),
+            user_y: self.user_y(user),
+            referrer,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(
+            accounts,
+            instruction::Swap {
+                is_x,
+                amount_in,
+                min_amount_out,
+                expiration,
+            },
+        )
+    }
+
+    fn flash_loan_accounts(&self, borrower: &Pubkey) -> accounts::FlashLoan {
+        accounts::FlashLoan {
+            borrower: *borrower,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            borrower_x: self.user_x(borrower),
+            borrower_y: self.user_y(borrower),
+            instructions: sysvar::instructions::ID,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            associated_token_program: associated_token::ID,
+        }
+    }
+
+    pub fn flash_loan(&self, borrower: &Pubkey, is_x: bool, amount: u64) -> Instruction {
+        instruction_for(
+            self.flash_loan_accounts(borrower),
+            instruction::FlashLoan { is_x, amount },
+        )
+    }
+
+    pub fn repay_flash_loan(&self, borrower: &Pubkey) -> Instruction {
+        instruction_for(
+            self.flash_loan_accounts(borrower),
+            instruction::RepayFlashLoan {},
+        )
+    }
+
+    pub fn get_twap(&self, window: u32) -> Instruction {
+        let accounts = accounts::GetTwap {
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            associated_token_program: associated_token::ID,
+        };
+        instruction_for(accounts, instruction::GetTwap { window })
+    }
+
+    // Any instruction taking the Update accounts, i.e. the authority-only settings
+    pub fn update<T: InstructionData>(&self, authority: &Pubkey, args: T) -> Instruction {
+        let accounts = accounts::Update {
+            authority: *authority,
+            config: self.config,
+        };
+        instruction_for(accounts, args)
+    }
+
+    pub fn accept_authority(&self, new_authority: &Pubkey) -> Instruction {
+        let accounts = accounts::AcceptAuthority {
+            new_authority: *new_authority,
+            config: self.config,
+        };
+        instruction_for(accounts, instruction::AcceptAuthority {})
+    }
+
+    pub fn register_pool(&self, payer: &Pubkey) -> Instruction {
+        let accounts = accounts::RegisterPool {
+            payer: *payer,
+            config: self.config,
+            pool_entry: self.pool_entry,
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, instruction::RegisterPool {})
+    }
+
+    pub fn migrate_config(&self, payer: &Pubkey) -> Instruction {
+        let accounts = accounts::MigrateConfig {
+            payer: *payer,
+            config: self.config,
+            oracle: self.oracle,
+            mint_lp: self.mint_lp,
+            locked_lp: self.locked_lp,
+            token_program: token::ID,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        };
+        instruction_for(accounts, instruction::MigrateConfig {})
+    }
+
+    // `tick` has to match the pool's price, unless the pool is empty
+    pub fn initialize_concentrated(
+        &self,
+        authority: &Pubkey,
+        tick_spacing: u16,
+        tick: i32,
+    ) -> Instruction {
+        let accounts = accounts::InitializeConcentrated {
+            authority: *authority,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            concentrated_pool: self.concentrated_pool,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(
+            accounts,
+            instruction::InitializeConcentrated { tick_spacing, tick },
+        )
+    }
+
+    // The concentrated pool's tick array starting at `start_tick`
+    pub fn tick_array(&self, start_tick: i32) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"tick_array",
+                self.concentrated_pool.as_ref(),
+                &start_tick.to_le_bytes(),
+            ],
+            &ID,
+        )
+        .0
+    }
+
+    pub fn initialize_tick_array(&self, payer: &Pubkey, start_tick: i32) -> Instruction {
+        let accounts = accounts::InitializeTickArray {
+            payer: *payer,
+            concentrated_pool: self.concentrated_pool,
+            tick_array: self.tick_array(start_tick),
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, instruction::InitializeTickArray { start_tick })
+    }
+
+    pub fn close_pool(&self, authority: &Pubkey) -> Instruction {
+        let accounts = accounts::ClosePool {
+            authority: *authority,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            concentrated_pool: self.concentrated_pool,
+            mint_lp: self.mint_lp,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            locked_lp: self.locked_lp,
+            treasury_x: self.user_x(authority),
+            treasury_y: self.user_y(authority),
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, instruction::ClosePool {})
+    }
+
+    // The farm paying out `reward_mint` to the pool's LP stakers
+    pub fn farm(&self, reward_mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"farm", self.config.as_ref(), reward_mint.as_ref()], &ID).0
+    }
+
+    // Funded from the authority's `reward_mint` ATA
+    pub fn create_farm(
+        &self,
+        authority: &Pubkey,
+        reward_mint: &Pubkey,
+        args: instruction::CreateFarm,
+    ) -> Instruction {
+        let farm = self.farm(reward_mint);
+        let accounts = accounts::CreateFarm {
+            authority: *authority,
+            config: self.config,
+            mint_lp: self.mint_lp,
+            reward_mint: *reward_mint,
+            farm,
+            lp_vault: get_associated_token_address(&farm, &self.mint_lp),
+            reward_vault: get_associated_token_address(&farm, reward_mint),
+            authority_reward: get_associated_token_address(authority, reward_mint),
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, args)
+    }
+
+    // stake_lp, unstake_lp or harvest for `owner` in the `reward_mint` farm
+    pub fn modify_stake<T: InstructionData>(
+        &self,
+        owner: &Pubkey,
+        reward_mint: &Pubkey,
+        args: T,
+    ) -> Instruction {
+        let farm = self.farm(reward_mint);
+        let accounts = accounts::ModifyStake {
+            owner: *owner,
+            config: self.config,
+            mint_lp: self.mint_lp,
+            reward_mint: *reward_mint,
+            farm,
+            stake: Pubkey::find_program_address(&[b"stake", farm.as_ref(), owner.as_ref()], &ID).0,
+            lp_vault: get_associated_token_address(&farm, &self.mint_lp),
+            reward_vault: get_associated_token_address(&farm, reward_mint),
+            user_lp: self.user_lp(owner),
+            user_reward: get_associated_token_address(owner, reward_mint),
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, args)
+    }
+
+    // Refunds the unemitted rewards to the authority's `reward_mint` ATA
+    pub fn close_farm(&self, authority: &Pubkey, reward_mint: &Pubkey) -> Instruction {
+        let farm = self.farm(reward_mint);
+        let accounts = accounts::CloseFarm {
+            authority: *authority,
+            config: self.config,
+            mint_lp: self.mint_lp,
+            reward_mint: *reward_mint,
+            farm,
+            lp_vault: get_associated_token_address(&farm, &self.mint_lp),
+            reward_vault: get_associated_token_address(&farm, reward_mint),
+            authority_reward: get_associated_token_address(authority, reward_mint),
+            token_program: token::ID,
+        };
+        instruction_for(accounts, instruction::CloseFarm {})
+    }
+
+    // The order `owner` placed with `id`
+    pub fn order(&self, owner: &Pubkey, id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"order",
+                self.config.as_ref(),
+                owner.as_ref(),
+                &id.to_le_bytes(),
+            ],
+            &ID,
+        )
+        .0
+    }
+
+    pub fn place_limit_order(
+        &self,
+        owner: &Pubkey,
+        is_x: bool,
+        args: instruction::PlaceLimitOrder,
+    ) -> Instruction {
+        let ((mint_in, token_program_in), (mint_out, token_program_out)) =
+            (self.token(is_x), self.token(!is_x));
+        let order = self.order(owner, args.id);
+        let accounts = accounts::PlaceLimitOrder {
+            owner: *owner,
+            mint_in,
+            mint_out,
+            config: self.config,
+            order,
+            order_vault: self.associated(&order, is_x),
+            owner_in: self.associated(owner, is_x),
+            owner_out: self.associated(owner, !is_x),
+            token_program_in,
+            token_program_out,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        instruction_for(accounts, args)
+    }
+
+    // Fills the orders given as (owner, id, is_x)
+    pub fn fill_limit_orders(
+        &self,
+        keeper: &Pubkey,
+        orders: &[(Pubkey, u64, bool)],
+    ) -> Instruction {
+        let accounts = accounts::FillLimitOrders {
+            keeper: *keeper,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            config: self.config,
+            oracle: self.oracle,
+            vault_x: self.vault_x,
+            vault_y: self.vault_y,
+            token_program_x: self.token_program_x,
+            token_program_y: self.token_program_y,
+            associated_token_program: associated_token::ID,
+        };
+        let mut ix = instruction_for(accounts, instruction::FillLimitOrders {});
+        for (owner, id, is_x) in orders {
+            let order = self.order(owner, *id);
+            ix.accounts.extend([
+                AccountMeta::new(order, false),
+                AccountMeta::new(self.associated(&order, *is_x), false),
+                AccountMeta::new(*owner, false),
+                AccountMeta::new(self.associated(owner, !is_x), false),
+            ]);
+        }
+        ix
+    }
+
+    pub fn cancel_limit_order(&self, owner: &Pubkey, id: u64, is_x: bool) -> Instruction {
+        let (mint_in, token_program) = self.token(is_x);
+        let order = self.order(owner, id);
+        let accounts = accounts::CancelLimitOrder {
+            owner: *owner,
+            mint_in,
+            order,
+            order_vault: self.associated(&order, is_x),
+            owner_in: self.associated(owner, is_x),
+            token_program,
+            associated_token_program: associated_token::ID,
+        };
+        instruction_for(accounts, instruction::CancelLimitOrder {})
+    }
+
+    pub fn update_lp_uri(&self, authority: &Pubkey, uri: &str) -> Instruction {
+        let accounts = accounts::UpdateLpMetadata {
+            authority: *authority,
+            config: self.config,
+            mint_lp: self.mint_lp,
+            lp_metadata: self.lp_metadata,
+            token_metadata_program: metadata::ID,
+        };
+        instruction_for(
+            accounts,
+            instruction::UpdateLpUri {
+                uri: uri.to_string(),
+            },
+        )
+    }
+}
+
+pub fn instruction_for(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
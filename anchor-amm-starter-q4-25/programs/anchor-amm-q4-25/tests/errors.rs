@@ -0,0 +1,895 @@
+// LiteSVM returns its large FailedTransactionMetadata unboxed
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use anchor_amm_q4_25::{
+    accounts,
+    errors::AmmError,
+    instruction,
+    state::{ConcentratedPool, Config, ConfigV0, CurveType, Oracle, TickArray, PAUSE_ALL},
+    ID,
+};
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::instruction::{AccountMeta, Instruction},
+    system_program, AnchorSerialize, Discriminator,
+};
+use anchor_spl::{associated_token, token, token_2022};
+use common::{assert_error, init_args, instruction_for, Env, Pool, SEED_X};
+use solana_account::Account;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+// Checklist tying every AmmError to the test below that triggers it. The match is
+// exhaustive, so a new variant doesn't compile until it gets a test or the reason it
+// can't be reached from a transaction
+#[allow(dead_code)]
+fn covered_by(error: AmmError) -> Option<fn()> {
+    match error {
+        // Never returned
+        AmmError::DefaultError => None,
+        AmmError::OfferExpired => Some(offer_expired),
+        AmmError::PoolLocked => Some(pool_locked),
+        AmmError::SlippageExceeded => Some(slippage_exceeded),
+        AmmError::Overflow => Some(overflow),
+        // Guards on the reserves and vault bookkeeping, which the program keeps consistent
+        AmmError::Underflow => None,
+        AmmError::InvalidToken => Some(invalid_token),
+        AmmError::LiquidityLessThanMinimum => Some(liquidity_less_than_minimum),
+        AmmError::NoLiquidityInPool => Some(no_liquidity_in_pool),
+        // Re-deriving a PDA from its stored canonical bump can't fail
+        AmmError::BumpError => None,
+        AmmError::CurveError => Some(curve_error),
+        AmmError::InvalidFee => Some(invalid_fee),
+        AmmError::InvalidAuthority => Some(invalid_authority),
+        AmmError::NoAuthoritySet => Some(no_authority_set),
+        AmmError::InvalidAmount => Some(invalid_amount),
+        // Only mapped from CurveError, the curves are always created with a valid precision
+        AmmError::InvalidPrecision => None,
+        AmmError::InsufficientBalance => Some(insufficient_balance),
+        // Only mapped from CurveError, empty reserves are rejected before any curve is built
+        AmmError::ZeroBalance => None,
+        AmmError::InvalidRoute => Some(invalid_route),
+        AmmError::OracleWindowTooLong => Some(oracle_window_too_long),
+        AmmError::InvalidTimestamp => Some(invalid_timestamp),
+        AmmError::NoPendingFee => Some(no_pending_fee),
+        AmmError::TimelockNotExpired => Some(timelock_not_expired),
+        AmmError::InvalidCurve => Some(invalid_curve),
+        AmmError::InvalidAmp => Some(invalid_amp),
+        AmmError::InvalidRamp => Some(invalid_ramp),
+        AmmError::InvalidTick => Some(invalid_tick),
+        AmmError::InvalidTickArray => Some(invalid_tick_array),
+        AmmError::FlashLoanActive => Some(flash_loan_active),
+        AmmError::NoFlashLoan => Some(no_flash_loan),
+        AmmError::FlashLoanNotRepaid => Some(flash_loan_not_repaid),
+        // repay_flash_loan transfers the full amount due itself, so the vault only comes
+        // up short if the token program delivers less than it was asked to
+        AmmError::FlashLoanUnderpaid => None,
+        AmmError::TradeTooLarge => Some(trade_too_large),
+        AmmError::CircuitBreakerTripped => Some(circuit_breaker_tripped),
+        AmmError::InvalidTradeLimits => Some(invalid_trade_limits),
+        AmmError::InvalidPauseFlags => Some(invalid_pause_flags),
+        AmmError::InvalidConfigVersion => Some(invalid_config_version),
+        AmmError::InvalidUri => Some(invalid_uri),
+        AmmError::PoolNotEmpty => Some(pool_not_empty),
+        AmmError::InvalidOrder => Some(invalid_order),
+        AmmError::OrderNotFillable => Some(order_not_fillable),
+        AmmError::InvalidReferrer => Some(invalid_referrer),
+        AmmError::PositionNotEmpty => Some(position_not_empty),
+        AmmError::AuthorityNotRenounced => Some(authority_not_renounced),
+        AmmError::FarmNotEmpty => Some(farm_not_empty),
+        AmmError::TransferHookMint => Some(transfer_hook_mint),
+    }
+}
+
+// A seeded pool and a user holding some of both tokens
+fn setup() -> (Env, Pool, Keypair) {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 100_000_000);
+    env.fund(&pool.mint_y, &user.pubkey(), 100_000_000);
+    (env, pool, user)
+}
+
+// Like setup, but the pool holds as much X as Y so its concentrated liquidity, spaced
+// 10 ticks apart, starts at tick 0
+fn concentrated_setup() -> (Env, Pool, Keypair) {
+    let mut env = Env::new();
+    let pool = env.create_pool(init_args(1, Some(env.authority.pubkey())));
+    let authority = env.authority.insecure_clone();
+    env.fund(&pool.mint_x, &authority.pubkey(), SEED_X);
+    env.fund(&pool.mint_y, &authority.pubkey(), SEED_X);
+    let deposit = pool.deposit(&authority.pubkey(), 1, SEED_X, SEED_X, env.expiration());
+    let initialize = pool.initialize_concentrated(&authority.pubkey(), 10, 0);
+    env.send(&[deposit, initialize], &[&authority]).unwrap();
+
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 100_000_000);
+    env.fund(&pool.mint_y, &user.pubkey(), 100_000_000);
+    (env, pool, user)
+}
+
+// Sends an instruction signed by the pool authority
+fn send_as_authority(env: &mut Env, ix: Instruction) -> litesvm::types::TransactionResult {
+    let authority = env.authority.insecure_clone();
+    env.send(&[ix], &[&authority])
+}
+
+fn initialize_fails(args: instruction::Initialize, error: AmmError) {
+    let mut env = Env::new();
+    let pool = Pool::new(
+        env.create_mint(),
+        env.create_mint(),
+        args.seed,
+        args.fee,
+        args.curve_type,
+    );
+    let ix = pool.initialize(&env.authority.pubkey(), args);
+    assert_error(send_as_authority(&mut env, ix), error);
+}
+
+#[test]
+fn offer_expired() {
+    let (mut env, pool, user) = setup();
+    let swap = pool.swap(&user.pubkey(), true, 1_000, 0, env.now());
+    assert_error(env.send(&[swap], &[&user]), AmmError::OfferExpired);
+}
+
+#[test]
+fn pool_locked() {
+    let (mut env, pool, user) = setup();
+    let lock = pool.update(
+        &env.authority.pubkey(),
+        instruction::Update { paused: PAUSE_ALL },
+    );
+    send_as_authority(&mut env, lock).unwrap();
+
+    let swap = pool.swap(&user.pubkey(), true, 1_000, 0, env.expiration());
+    assert_error(env.send(&[swap], &[&user]), AmmError::PoolLocked);
+}
+
+#[test]
+fn slippage_exceeded() {
+    let (mut env, pool, user) = setup();
+    let swap = pool.swap(&user.pubkey(), true, 1_000, u64::MAX, env.expiration());
+    assert_error(env.send(&[swap], &[&user]), AmmError::SlippageExceeded);
+}
+
+#[test]
+fn overflow() {
+    let (mut env, pool, _) = setup();
+    let authority = env.authority.pubkey();
+    let reward_mint = env.create_mint();
+    env.fund(&reward_mint, &authority, 1_000);
+
+    // The schedule's total rewards don't fit in a u64
+    let args = instruction::CreateFarm {
+        reward_per_second: u64::MAX,
+        start_time: env.now(),
+        end_time: env.now() + 2,
+    };
+    let create_farm = pool.create_farm(&authority, &reward_mint, args);
+    assert_error(send_as_authority(&mut env, create_farm), AmmError::Overflow);
+}
+
+// Route through a single pool with the hop's accounts given by `hop`
+fn route_swap(env: &Env, pool: &Pool, user: &Pubkey, hop: &[Pubkey]) -> Instruction {
+    let accounts = accounts::RouteSwap {
+        user: *user,
+        mint_in: pool.mint_x,
+        mint_out: pool.mint_y,
+        user_in: pool.user_x(user),
+        user_out: pool.user_y(user),
+        token_program_in: pool.token_program_x,
+        token_program_out: pool.token_program_y,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    let mut ix = instruction_for(
+        accounts,
+        instruction::RouteSwap {
+            amount_in: 1_000_000,
+            min_amount_out: 0,
+            expiration: env.expiration(),
+        },
+    );
+    ix.accounts
+        .extend(hop.iter().map(|address| AccountMeta::new(*address, false)));
+    ix
+}
+
+#[test]
+fn invalid_token() {
+    let (mut env, pool, user) = setup();
+    // The hop's mints in the wrong order
+    let hop = [
+        pool.config,
+        pool.oracle,
+        pool.mint_y,
+        pool.mint_x,
+        pool.vault_x,
+        pool.vault_y,
+    ];
+    let route = route_swap(&env, &pool, &user.pubkey(), &hop);
+    assert_error(env.send(&[route], &[&user]), AmmError::InvalidToken);
+}
+
+#[test]
+fn liquidity_less_than_minimum() {
+    let mut env = Env::new();
+    let authority = env.authority.pubkey();
+    let pool = env.create_pool(init_args(1, Some(authority)));
+    env.fund(&pool.mint_x, &authority, SEED_X);
+    env.fund(&pool.mint_y, &authority, SEED_X);
+
+    // The first deposit has to bring both tokens
+    let deposit = pool.deposit(&authority, 1, 0, SEED_X, env.expiration());
+    assert_error(
+        send_as_authority(&mut env, deposit),
+        AmmError::LiquidityLessThanMinimum,
+    );
+}
+
+#[test]
+fn no_liquidity_in_pool() {
+    let mut env = Env::new();
+    let authority = env.authority.pubkey();
+    let pool = env.create_pool(init_args(1, Some(authority)));
+    env.fund(&pool.mint_x, &authority, SEED_X);
+    env.fund(&pool.mint_y, &authority, 0);
+
+    let swap = pool.swap(&authority, true, 1_000, 0, env.expiration());
+    assert_error(
+        send_as_authority(&mut env, swap),
+        AmmError::NoLiquidityInPool,
+    );
+}
+
+#[test]
+fn curve_error() {
+    let (mut env, pool, user) = setup();
+    // Adding this much to the reserve overflows the curve
+    let amount = u64::MAX - env.balance(&pool.user_x(&user.pubkey()));
+    env.fund(&pool.mint_x, &user.pubkey(), amount);
+
+    let swap = pool.swap(&user.pubkey(), true, u64::MAX, 0, env.expiration());
+    assert_error(env.send(&[swap], &[&user]), AmmError::CurveError);
+}
+
+#[test]
+fn invalid_fee() {
+    let mut args = init_args(1, None);
+    args.fee = 1_001;
+    initialize_fails(args, AmmError::InvalidFee);
+}
+
+#[test]
+fn invalid_authority() {
+    let (mut env, pool, user) = setup();
+    let update = pool.update(&user.pubkey(), instruction::UpdateFee { fee: 10 });
+    assert_error(env.send(&[update], &[&user]), AmmError::InvalidAuthority);
+}
+
+#[test]
+fn no_authority_set() {
+    let mut env = Env::new();
+    let pool = env.create_pool(init_args(1, None));
+    let update = pool.update(&env.authority.pubkey(), instruction::UpdateFee { fee: 10 });
+    assert_error(
+        send_as_authority(&mut env, update),
+        AmmError::NoAuthoritySet,
+    );
+}
+
+#[test]
+fn invalid_amount() {
+    let (mut env, pool, user) = setup();
+    let swap = pool.swap(&user.pubkey(), true, 0, 0, env.expiration());
+    assert_error(env.send(&[swap], &[&user]), AmmError::InvalidAmount);
+}
+
+#[test]
+fn insufficient_balance() {
+    let (mut env, pool, user) = setup();
+    // Borrowing more than the vault holds
+    let flash_loan = pool.flash_loan(&user.pubkey(), true, SEED_X + 1);
+    let repay = pool.repay_flash_loan(&user.pubkey());
+    assert_error(
+        env.send(&[flash_loan, repay], &[&user]),
+        AmmError::InsufficientBalance,
+    );
+}
+
+#[test]
+fn invalid_route() {
+    let (mut env, pool, user) = setup();
+    let route = route_swap(&env, &pool, &user.pubkey(), &[]);
+    assert_error(env.send(&[route], &[&user]), AmmError::InvalidRoute);
+}
+
+#[test]
+fn oracle_window_too_long() {
+    let (mut env, pool, user) = setup();
+    // No observation is an hour old yet
+    env.warp(60);
+    let twap = pool.get_twap(3_600);
+    assert_error(env.send(&[twap], &[&user]), AmmError::OracleWindowTooLong);
+}
+
+#[test]
+fn invalid_timestamp() {
+    let mut args = init_args(1, None);
+    args.fee_timelock = -1;
+    initialize_fails(args, AmmError::InvalidTimestamp);
+}
+
+#[test]
+fn no_pending_fee() {
+    let (mut env, pool, _) = setup();
+    let apply = pool.update(&env.authority.pubkey(), instruction::ApplyFee {});
+    assert_error(send_as_authority(&mut env, apply), AmmError::NoPendingFee);
+}
+
+#[test]
+fn timelock_not_expired() {
+    let mut env = Env::new();
+    let authority = env.authority.pubkey();
+    let mut args = init_args(1, Some(authority));
+    args.fee_timelock = 3_600;
+    let pool = env.create_pool(args);
+
+    let update = pool.update(&authority, instruction::UpdateFee { fee: 10 });
+    send_as_authority(&mut env, update).unwrap();
+
+    env.warp(3_599);
+    let apply = pool.update(&authority, instruction::ApplyFee {});
+    assert_error(
+        send_as_authority(&mut env, apply.clone()),
+        AmmError::TimelockNotExpired,
+    );
+
+    env.warp(1);
+    send_as_authority(&mut env, apply).unwrap();
+    assert_eq!(env.fetch::<Config>(&pool.config).fee, 10);
+}
+
+#[test]
+fn invalid_curve() {
+    let (mut env, pool, _) = setup();
+    // Constant product pools have no amplification to ramp
+    let ramp = pool.update(
+        &env.authority.pubkey(),
+        instruction::RampAmp {
+            target_amp: 100,
+            ramp_end: env.now() + 86_400,
+        },
+    );
+    assert_error(send_as_authority(&mut env, ramp), AmmError::InvalidCurve);
+}
+
+fn stable_args(seed: u64, authority: Option<Pubkey>, amp: u64) -> instruction::Initialize {
+    let mut args = init_args(seed, authority);
+    args.curve_type = CurveType::StableSwap;
+    args.amp = amp;
+    args
+}
+
+#[test]
+fn invalid_amp() {
+    initialize_fails(stable_args(1, None, 0), AmmError::InvalidAmp);
+}
+
+#[test]
+fn invalid_ramp() {
+    let mut env = Env::new();
+    let authority = env.authority.pubkey();
+    let pool = env.create_pool(stable_args(1, Some(authority), 100));
+
+    // Ramps have to take at least a day
+    let ramp = pool.update(
+        &authority,
+        instruction::RampAmp {
+            target_amp: 200,
+            ramp_end: env.now() + 60,
+        },
+    );
+    assert_error(send_as_authority(&mut env, ramp), AmmError::InvalidRamp);
+}
+
+#[test]
+fn invalid_tick() {
+    let (mut env, pool, _) = setup();
+    let initialize = pool.initialize_concentrated(&env.authority.pubkey(), 0, 0);
+    assert_error(
+        send_as_authority(&mut env, initialize),
+        AmmError::InvalidTick,
+    );
+}
+
+fn position(pool: &Pool, owner: &Pubkey, tick_lower: i32, tick_upper: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"position",
+            pool.concentrated_pool.as_ref(),
+            owner.as_ref(),
+            &tick_lower.to_le_bytes(),
+            &tick_upper.to_le_bytes(),
+        ],
+        &ID,
+    )
+    .0
+}
+
+// The tick array holding `tick`, created by `payer` first if it doesn't exist yet
+fn tick_array(env: &mut Env, pool: &Pool, payer: &Keypair, tick: i32) -> Pubkey {
+    let concentrated: ConcentratedPool = env.fetch(&pool.concentrated_pool);
+    let start_tick = concentrated.tick_array_start(tick);
+    let tick_array = pool.tick_array(start_tick);
+    if env.lamports(&tick_array) == 0 {
+        let initialize = pool.initialize_tick_array(&payer.pubkey(), start_tick);
+        env.send(&[initialize], &[payer]).unwrap();
+    }
+    tick_array
+}
+
+fn modify_position(
+    env: &mut Env,
+    pool: &Pool,
+    owner: &Keypair,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> accounts::ModifyPosition {
+    accounts::ModifyPosition {
+        owner: owner.pubkey(),
+        mint_x: pool.mint_x,
+        mint_y: pool.mint_y,
+        config: pool.config,
+        concentrated_pool: pool.concentrated_pool,
+        position: position(pool, &owner.pubkey(), tick_lower, tick_upper),
+        tick_array_lower: tick_array(env, pool, owner, tick_lower),
+        tick_array_upper: tick_array(env, pool, owner, tick_upper),
+        vault_x: pool.vault_x,
+        vault_y: pool.vault_y,
+        user_x: pool.user_x(&owner.pubkey()),
+        user_y: pool.user_y(&owner.pubkey()),
+        token_program_x: pool.token_program_x,
+        token_program_y: pool.token_program_y,
+        associated_token_program: associated_token::ID,
+    }
+}
+
+// Opens a position over [tick_lower, tick_upper) and adds `liquidity` to it
+fn add_position(
+    env: &mut Env,
+    pool: &Pool,
+    owner: &Keypair,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+) -> litesvm::types::TransactionResult {
+    let open = instruction_for(
+        accounts::OpenPosition {
+            owner: owner.pubkey(),
+            config: pool.config,
+            concentrated_pool: pool.concentrated_pool,
+            position: position(pool, &owner.pubkey(), tick_lower, tick_upper),
+            system_program: system_program::ID,
+        },
+        instruction::OpenPosition {
+            tick_lower,
+            tick_upper,
+        },
+    );
+    let increase = instruction_for(
+        modify_position(env, pool, owner, tick_lower, tick_upper),
+        instruction::IncreaseLiquidity {
+            liquidity,
+            max_x: u64::MAX,
+            max_y: u64::MAX,
+            expiration: env.expiration(),
+        },
+    );
+    env.send(&[open, increase], &[owner])
+}
+
+#[test]
+fn invalid_tick_array() {
+    let (mut env, pool, user) = concentrated_setup();
+
+    // However many ticks positions use, there is always room for more
+    for lower in (-1_000..1_000).step_by(20) {
+        add_position(&mut env, &pool, &user, lower, lower + 10, 1_000_000).unwrap();
+    }
+
+    // Selling X moves the price down out of the tick array at 0 into the one below
+    let (below, current) = (pool.tick_array(-320), pool.tick_array(0));
+    for tick_arrays in [vec![current], vec![current, current, below]] {
+        let swap = swap_concentrated(&env, &pool, &user.pubkey(), true, 1_000, &tick_arrays);
+        assert_error(env.send(&[swap], &[&user]), AmmError::InvalidTickArray);
+    }
+    let swap = swap_concentrated(&env, &pool, &user.pubkey(), true, 1_000, &[current, below]);
+    env.send(&[swap], &[&user]).unwrap();
+}
+
+#[test]
+fn position_not_empty() {
+    let (mut env, pool, user) = concentrated_setup();
+    add_position(&mut env, &pool, &user, -100, 100, 1_000_000).unwrap();
+
+    let position = position(&pool, &user.pubkey(), -100, 100);
+    let close = instruction_for(
+        accounts::ClosePosition {
+            owner: user.pubkey(),
+            position,
+        },
+        instruction::ClosePosition {},
+    );
+    assert_error(
+        env.send(std::slice::from_ref(&close), &[&user]),
+        AmmError::PositionNotEmpty,
+    );
+
+    // Emptying the position frees its ticks, closing it returns the rent
+    let decrease = instruction_for(
+        modify_position(&mut env, &pool, &user, -100, 100),
+        instruction::DecreaseLiquidity {
+            liquidity: 1_000_000,
+            min_x: 0,
+            min_y: 0,
+            expiration: env.expiration(),
+        },
+    );
+    env.send(&[decrease, close], &[&user]).unwrap();
+    assert_eq!(env.lamports(&position), 0);
+    for start_tick in [-320, 0] {
+        let tick_array: TickArray = env.fetch(&pool.tick_array(start_tick));
+        assert!(tick_array
+            .ticks
+            .iter()
+            .all(|tick| tick.liquidity_gross == 0));
+    }
+}
+
+#[test]
+fn flash_loan_active() {
+    let (mut env, pool, user) = setup();
+    // Nothing can trade against the vault while it is short of the loan
+    let flash_loan = pool.flash_loan(&user.pubkey(), true, 1_000);
+    let swap = pool.swap(&user.pubkey(), false, 1_000, 0, env.expiration());
+    let repay = pool.repay_flash_loan(&user.pubkey());
+    assert_error(
+        env.send(&[flash_loan, swap, repay], &[&user]),
+        AmmError::FlashLoanActive,
+    );
+}
+
+#[test]
+fn no_flash_loan() {
+    let (mut env, pool, user) = setup();
+    let repay = pool.repay_flash_loan(&user.pubkey());
+    assert_error(env.send(&[repay], &[&user]), AmmError::NoFlashLoan);
+}
+
+#[test]
+fn flash_loan_not_repaid() {
+    let (mut env, pool, user) = setup();
+    let flash_loan = pool.flash_loan(&user.pubkey(), true, 1_000);
+    assert_error(
+        env.send(&[flash_loan], &[&user]),
+        AmmError::FlashLoanNotRepaid,
+    );
+}
+
+fn set_trade_limits(env: &mut Env, pool: &Pool, max_trade: u16, breaker_threshold: u16) {
+    let update = pool.update(
+        &env.authority.pubkey(),
+        instruction::UpdateTradeLimits {
+            max_trade,
+            breaker_threshold,
+            breaker_window: 3_600,
+        },
+    );
+    send_as_authority(env, update).unwrap();
+}
+
+#[test]
+fn trade_too_large() {
+    let (mut env, pool, user) = setup();
+    // Swaps are capped at 1% of the input reserve
+    set_trade_limits(&mut env, &pool, 100, 0);
+
+    let swap = pool.swap(&user.pubkey(), true, SEED_X / 100 + 1, 0, env.expiration());
+    assert_error(env.send(&[swap], &[&user]), AmmError::TradeTooLarge);
+    let swap = pool.swap(&user.pubkey(), true, SEED_X / 100, 0, env.expiration());
+    env.send(&[swap], &[&user]).unwrap();
+}
+
+#[test]
+fn circuit_breaker_tripped() {
+    let (mut env, pool, user) = setup();
+    // Trips on a 1% price move within an hour
+    set_trade_limits(&mut env, &pool, 0, 100);
+
+    // The swap that moves the price too far still goes through, the next one doesn't
+    let swap = pool.swap(&user.pubkey(), true, SEED_X / 20, 0, env.expiration());
+    env.send(&[swap], &[&user]).unwrap();
+    assert!(env.fetch::<Config>(&pool.config).breaker_tripped);
+
+    let swap = pool.swap(&user.pubkey(), false, 1_000, 0, env.expiration());
+    assert_error(
+        env.send(std::slice::from_ref(&swap), &[&user]),
+        AmmError::CircuitBreakerTripped,
+    );
+
+    let reset = pool.update(&env.authority.pubkey(), instruction::ResetCircuitBreaker {});
+    send_as_authority(&mut env, reset).unwrap();
+    env.send(&[swap], &[&user]).unwrap();
+}
+
+fn swap_concentrated(
+    env: &Env,
+    pool: &Pool,
+    user: &Pubkey,
+    is_x: bool,
+    amount_in: u64,
+    tick_arrays: &[Pubkey],
+) -> Instruction {
+    let mut ix = instruction_for(
+        accounts::SwapConcentrated {
+            user: *user,
+            mint_x: pool.mint_x,
+            mint_y: pool.mint_y,
+            config: pool.config,
+            concentrated_pool: pool.concentrated_pool,
+            oracle: pool.oracle,
+            vault_x: pool.vault_x,
+            vault_y: pool.vault_y,
+            user_x: pool.user_x(user),
+            user_y: pool.user_y(user),
+            referrer: None,
+            token_program_x: pool.token_program_x,
+            token_program_y: pool.token_program_y,
+            associated_token_program: associated_token::ID,
+        },
+        instruction::SwapConcentrated {
+            is_x,
+            amount_in,
+            min_amount_out: 0,
+            expiration: env.expiration(),
+        },
+    );
+    ix.accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|tick_array| AccountMeta::new(*tick_array, false)),
+    );
+    ix
+}
+
+#[test]
+fn circuit_breaker_covers_concentrated_swaps() {
+    let (mut env, pool, user) = concentrated_setup();
+    add_position(&mut env, &pool, &user, -50_000, 50_000, 50_000_000).unwrap();
+    let tick_arrays = [
+        tick_array(&mut env, &pool, &user, -10),
+        tick_array(&mut env, &pool, &user, 0),
+    ];
+    set_trade_limits(&mut env, &pool, 0, 100);
+
+    // ~2% off the concentrated price
+    let swap = swap_concentrated(&env, &pool, &user.pubkey(), true, 500_000, &tick_arrays);
+    env.warp(60);
+    env.send(&[swap], &[&user]).unwrap();
+    assert!(env.fetch::<Config>(&pool.config).breaker_tripped);
+    // Concentrated swaps keep the oracle up to date too
+    let oracle: Oracle = env.fetch(&pool.oracle);
+    assert_eq!(oracle.last_timestamp, env.now());
+
+    let swap = swap_concentrated(&env, &pool, &user.pubkey(), false, 1_000, &tick_arrays);
+    assert_error(env.send(&[swap], &[&user]), AmmError::CircuitBreakerTripped);
+
+    // The trade cap applies to all of the input token backing the pool, 0.01% here
+    let reset = pool.update(&env.authority.pubkey(), instruction::ResetCircuitBreaker {});
+    send_as_authority(&mut env, reset).unwrap();
+    set_trade_limits(&mut env, &pool, 1, 0);
+    let swap = swap_concentrated(&env, &pool, &user.pubkey(), false, 1_000_000, &tick_arrays);
+    assert_error(env.send(&[swap], &[&user]), AmmError::TradeTooLarge);
+}
+
+#[test]
+fn invalid_trade_limits() {
+    let (mut env, pool, _) = setup();
+    let update = pool.update(
+        &env.authority.pubkey(),
+        instruction::UpdateTradeLimits {
+            max_trade: 10_001,
+            breaker_threshold: 0,
+            breaker_window: 0,
+        },
+    );
+    assert_error(
+        send_as_authority(&mut env, update),
+        AmmError::InvalidTradeLimits,
+    );
+}
+
+#[test]
+fn invalid_pause_flags() {
+    let (mut env, pool, _) = setup();
+    let update = pool.update(
+        &env.authority.pubkey(),
+        instruction::Update {
+            paused: PAUSE_ALL + 1,
+        },
+    );
+    assert_error(
+        send_as_authority(&mut env, update),
+        AmmError::InvalidPauseFlags,
+    );
+}
+
+#[test]
+fn invalid_config_version() {
+    let (mut env, pool, user) = setup();
+    let migrate = pool.migrate_config(&user.pubkey());
+
+    // Already current
+    assert_error(
+        env.send(std::slice::from_ref(&migrate), &[&user]),
+        AmmError::InvalidConfigVersion,
+    );
+
+    // Rewind the config to the first deployed layout, which nothing accepts until migrated
+    let config: Config = env.fetch(&pool.config);
+    let v0 = ConfigV0 {
+        seed: config.seed,
+        authority: config.authority,
+        mint_x: config.mint_x,
+        mint_y: config.mint_y,
+        fee: config.fee,
+        locked: false,
+        config_bump: config.config_bump,
+        lp_bump: config.lp_bump,
+    };
+    let mut account = env.svm.get_account(&pool.config).unwrap();
+    account.data = Config::DISCRIMINATOR.to_vec();
+    v0.serialize(&mut account.data).unwrap();
+    env.svm.set_account(pool.config, account).unwrap();
+    // which had no locked LP account either
+    env.svm
+        .set_account(pool.locked_lp, Account::default())
+        .unwrap();
+
+    let swap = pool.swap(&user.pubkey(), true, 1_000, 0, env.expiration());
+    assert!(env.send(std::slice::from_ref(&swap), &[&user]).is_err());
+
+    env.send(&[migrate], &[&user]).unwrap();
+    env.send(&[swap], &[&user]).unwrap();
+    let deposit = pool.deposit(&user.pubkey(), 1_000, u64::MAX, u64::MAX, env.expiration());
+    env.send(&[deposit], &[&user]).unwrap();
+}
+
+#[test]
+fn invalid_uri() {
+    let (mut env, pool, _) = setup();
+    let update = pool.update_lp_uri(&env.authority.pubkey(), &"a".repeat(201));
+    assert_error(send_as_authority(&mut env, update), AmmError::InvalidUri);
+}
+
+#[test]
+fn pool_not_empty() {
+    let (mut env, pool, _) = setup();
+    let close = pool.close_pool(&env.authority.pubkey());
+    assert_error(send_as_authority(&mut env, close), AmmError::PoolNotEmpty);
+}
+
+// `user` asks 10 Y per X, far above the pool price
+fn place_order(env: &mut Env, pool: &Pool, user: &Keypair) {
+    let args = instruction::PlaceLimitOrder {
+        id: 0,
+        amount_in: 1_000_000,
+        min_amount_out: 10_000_000,
+        tip: 0,
+        expiration: env.expiration(),
+    };
+    let place = pool.place_limit_order(&user.pubkey(), true, args);
+    env.send(&[place], &[user]).unwrap();
+}
+
+#[test]
+fn invalid_order() {
+    let (mut env, pool, user) = setup();
+    place_order(&mut env, &pool, &user);
+
+    // The order's accounts for the wrong side of the pool
+    let fill = pool.fill_limit_orders(&user.pubkey(), &[(user.pubkey(), 0, false)]);
+    assert_error(env.send(&[fill], &[&user]), AmmError::InvalidOrder);
+}
+
+#[test]
+fn order_not_fillable() {
+    let (mut env, pool, user) = setup();
+    place_order(&mut env, &pool, &user);
+
+    let fill = pool.fill_limit_orders(&user.pubkey(), &[(user.pubkey(), 0, true)]);
+    assert_error(env.send(&[fill], &[&user]), AmmError::OrderNotFillable);
+}
+
+#[test]
+fn invalid_referrer() {
+    let (mut env, pool, user) = setup();
+    let update = pool.update(
+        &env.authority.pubkey(),
+        instruction::UpdateReferralFee {
+            referral_fee: 1_000,
+        },
+    );
+    send_as_authority(&mut env, update).unwrap();
+
+    // The fee is taken in X, the input, but the referrer holds Y
+    let referrer = env.fund(&pool.mint_y, &Pubkey::new_unique(), 0);
+    let swap = pool.referred_swap(
+        &user.pubkey(),
+        Some(referrer),
+        true,
+        1_000_000,
+        0,
+        env.expiration(),
+    );
+    assert_error(env.send(&[swap], &[&user]), AmmError::InvalidReferrer);
+}
+
+#[test]
+fn authority_not_renounced() {
+    let (mut env, pool, user) = setup();
+    let register = pool.register_pool(&user.pubkey());
+    assert_error(
+        env.send(&[register], &[&user]),
+        AmmError::AuthorityNotRenounced,
+    );
+}
+
+#[test]
+fn farm_not_empty() {
+    let (mut env, pool, _) = setup();
+    let authority = env.authority.pubkey();
+    let reward_mint = env.create_mint();
+    env.fund(&reward_mint, &authority, 1_000);
+    let args = instruction::CreateFarm {
+        reward_per_second: 1,
+        start_time: env.now(),
+        end_time: env.now() + 1_000,
+    };
+    let create_farm = pool.create_farm(&authority, &reward_mint, args);
+    send_as_authority(&mut env, create_farm).unwrap();
+
+    // The authority's own stake keeps the farm open
+    let stake = pool.modify_stake(
+        &authority,
+        &reward_mint,
+        instruction::StakeLp { amount: 1_000 },
+    );
+    send_as_authority(&mut env, stake).unwrap();
+    let close = pool.close_farm(&authority, &reward_mint);
+    assert_error(send_as_authority(&mut env, close), AmmError::FarmNotEmpty);
+}
+
+#[test]
+fn transfer_hook_mint() {
+    let mut env = Env::new();
+    let pool = Pool::new(
+        env.create_transfer_hook_mint(),
+        env.create_mint(),
+        1,
+        30,
+        CurveType::ConstantProduct,
+    )
+    .with_token_programs(token_2022::ID, token::ID);
+    let initialize = pool.initialize(&env.authority.pubkey(), init_args(1, None));
+    assert_error(
+        send_as_authority(&mut env, initialize),
+        AmmError::TransferHookMint,
+    );
+}
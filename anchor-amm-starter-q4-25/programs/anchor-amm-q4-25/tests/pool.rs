@@ -0,0 +1,896 @@
+// LiteSVM returns its large FailedTransactionMetadata unboxed
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use anchor_amm_q4_25::{
+    errors::AmmError,
+    instruction,
+    quote::{
+        initial_lp, lp_decimals, quote_deposit, quote_swap, share_precision, PoolState,
+        MINIMUM_LIQUIDITY,
+    },
+    state::{
+        Config, CurveType, FeeSide, PoolEntry, CONFIG_VERSION, LP_SYMBOL, PAUSE_ALL, PAUSE_SWAPS,
+    },
+};
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{
+    associated_token::get_associated_token_address, metadata::MetadataAccount, token, token_2022,
+};
+use common::{assert_error, init_args, Env, Pool, SEED_X, SEED_Y};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+// Pool balances, LP supply and the user's X, Y and LP balances
+fn snapshot(env: &Env, pool: &Pool, user: &Keypair) -> [u64; 6] {
+    let user = user.pubkey();
+    [
+        env.balance(&pool.vault_x),
+        env.balance(&pool.vault_y),
+        env.supply(&pool.mint_lp),
+        env.balance(&pool.user_x(&user)),
+        env.balance(&pool.user_y(&user)),
+        env.balance(&pool.user_lp(&user)),
+    ]
+}
+
+// `amount` is the `lp` out of `supply` share of `reserve`, up to the curve's LP precision
+fn assert_share(amount: u64, reserve: u64, lp: u64, supply: u64) {
+    let share = (reserve as u128 * lp as u128 / supply as u128) as u64;
+    assert!(
+        amount.abs_diff(share) <= share / 10_000 + 1,
+        "{amount} is not {lp}/{supply} of {reserve}"
+    );
+}
+
+#[test]
+fn initializes_the_pool() {
+    let mut env = Env::new();
+    let authority = env.authority.pubkey();
+    let pool = env.create_pool(init_args(7, Some(authority)));
+
+    let config: Config = env.fetch(&pool.config);
+    assert_eq!(config.seed, 7);
+    assert_eq!(config.authority, Some(authority));
+    assert_eq!((config.mint_x, config.mint_y), (pool.mint_x, pool.mint_y));
+    assert_eq!(config.fee, 30);
+    assert_eq!(config.paused, 0);
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.lp_decimals, lp_decimals(6, 6));
+    assert_eq!(config.precision, share_precision(6, 6));
+
+    // Named after the mints, which have no metadata of their own
+    let metadata: MetadataAccount = env.fetch(&pool.lp_metadata);
+    assert_eq!(metadata.mint, pool.mint_lp);
+    assert_eq!(metadata.update_authority, pool.config);
+    assert_eq!(
+        metadata.name.trim_end_matches('\0'),
+        format!(
+            "{}-{} LP",
+            &pool.mint_x.to_string()[..4],
+            &pool.mint_y.to_string()[..4]
+        )
+    );
+    assert_eq!(metadata.symbol.trim_end_matches('\0'), LP_SYMBOL);
+
+    assert_eq!(env.supply(&pool.mint_lp), 0);
+    assert_eq!(env.balance(&pool.vault_x), 0);
+}
+
+#[test]
+fn lp_token_is_named_after_the_pair() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let (mint_x, mint_y) = (env.create_mint(), env.create_mint());
+    env.set_metadata(&mint_x, "SOL");
+
+    let pool = Pool::new(mint_x, mint_y, 1, 30, CurveType::ConstantProduct);
+    let initialize = pool.initialize(&authority.pubkey(), init_args(1, None));
+    env.send(&[initialize], &[&authority]).unwrap();
+
+    // Y has no metadata, so its address stands in for a symbol
+    let metadata: MetadataAccount = env.fetch(&pool.lp_metadata);
+    assert_eq!(
+        metadata.name.trim_end_matches('\0'),
+        format!("SOL-{} LP", &mint_y.to_string()[..4])
+    );
+}
+
+#[test]
+fn renounced_pools_register_once() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let pool = env.create_pool(init_args(7, Some(authority.pubkey())));
+
+    // Not while the authority can still pause the pool or change its fees
+    let register = pool.register_pool(&authority.pubkey());
+    assert!(env
+        .send(std::slice::from_ref(&register), &[&authority])
+        .is_err());
+
+    let renounce = pool.update(&authority.pubkey(), instruction::RenounceAuthority {});
+    env.send(&[renounce], &[&authority]).unwrap();
+    env.send(&[register], &[&authority]).unwrap();
+    let entry: PoolEntry = env.fetch(&pool.pool_entry);
+    assert_eq!(entry.config, pool.config);
+    assert_eq!((entry.mint_x, entry.mint_y), (pool.mint_x, pool.mint_y));
+    assert_eq!(entry.fee, 30);
+    assert_eq!(entry.curve_type, CurveType::ConstantProduct);
+
+    // The first pool registered keeps the pair, fee tier and curve
+    let duplicate = Pool::new(pool.mint_x, pool.mint_y, 8, 30, CurveType::ConstantProduct);
+    let initialize = duplicate.initialize(&authority.pubkey(), init_args(8, None));
+    env.send(&[initialize], &[&authority]).unwrap();
+    assert!(env
+        .send(
+            &[duplicate.register_pool(&authority.pubkey())],
+            &[&authority]
+        )
+        .is_err());
+
+    // but a StableSwap pool gets its own entry
+    let mut args = init_args(9, None);
+    args.curve_type = CurveType::StableSwap;
+    args.amp = 100;
+    let stable = Pool::new(pool.mint_x, pool.mint_y, 9, 30, CurveType::StableSwap);
+    env.send(
+        &[stable.initialize(&authority.pubkey(), args)],
+        &[&authority],
+    )
+    .unwrap();
+    env.send(&[stable.register_pool(&authority.pubkey())], &[&authority])
+        .unwrap();
+    let entry: PoolEntry = env.fetch(&stable.pool_entry);
+    assert_eq!(entry.config, stable.config);
+    assert_eq!(entry.curve_type, CurveType::StableSwap);
+}
+
+#[test]
+fn first_deposit_locks_minimum_liquidity() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.pubkey();
+
+    let lp = initial_lp(SEED_X, SEED_Y) - MINIMUM_LIQUIDITY;
+    assert_eq!(env.balance(&pool.user_lp(&authority)), lp);
+    assert_eq!(env.balance(&pool.locked_lp), MINIMUM_LIQUIDITY);
+    assert_eq!(env.supply(&pool.mint_lp), lp + MINIMUM_LIQUIDITY);
+    assert_eq!(env.balance(&pool.vault_x), SEED_X);
+    assert_eq!(env.balance(&pool.vault_y), SEED_Y);
+    assert_eq!(env.balance(&pool.user_x(&authority)), 0);
+}
+
+#[test]
+fn first_deposit_respects_min_lp() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let pool = env.create_pool(init_args(1, Some(authority.pubkey())));
+    env.fund(&pool.mint_x, &authority.pubkey(), SEED_X);
+    env.fund(&pool.mint_y, &authority.pubkey(), SEED_Y);
+
+    // For the first deposit `lp_amount` is the least LP the depositor accepts
+    let lp = initial_lp(SEED_X, SEED_Y) - MINIMUM_LIQUIDITY;
+    let deposit = pool.deposit(
+        &authority.pubkey(),
+        lp + 1,
+        SEED_X,
+        SEED_Y,
+        env.expiration(),
+    );
+    assert_error(
+        env.send(&[deposit], &[&authority]),
+        AmmError::SlippageExceeded,
+    );
+}
+
+#[test]
+fn subsequent_deposit_keeps_the_price() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), SEED_X);
+    env.fund(&pool.mint_y, &user.pubkey(), SEED_Y);
+
+    let [vault_x, vault_y, supply, ..] = snapshot(&env, &pool, &user);
+    let amount = supply / 10;
+    let deposit = pool.deposit(&user.pubkey(), amount, SEED_X, SEED_Y, env.expiration());
+    env.send(&[deposit], &[&user]).unwrap();
+
+    let [new_vault_x, new_vault_y, new_supply, user_x, user_y, user_lp] =
+        snapshot(&env, &pool, &user);
+    assert_eq!(user_lp, amount);
+    assert_eq!(new_supply, supply + amount);
+
+    // Paid in the pool ratio
+    let (paid_x, paid_y) = (SEED_X - user_x, SEED_Y - user_y);
+    assert_eq!(
+        (new_vault_x, new_vault_y),
+        (vault_x + paid_x, vault_y + paid_y)
+    );
+    assert_share(paid_x, vault_x, amount, supply);
+    assert_share(paid_y, vault_y, amount, supply);
+}
+
+#[test]
+fn deposit_respects_max_amounts() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), SEED_X);
+    env.fund(&pool.mint_y, &user.pubkey(), SEED_Y);
+
+    let before = snapshot(&env, &pool, &user);
+    let amount = before[2] / 10;
+    let deposit = pool.deposit(
+        &user.pubkey(),
+        amount,
+        SEED_X / 20,
+        SEED_Y,
+        env.expiration(),
+    );
+    assert_error(env.send(&[deposit], &[&user]), AmmError::SlippageExceeded);
+    assert_eq!(snapshot(&env, &pool, &user), before);
+}
+
+#[test]
+fn withdraw_returns_both_tokens() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+
+    let [vault_x, vault_y, supply, _, _, user_lp] = snapshot(&env, &pool, &authority);
+    let amount = user_lp / 2;
+
+    // Asking for more than the share is worth fails
+    let withdraw = pool.withdraw(
+        &authority.pubkey(),
+        amount,
+        vault_x / 2 + 1,
+        0,
+        env.expiration(),
+    );
+    assert_error(
+        env.send(&[withdraw], &[&authority]),
+        AmmError::SlippageExceeded,
+    );
+
+    let withdraw = pool.withdraw(&authority.pubkey(), amount, 0, 0, env.expiration());
+    env.send(&[withdraw], &[&authority]).unwrap();
+
+    let [new_vault_x, new_vault_y, new_supply, user_x, user_y, new_user_lp] =
+        snapshot(&env, &pool, &authority);
+    assert_eq!(new_user_lp, user_lp - amount);
+    assert_eq!(new_supply, supply - amount);
+    assert_eq!(
+        (new_vault_x, new_vault_y),
+        (vault_x - user_x, vault_y - user_y)
+    );
+
+    // Paid out in the pool ratio
+    assert_share(user_x, vault_x, amount, supply);
+    assert_share(user_y, vault_y, amount, supply);
+
+    // The locked minimum liquidity can't be withdrawn, so the pool never empties
+    let withdraw = pool.withdraw(&authority.pubkey(), new_user_lp, 0, 0, env.expiration());
+    env.send(&[withdraw], &[&authority]).unwrap();
+    assert_eq!(env.supply(&pool.mint_lp), MINIMUM_LIQUIDITY);
+    assert!(env.balance(&pool.vault_x) > 0 && env.balance(&pool.vault_y) > 0);
+}
+
+fn swap(is_x: bool) {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 10_000_000);
+    env.fund(&pool.mint_y, &user.pubkey(), 10_000_000);
+
+    let [vault_x, vault_y, supply, user_x, user_y, _] = snapshot(&env, &pool, &user);
+    let swap = pool.swap(&user.pubkey(), is_x, 10_000_000, 1, env.expiration());
+    env.send(&[swap], &[&user]).unwrap();
+
+    let [new_vault_x, new_vault_y, new_supply, new_user_x, new_user_y, _] =
+        snapshot(&env, &pool, &user);
+    assert_eq!(new_supply, supply);
+    assert_eq!(new_vault_x + new_user_x, vault_x + user_x);
+    assert_eq!(new_vault_y + new_user_y, vault_y + user_y);
+
+    let (paid, received) = match is_x {
+        true => (user_x - new_user_x, new_user_y - user_y),
+        false => (user_y - new_user_y, new_user_x - user_x),
+    };
+    assert_eq!(paid, 10_000_000);
+
+    // At most the spot price (2 Y per X) less the 0.3% fee, and the pool's k only grows
+    let spot = match is_x {
+        true => paid * 2,
+        false => paid / 2,
+    };
+    assert!(received > 0 && received <= spot * 9_970 / 10_000);
+    assert!(new_vault_x as u128 * new_vault_y as u128 > vault_x as u128 * vault_y as u128);
+}
+
+#[test]
+fn swaps_x_for_y() {
+    swap(true);
+}
+
+#[test]
+fn swaps_y_for_x() {
+    swap(false);
+}
+
+#[test]
+fn swap_respects_min_amount_out() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 10_000_000);
+
+    let before = snapshot(&env, &pool, &user);
+    // 10 X can't buy more than 20 Y
+    let swap = pool.swap(
+        &user.pubkey(),
+        true,
+        10_000_000,
+        20_000_000,
+        env.expiration(),
+    );
+    assert_error(env.send(&[swap], &[&user]), AmmError::SlippageExceeded);
+    assert_eq!(snapshot(&env, &pool, &user), before);
+}
+
+#[test]
+fn output_fee_is_split_with_the_referrer() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let mut args = init_args(1, Some(authority.pubkey()));
+    args.fee_side = FeeSide::Output;
+    args.referral_fee = 5_000;
+    let pool = env.create_pool(args);
+    env.seed_pool(&pool);
+
+    let config: Config = env.fetch(&pool.config);
+    assert_eq!(
+        (config.fee_side, config.referral_fee),
+        (FeeSide::Output, 5_000)
+    );
+
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 10_000_000);
+    // With the fee on the output the referrer is paid in Y
+    let referrer = env.fund(&pool.mint_y, &Pubkey::new_unique(), 0);
+
+    let [vault_x, vault_y, _, _, user_y, _] = snapshot(&env, &pool, &user);
+    let swap = pool.referred_swap(
+        &user.pubkey(),
+        Some(referrer),
+        true,
+        10_000_000,
+        1,
+        env.expiration(),
+    );
+    env.send(&[swap], &[&user]).unwrap();
+    let [new_vault_x, new_vault_y, _, _, new_user_y, _] = snapshot(&env, &pool, &user);
+
+    // The whole input is swapped, and half of the 0.3% fee on the output goes to the
+    // referrer while the rest stays with the LPs
+    let received = new_user_y - user_y;
+    let referral = env.balance(&referrer);
+    assert_eq!(new_vault_x, vault_x + 10_000_000);
+    assert_eq!(vault_y - new_vault_y, received + referral);
+    assert!(referral > 0);
+    assert!(referral.abs_diff((received + 2 * referral) * 15 / 10_000) <= 1);
+    assert_eq!(env.fetch::<Config>(&pool.config).protocol_fees_y, 0);
+}
+
+// The quote clients build from the on-chain accounts, for a pool in fixed fee mode
+fn pool_state(env: &Env, pool: &Pool) -> PoolState {
+    let config: Config = env.fetch(&pool.config);
+    let (reserve_x, reserve_y) = config
+        .reserves(env.balance(&pool.vault_x), env.balance(&pool.vault_y))
+        .unwrap();
+    PoolState {
+        curve_type: config.curve_type.into(),
+        reserve_x,
+        reserve_y,
+        lp_supply: env.supply(&pool.mint_lp),
+        fee: config.fee,
+        protocol_fee: config.protocol_fee,
+        amp: config.amp(env.now()),
+        precision: config.precision,
+        fee_side: config.fee_side.into(),
+        referral_fee: 0,
+    }
+}
+
+#[test]
+fn swaps_match_the_quote() {
+    for (curve_type, amp, fee_side) in [
+        (CurveType::ConstantProduct, 0, FeeSide::Input),
+        (CurveType::ConstantProduct, 0, FeeSide::Output),
+        (CurveType::StableSwap, 100, FeeSide::Input),
+        (CurveType::StableSwap, 100, FeeSide::Output),
+    ] {
+        let mut env = Env::new();
+        let mut args = init_args(1, Some(env.authority.pubkey()));
+        args.curve_type = curve_type;
+        args.amp = amp;
+        args.fee_side = fee_side;
+        args.protocol_fee = 2_000;
+        let pool = env.create_pool(args);
+        env.seed_pool(&pool);
+
+        let user = env.user();
+        env.fund(&pool.mint_x, &user.pubkey(), 10_000_000);
+        env.fund(&pool.mint_y, &user.pubkey(), 10_000_000);
+        for is_x in [true, false] {
+            let quote = quote_swap(&pool_state(&env, &pool), is_x, 10_000_000).unwrap();
+            let [_, _, _, user_x, user_y, _] = snapshot(&env, &pool, &user);
+            let swap = pool.swap(&user.pubkey(), is_x, 10_000_000, 1, env.expiration());
+            env.send(&[swap], &[&user]).unwrap();
+            let [_, _, _, new_user_x, new_user_y, _] = snapshot(&env, &pool, &user);
+
+            // The swap pays out the quoted amount and leaves the quoted reserves
+            let received = match is_x {
+                true => new_user_y - user_y,
+                false => new_user_x - user_x,
+            };
+            let state = pool_state(&env, &pool);
+            assert_eq!(received, quote.amount_out, "{curve_type:?} {fee_side:?}");
+            assert_eq!(
+                user_x + user_y + received - new_user_x - new_user_y,
+                quote.amount_in
+            );
+            assert_eq!(
+                (state.reserve_x, state.reserve_y),
+                (quote.reserve_x, quote.reserve_y)
+            );
+            assert!(quote.protocol_fee > 0);
+        }
+    }
+}
+
+// Fee Token-2022 withholds from a transfer of `amount` at 1%, rounded up
+fn transfer_fee(amount: u64) -> u64 {
+    amount.div_ceil(100)
+}
+
+#[test]
+fn transfer_fee_pool_prices_what_the_vaults_receive() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let pool = Pool::new(
+        env.create_transfer_fee_mint(100),
+        env.create_mint(),
+        1,
+        30,
+        CurveType::ConstantProduct,
+    )
+    .with_token_programs(token_2022::ID, token::ID);
+    let initialize = pool.initialize(&authority.pubkey(), init_args(1, Some(authority.pubkey())));
+    env.send(&[initialize], &[&authority]).unwrap();
+
+    // The first deposit sets the price from what reached the vaults
+    env.seed_pool(&pool);
+    let [vault_x, vault_y, ..] = snapshot(&env, &pool, &authority);
+    assert_eq!((vault_x, vault_y), (SEED_X - transfer_fee(SEED_X), SEED_Y));
+
+    // Later deposits pay the fee on top, so the vaults get exactly the quote
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 100_000_000);
+    env.fund(&pool.mint_y, &user.pubkey(), 100_000_000);
+    let lp_amount = env.supply(&pool.mint_lp) / 100;
+    let quote = quote_deposit(&pool_state(&env, &pool), lp_amount).unwrap();
+    let deposit = pool.deposit(
+        &user.pubkey(),
+        lp_amount,
+        u64::MAX,
+        u64::MAX,
+        env.expiration(),
+    );
+    env.send(&[deposit], &[&user]).unwrap();
+    let [vault_x, vault_y, _, user_x, user_y, user_lp] = snapshot(&env, &pool, &user);
+    assert_eq!(
+        (vault_x, vault_y),
+        (
+            SEED_X - transfer_fee(SEED_X) + quote.amount_x,
+            SEED_Y + quote.amount_y
+        )
+    );
+    assert_eq!(user_lp, lp_amount);
+    assert!(100_000_000 - user_x > quote.amount_x);
+    assert_eq!(100_000_000 - user_y, quote.amount_y);
+
+    // Swapping X in prices what is left after the fee
+    let received = 1_000_000 - transfer_fee(1_000_000);
+    let quote = quote_swap(&pool_state(&env, &pool), true, received).unwrap();
+    let swap = pool.swap(&user.pubkey(), true, 1_000_000, 1, env.expiration());
+    env.send(&[swap], &[&user]).unwrap();
+    let [new_vault_x, _, _, new_user_x, new_user_y, _] = snapshot(&env, &pool, &user);
+    assert_eq!(user_x - new_user_x, 1_000_000);
+    assert_eq!(new_vault_x - vault_x, received);
+    assert_eq!(new_user_y - user_y, quote.amount_out);
+
+    // Swapping X out sends the quote, and the user nets it less the fee
+    let [vault_x, _, _, user_x, ..] = snapshot(&env, &pool, &user);
+    let quote = quote_swap(&pool_state(&env, &pool), false, 1_000_000).unwrap();
+    let swap = pool.swap(&user.pubkey(), false, 1_000_000, 1, env.expiration());
+    env.send(&[swap], &[&user]).unwrap();
+    let [new_vault_x, _, _, new_user_x, ..] = snapshot(&env, &pool, &user);
+    assert_eq!(vault_x - new_vault_x, quote.amount_out);
+    assert_eq!(
+        new_user_x - user_x,
+        quote.amount_out - transfer_fee(quote.amount_out)
+    );
+
+    // Withdrawals pay out the share, and the fee comes off what the user gets
+    let [vault_x, vault_y, supply, user_x, user_y, user_lp] = snapshot(&env, &pool, &user);
+    let withdraw = pool.withdraw(&user.pubkey(), user_lp, 0, 0, env.expiration());
+    env.send(&[withdraw], &[&user]).unwrap();
+    let [new_vault_x, new_vault_y, _, new_user_x, new_user_y, _] = snapshot(&env, &pool, &user);
+    let (sent_x, sent_y) = (vault_x - new_vault_x, vault_y - new_vault_y);
+    assert_share(sent_x, vault_x, user_lp, supply);
+    assert_share(sent_y, vault_y, user_lp, supply);
+    assert_eq!(new_user_x - user_x, sent_x - transfer_fee(sent_x));
+    assert_eq!(new_user_y - user_y, sent_y);
+}
+
+#[test]
+fn rejects_expired_instructions() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+    env.fund(&pool.mint_x, &authority.pubkey(), 10_000_000);
+    env.fund(&pool.mint_y, &authority.pubkey(), 10_000_000);
+
+    // The deadline is exclusive
+    let expiration = env.now();
+    let user = authority.pubkey();
+    for ix in [
+        pool.deposit(&user, 1_000, u64::MAX, u64::MAX, expiration),
+        pool.withdraw(&user, 1_000, 0, 0, expiration),
+        pool.swap(&user, true, 1_000, 0, expiration),
+    ] {
+        assert_error(env.send(&[ix], &[&authority]), AmmError::OfferExpired);
+    }
+
+    // A deadline that was fine when the order was signed runs out
+    let swap = pool.swap(&user, false, 1_000, 0, env.now() + 10);
+    env.warp(10);
+    assert_error(env.send(&[swap], &[&authority]), AmmError::OfferExpired);
+}
+
+#[test]
+fn authority_pauses_and_resumes_the_pool() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+    let user = env.user();
+    env.fund(&pool.mint_x, &user.pubkey(), 10_000_000);
+    env.fund(&pool.mint_y, &user.pubkey(), 10_000_000);
+
+    let pause = |paused| pool.update(&authority.pubkey(), instruction::Update { paused });
+    let swap = |env: &Env| pool.swap(&user.pubkey(), true, 1_000_000, 1, env.expiration());
+    let deposit =
+        |env: &Env| pool.deposit(&user.pubkey(), 1_000, u64::MAX, u64::MAX, env.expiration());
+
+    // Nobody else can pause it
+    let result = env.send(
+        &[pool.update(&user.pubkey(), instruction::Update { paused: PAUSE_ALL })],
+        &[&user],
+    );
+    assert_error(result, AmmError::InvalidAuthority);
+
+    env.send(&[pause(PAUSE_ALL)], &[&authority]).unwrap();
+    assert_eq!(env.fetch::<Config>(&pool.config).paused, PAUSE_ALL);
+    assert_error(env.send(&[swap(&env)], &[&user]), AmmError::PoolLocked);
+    assert_error(env.send(&[deposit(&env)], &[&user]), AmmError::PoolLocked);
+    let withdraw = pool.withdraw(&authority.pubkey(), 1_000, 0, 0, env.expiration());
+    assert_error(env.send(&[withdraw], &[&authority]), AmmError::PoolLocked);
+
+    // Swaps only
+    env.send(&[pause(PAUSE_SWAPS)], &[&authority]).unwrap();
+    assert_error(env.send(&[swap(&env)], &[&user]), AmmError::PoolLocked);
+    env.send(&[deposit(&env)], &[&user]).unwrap();
+
+    env.send(&[pause(0)], &[&authority]).unwrap();
+    assert_eq!(env.fetch::<Config>(&pool.config).paused, 0);
+    env.send(&[swap(&env)], &[&user]).unwrap();
+}
+
+#[test]
+fn authority_is_handed_over_in_two_steps() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+    let new_authority = env.user();
+    let stranger = env.user();
+
+    let propose = pool.update(
+        &authority.pubkey(),
+        instruction::ProposeAuthority {
+            new_authority: new_authority.pubkey(),
+        },
+    );
+    env.send(&[propose], &[&authority]).unwrap();
+
+    // Only the proposed authority can accept
+    let result = env.send(&[pool.accept_authority(&stranger.pubkey())], &[&stranger]);
+    assert_error(result, AmmError::InvalidAuthority);
+    env.send(
+        &[pool.accept_authority(&new_authority.pubkey())],
+        &[&new_authority],
+    )
+    .unwrap();
+
+    let config: Config = env.fetch(&pool.config);
+    assert_eq!(config.authority, Some(new_authority.pubkey()));
+    assert_eq!(config.pending_authority, None);
+
+    // The old authority is out, the new one is in
+    let lock =
+        |signer: &Keypair| pool.update(&signer.pubkey(), instruction::Update { paused: PAUSE_ALL });
+    assert_error(
+        env.send(&[lock(&authority)], &[&authority]),
+        AmmError::InvalidAuthority,
+    );
+    env.send(&[lock(&new_authority)], &[&new_authority])
+        .unwrap();
+
+    // A paused pool can't be renounced, it could never be resumed
+    let renounce = pool.update(&new_authority.pubkey(), instruction::RenounceAuthority {});
+    assert_error(
+        env.send(std::slice::from_ref(&renounce), &[&new_authority]),
+        AmmError::PoolLocked,
+    );
+    let unlock = pool.update(&new_authority.pubkey(), instruction::Update { paused: 0 });
+    env.send(std::slice::from_ref(&unlock), &[&new_authority])
+        .unwrap();
+    env.send(&[renounce], &[&new_authority]).unwrap();
+
+    assert_eq!(env.fetch::<Config>(&pool.config).authority, None);
+    assert_error(
+        env.send(&[unlock], &[&new_authority]),
+        AmmError::NoAuthoritySet,
+    );
+}
+
+#[test]
+fn protocol_fee_updates_wait_for_the_timelock() {
+    let mut env = Env::new();
+    let authority = env.authority.insecure_clone();
+    let mut args = init_args(1, Some(authority.pubkey()));
+    args.fee_timelock = 3_600;
+    args.referral_fee = 2_000;
+    let pool = env.create_pool(args);
+
+    // Protocol and referral shares can't add up to more than the whole fee
+    let too_much = pool.update(
+        &authority.pubkey(),
+        instruction::UpdateProtocolFee {
+            protocol_fee: 8_001,
+        },
+    );
+    assert_error(env.send(&[too_much], &[&authority]), AmmError::InvalidFee);
+
+    let update = pool.update(
+        &authority.pubkey(),
+        instruction::UpdateProtocolFee {
+            protocol_fee: 8_000,
+        },
+    );
+    env.send(&[update], &[&authority]).unwrap();
+    let config: Config = env.fetch(&pool.config);
+    assert_eq!(config.protocol_fee, 0);
+    assert_eq!(config.pending_protocol_fee, Some(8_000));
+
+    let apply = pool.update(&authority.pubkey(), instruction::ApplyProtocolFee {});
+    assert_error(
+        env.send(std::slice::from_ref(&apply), &[&authority]),
+        AmmError::TimelockNotExpired,
+    );
+
+    env.warp(3_600);
+    env.send(&[apply], &[&authority]).unwrap();
+    let config: Config = env.fetch(&pool.config);
+    assert_eq!(
+        (config.protocol_fee, config.pending_protocol_fee),
+        (8_000, None)
+    );
+}
+
+#[test]
+fn authority_updates_the_lp_uri() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+    let stranger = env.user();
+    let uri = "https://example.com/lp.json";
+
+    let result = env.send(&[pool.update_lp_uri(&stranger.pubkey(), uri)], &[&stranger]);
+    assert_error(result, AmmError::InvalidAuthority);
+
+    env.send(
+        &[pool.update_lp_uri(&authority.pubkey(), uri)],
+        &[&authority],
+    )
+    .unwrap();
+
+    // Only the URI changes
+    let metadata: MetadataAccount = env.fetch(&pool.lp_metadata);
+    assert_eq!(metadata.uri.trim_end_matches('\0'), uri);
+    assert_eq!(metadata.symbol.trim_end_matches('\0'), LP_SYMBOL);
+}
+
+#[test]
+fn authority_closes_an_empty_pool() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+
+    // 1.0001^6930 is within the fee of the seeded price, 2 Y per X
+    let initialize = pool.initialize_concentrated(&authority.pubkey(), 10, 6_930);
+    env.send(&[initialize], &[&authority]).unwrap();
+
+    // Everything but the locked LP tokens has to be withdrawn first
+    let lp = env.balance(&pool.user_lp(&authority.pubkey()));
+    let withdraw = pool.withdraw(&authority.pubkey(), lp, 0, 0, env.expiration());
+    env.send(&[withdraw], &[&authority]).unwrap();
+
+    // No protocol fees are owed, and the reserves behind the locked LP tokens stay put
+    let before = snapshot(&env, &pool, &authority);
+    env.send(&[pool.close_pool(&authority.pubkey())], &[&authority])
+        .unwrap();
+    assert_eq!(snapshot(&env, &pool, &authority), before);
+
+    for closed in [pool.config, pool.oracle, pool.concentrated_pool] {
+        assert_eq!(env.lamports(&closed), 0, "{closed} is still open");
+    }
+
+    // The LP mint outlives the pool, so its seed can't be initialized again
+    let initialize = pool.initialize(&authority.pubkey(), init_args(1, Some(authority.pubkey())));
+    assert!(env.send(&[initialize], &[&authority]).is_err());
+
+    // Another pool can take the pair and fee tier
+    let replacement = Pool::new(pool.mint_x, pool.mint_y, 2, 30, CurveType::ConstantProduct);
+    env.send(
+        &[replacement.initialize(&authority.pubkey(), init_args(2, Some(authority.pubkey())))],
+        &[&authority],
+    )
+    .unwrap();
+}
+
+#[test]
+fn farms_are_closed_before_the_pool() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let authority = env.authority.insecure_clone();
+    let reward_mint = env.create_mint();
+    let authority_reward = env.fund(&reward_mint, &authority.pubkey(), 100_000);
+
+    let args = instruction::CreateFarm {
+        reward_per_second: 10,
+        start_time: env.now(),
+        end_time: env.now() + 10_000,
+    };
+    let create_farm = pool.create_farm(&authority.pubkey(), &reward_mint, args);
+    env.send(&[create_farm], &[&authority]).unwrap();
+    assert_eq!(env.balance(&authority_reward), 0);
+
+    // Stake for a while, then take everything out of the farm and the pool
+    let stake = instruction::StakeLp { amount: 1_000 };
+    let stake = pool.modify_stake(&authority.pubkey(), &reward_mint, stake);
+    env.send(&[stake], &[&authority]).unwrap();
+    env.warp(100);
+    let unstake = instruction::UnstakeLp { amount: 1_000 };
+    let unstake = pool.modify_stake(&authority.pubkey(), &reward_mint, unstake);
+    env.send(&[unstake], &[&authority]).unwrap();
+    let lp = env.balance(&pool.user_lp(&authority.pubkey()));
+    let withdraw = pool.withdraw(&authority.pubkey(), lp, 0, 0, env.expiration());
+    env.send(&[withdraw], &[&authority]).unwrap();
+
+    // The farm is still open
+    let close_pool = pool.close_pool(&authority.pubkey());
+    assert_error(
+        env.send(std::slice::from_ref(&close_pool), &[&authority]),
+        AmmError::PoolNotEmpty,
+    );
+
+    // and owes the rewards earned while staked until they are harvested
+    let close_farm = pool.close_farm(&authority.pubkey(), &reward_mint);
+    assert_error(
+        env.send(std::slice::from_ref(&close_farm), &[&authority]),
+        AmmError::FarmNotEmpty,
+    );
+    let harvest = pool.modify_stake(&authority.pubkey(), &reward_mint, instruction::Harvest {});
+    env.send(&[harvest], &[&authority]).unwrap();
+    let harvested = env.balance(&authority_reward);
+    assert!(harvested > 0);
+
+    // Closing refunds the rest of the schedule
+    env.send(&[close_farm], &[&authority]).unwrap();
+    assert_eq!(env.balance(&authority_reward), 100_000);
+    assert_eq!(env.lamports(&pool.farm(&reward_mint)), 0);
+
+    env.send(&[close_pool], &[&authority]).unwrap();
+    assert_eq!(env.lamports(&pool.config), 0);
+}
+
+// An order selling X for Y at `min_amount_out` per 1 X, tipping the keeper 0.001 SOL
+fn sell_x(env: &Env, id: u64, min_amount_out: u64) -> instruction::PlaceLimitOrder {
+    instruction::PlaceLimitOrder {
+        id,
+        amount_in: 1_000_000,
+        min_amount_out,
+        tip: 1_000_000,
+        expiration: env.expiration(),
+    }
+}
+
+#[test]
+fn limit_order_fills_once_the_price_crosses() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let (owner, trader, keeper) = (env.user(), env.user(), env.user());
+    env.fund(&pool.mint_x, &owner.pubkey(), 1_000_000);
+    env.fund(&pool.mint_x, &trader.pubkey(), 0);
+    env.fund(&pool.mint_y, &trader.pubkey(), 100_000_000);
+
+    // Asks 2.1 Y per X while the pool pays just under 2
+    let place = pool.place_limit_order(&owner.pubkey(), true, sell_x(&env, 1, 2_100_000));
+    env.send(&[place], &[&owner]).unwrap();
+    let order = pool.order(&owner.pubkey(), 1);
+    let order_vault = get_associated_token_address(&order, &pool.mint_x);
+    assert_eq!(env.balance(&pool.user_x(&owner.pubkey())), 0);
+    assert_eq!(env.balance(&order_vault), 1_000_000);
+
+    let orders = [(owner.pubkey(), 1, true)];
+    assert_error(
+        env.send(
+            &[pool.fill_limit_orders(&keeper.pubkey(), &orders)],
+            &[&keeper],
+        ),
+        AmmError::OrderNotFillable,
+    );
+
+    // Buying X moves the price past the limit
+    let swap = pool.swap(&trader.pubkey(), false, 100_000_000, 1, env.expiration());
+    env.send(&[swap], &[&trader]).unwrap();
+
+    let keeper_lamports = env.lamports(&keeper.pubkey());
+    env.send(
+        &[pool.fill_limit_orders(&keeper.pubkey(), &orders)],
+        &[&keeper],
+    )
+    .unwrap();
+    assert!(env.balance(&pool.user_y(&owner.pubkey())) >= 2_100_000);
+    // The tip more than pays for the transaction
+    assert!(env.lamports(&keeper.pubkey()) > keeper_lamports);
+    assert_eq!(env.lamports(&order), 0);
+    assert_eq!(env.lamports(&order_vault), 0);
+}
+
+#[test]
+fn owner_cancels_a_limit_order() {
+    let mut env = Env::new();
+    let pool = env.seeded_pool();
+    let owner = env.user();
+    env.fund(&pool.mint_x, &owner.pubkey(), 1_000_000);
+    env.fund(&pool.mint_y, &owner.pubkey(), 0);
+    let lamports = env.lamports(&owner.pubkey());
+
+    let place = pool.place_limit_order(&owner.pubkey(), true, sell_x(&env, 7, 10_000_000));
+    env.send(&[place], &[&owner]).unwrap();
+    env.send(
+        &[pool.cancel_limit_order(&owner.pubkey(), 7, true)],
+        &[&owner],
+    )
+    .unwrap();
+
+    // Tokens, tip and rent all come back, less at most the two transaction fees
+    let order = pool.order(&owner.pubkey(), 7);
+    assert_eq!(env.balance(&pool.user_x(&owner.pubkey())), 1_000_000);
+    assert_eq!(env.lamports(&order), 0);
+    assert!(lamports - env.lamports(&owner.pubkey()) <= 2 * 5_000);
+}
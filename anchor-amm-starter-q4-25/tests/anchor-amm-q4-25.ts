@@ -1,11 +1,14 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { PublicKey } from "@solana/web3.js";
+import { PublicKey, SystemProgram } from "@solana/web3.js";
 import {
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
   createMint,
   mintTo,
   getOrCreateAssociatedTokenAccount,
   getAccount,
+  getAssociatedTokenAddressSync,
 } from "@solana/spl-token";
 import { assert } from "chai";
 import { AnchorAmmQ425 } from "../target/types/anchor_amm_q4_25";
@@ -19,6 +22,14 @@ describe("anchor-amm-q4-25", () => {
 
   const seed = new anchor.BN(Math.floor(Math.random() * 1_000_000));
   const fee = 30; // 0.3% in basis points
+  const protocolFee = 1000; // 10% of the swap fee goes to the protocol
+  const feeTimelock = new anchor.BN(0); // Fee updates apply immediately
+  const fixedFee = 0; // min_fee and max_fee of 0 keep the swap fee fixed
+  const constantProduct = { constantProduct: {} };
+  const feeOnInput = { input: {} }; // Fee comes off the input before the swap
+  const noReferralFee = 0; // Referrers get no share of the swap fee
+  const pauseSwaps = 1; // PAUSE_SWAPS bit of Config::paused
+  const pauseAll = 7; // PAUSE_SWAPS | PAUSE_DEPOSITS | PAUSE_WITHDRAWALS
 
   let mintX: PublicKey;
   let mintY: PublicKey;
@@ -35,7 +46,75 @@ describe("anchor-amm-q4-25", () => {
   // Amounts
   const initialDepositX = 1_000_000_000; // 1000 tokens (6 decimals)
   const initialDepositY = 1_000_000_000;
-  const lpAmount = new anchor.BN(1_000_000); // LP tokens to claim on first deposit
+  const lpAmount = new anchor.BN(1_000_000); // Minimum LP tokens to accept on first deposit
+  const minimumLiquidity = 1_000; // LP tokens locked forever on first deposit
+
+  // LP token metadata, named after the pair. Mints without token metadata of their own
+  // go by the first 4 characters of their address
+  const lpNameOf = (mintX: PublicKey, mintY: PublicKey) =>
+    `${mintX.toBase58().slice(0, 4)}-${mintY.toBase58().slice(0, 4)} LP`;
+  const lpSymbol = "LP";
+
+  // Registry entry for a mint pair, fee tier and curve (0 = constant product,
+  // 1 = StableSwap), with the mints in sorted order
+  const poolEntryPda = (
+    mintA: PublicKey,
+    mintB: PublicKey,
+    feeTier: number,
+    curveType = 0
+  ) => {
+    const [lo, hi] =
+      Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) <= 0
+        ? [mintA, mintB]
+        : [mintB, mintA];
+    const feeBytes = Buffer.alloc(2);
+    feeBytes.writeUInt16LE(feeTier);
+    return PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool"),
+        lo.toBuffer(),
+        hi.toBuffer(),
+        feeBytes,
+        Buffer.from([curveType]),
+      ],
+      program.programId
+    )[0];
+  };
+
+  // Name and symbol of an LP mint's token metadata, which pads both with zeros
+  const tokenMetadataProgram = new PublicKey(
+    "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+  );
+  const lpMetadataOf = async (mintLp: PublicKey) => {
+    const [metadata] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        tokenMetadataProgram.toBuffer(),
+        mintLp.toBuffer(),
+      ],
+      tokenMetadataProgram
+    );
+    const data = (await provider.connection.getAccountInfo(metadata)).data;
+    // key, update authority and mint, then the borsh strings
+    const readString = (offset: number) => {
+      const len = data.readUInt32LE(offset);
+      const text = data.subarray(offset + 4, offset + 4 + len).toString();
+      return { text: text.replace(/\0+$/, ""), end: offset + 4 + len };
+    };
+    const name = readString(65);
+    const symbol = readString(name.end);
+    return { name: name.text, symbol: symbol.text };
+  };
+
+  // Decodes the program events emitted by a confirmed transaction
+  const eventsOf = async (signature: string) => {
+    const txInfo = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    return Array.from(parser.parseLogs(txInfo.meta.logMessages));
+  };
 
   it("Initializes the pool", async () => {
     // Create two token mints
@@ -68,10 +147,25 @@ describe("anchor-amm-q4-25", () => {
     );
 
     const tx = await program.methods
-      .initialize(seed, fee, provider.wallet.publicKey)
+      .initialize(
+        seed,
+        fee,
+        fixedFee,
+        fixedFee,
+        protocolFee,
+        feeTimelock,
+        constantProduct,
+        new anchor.BN(0),
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
       .accounts({
         mintX,
         mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
     console.log("Initialize tx:", tx);
@@ -80,12 +174,33 @@ describe("anchor-amm-q4-25", () => {
     const config = await program.account.config.fetch(configPda);
     assert.equal(config.seed.toNumber(), seed.toNumber());
     assert.equal(config.fee, 30);
-    assert.isFalse(config.locked);
+    assert.equal(config.protocolFee, protocolFee);
+    assert.equal(config.paused, 0);
+    assert.equal(config.version, 1);
+    assert.equal(config.lpDecimals, 6);
+    assert.equal(config.precision, 15);
+    assert.deepEqual(config.feeSide, feeOnInput);
+    assert.equal(config.referralFee, noReferralFee);
     assert.deepEqual(config.mintX, mintX);
     assert.deepEqual(config.mintY, mintY);
     assert.deepEqual(config.authority, provider.wallet.publicKey);
   });
 
+  it("Only registers pools without an authority", async () => {
+    try {
+      await program.methods
+        .registerPool()
+        .accountsPartial({
+          config: configPda,
+          poolEntry: poolEntryPda(mintX, mintY, fee),
+        })
+        .rpc();
+      assert.fail("register_pool should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "AuthorityNotRenounced");
+    }
+  });
+
   it("Deposits initial liquidity", async () => {
     // Create user ATAs and mint tokens to them
     const userAtaXAccount = await getOrCreateAssociatedTokenAccount(
@@ -138,8 +253,11 @@ describe("anchor-amm-q4-25", () => {
         mintX,
         mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .rpc();
+      .rpc({ commitment: "confirmed" });
     console.log("Deposit tx:", tx);
 
     // Verify vaults received tokens
@@ -180,7 +298,27 @@ describe("anchor-amm-q4-25", () => {
       )
     ).address;
     const userLpAccount = await getAccount(provider.connection, userLpAta);
-    assert.equal(Number(userLpAccount.amount), lpAmount.toNumber());
+    // First deposit mints sqrt(x * y), minus the permanently locked minimum liquidity
+    const expectedLp =
+      Math.floor(Math.sqrt(initialDepositX * initialDepositY)) -
+      minimumLiquidity;
+    assert.equal(Number(userLpAccount.amount), expectedLp);
+
+    const lockedLp = await getAccount(
+      provider.connection,
+      getAssociatedTokenAddressSync(mintLpPda, SystemProgram.programId, true)
+    );
+    assert.equal(Number(lockedLp.amount), minimumLiquidity);
+
+    // The deposit is reported with the resulting reserves and LP supply
+    const [event] = await eventsOf(tx);
+    assert.equal(event.name, "liquidityDeposited");
+    assert.equal(event.data.amountX.toNumber(), initialDepositX);
+    assert.equal(event.data.amountY.toNumber(), initialDepositY);
+    assert.equal(event.data.lpAmount.toNumber(), expectedLp);
+    assert.equal(event.data.reserveX.toNumber(), initialDepositX);
+    assert.equal(event.data.reserveY.toNumber(), initialDepositY);
+    assert.equal(event.data.lpSupply.toNumber(), expectedLp + minimumLiquidity);
 
     // Verify user token balances decreased
     const postUserX = await getAccount(provider.connection, userAtaX);
@@ -217,8 +355,10 @@ describe("anchor-amm-q4-25", () => {
         mintX,
         mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
       })
-      .rpc();
+      .rpc({ commitment: "confirmed" });
     console.log("Swap X->Y tx:", tx);
 
     const postUserX = await getAccount(provider.connection, userAtaX);
@@ -235,6 +375,27 @@ describe("anchor-amm-q4-25", () => {
     // Vault Y decreased
     assert.isTrue(Number(postVaultY.amount) < Number(preVaultY.amount));
 
+    // The swap is reported with the reserves it left behind
+    const [event] = await eventsOf(tx);
+    assert.equal(event.name, "swapped");
+    assert.deepEqual(event.data.config, configPda);
+    assert.isTrue(event.data.isX);
+    assert.equal(event.data.amountIn.toNumber(), swapAmountIn);
+    assert.equal(
+      event.data.amountOut.toNumber(),
+      Number(postUserY.amount) - Number(preUserY.amount)
+    );
+    assert.isTrue(event.data.fee.toNumber() > 0);
+    const config = await program.account.config.fetch(configPda);
+    assert.equal(
+      event.data.reserveX.toNumber(),
+      Number(postVaultX.amount) - config.protocolFeesX.toNumber()
+    );
+    assert.equal(
+      event.data.reserveY.toNumber(),
+      Number(postVaultY.amount) - config.protocolFeesY.toNumber()
+    );
+
     console.log(
       `  Swapped ${swapAmountIn} X -> ${Number(postUserY.amount) - Number(preUserY.amount)} Y`
     );
@@ -266,6 +427,8 @@ describe("anchor-amm-q4-25", () => {
         mintX,
         mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
       })
       .rpc();
     console.log("Swap Y->X tx:", tx);
@@ -283,6 +446,139 @@ describe("anchor-amm-q4-25", () => {
     );
   });
 
+  it("Swaps Y for an exact amount of X", async () => {
+    const amountOut = 10_000_000; // 10 tokens
+    const maxAmountIn = 20_000_000;
+    await mintTo(
+      provider.connection,
+      payer,
+      mintY,
+      userAtaY,
+      payer,
+      maxAmountIn
+    );
+
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    const preUserY = await getAccount(provider.connection, userAtaY);
+
+    const tx = await program.methods
+      .swapExactOut(
+        false, // is_x: paying with Y
+        new anchor.BN(amountOut),
+        new anchor.BN(maxAmountIn),
+        expiration
+      )
+      .accountsPartial({
+        mintX,
+        mintY,
+        config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    console.log("Swap exact out tx:", tx);
+
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const postUserY = await getAccount(provider.connection, userAtaY);
+
+    // User received at least the requested X without paying more than the cap
+    assert.isTrue(
+      Number(postUserX.amount) - Number(preUserX.amount) >= amountOut
+    );
+    const paid = Number(preUserY.amount) - Number(postUserY.amount);
+    assert.isTrue(paid > 0 && paid <= maxAmountIn);
+  });
+
+  it("Rejects an exact-out swap above max_amount_in", async () => {
+    try {
+      await program.methods
+        .swapExactOut(
+          false,
+          new anchor.BN(10_000_000),
+          new anchor.BN(1),
+          expiration
+        )
+        .accountsPartial({
+          mintX,
+          mintY,
+          config: configPda,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.fail("swap should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "SlippageExceeded");
+    }
+  });
+
+  it("Collects accrued protocol fees", async () => {
+    const preConfig = await program.account.config.fetch(configPda);
+    assert.isTrue(preConfig.protocolFeesX.toNumber() > 0);
+    assert.isTrue(preConfig.protocolFeesY.toNumber() > 0);
+
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    const preVaultY = await getAccount(provider.connection, vaultY);
+
+    const tx = await program.methods
+      .collectProtocolFees()
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        mintX,
+        mintY,
+        config: configPda,
+        treasuryX: userAtaX,
+        treasuryY: userAtaY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    console.log("Collect protocol fees tx:", tx);
+
+    const postConfig = await program.account.config.fetch(configPda);
+    assert.equal(postConfig.protocolFeesX.toNumber(), 0);
+    assert.equal(postConfig.protocolFeesY.toNumber(), 0);
+
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const postVaultY = await getAccount(provider.connection, vaultY);
+    assert.equal(
+      Number(postUserX.amount) - Number(preUserX.amount),
+      preConfig.protocolFeesX.toNumber()
+    );
+    assert.equal(
+      Number(preVaultY.amount) - Number(postVaultY.amount),
+      preConfig.protocolFeesY.toNumber()
+    );
+  });
+
+  it("Reads a TWAP from the pool oracle", async () => {
+    // Make sure at least one second has passed since the first observation
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    const twap = await program.methods
+      .getTwap(1)
+      .accountsPartial({
+        mintX,
+        mintY,
+        config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      .view();
+
+    assert.isTrue(twap.window >= 1);
+    assert.isTrue(twap.priceX.gtn(0));
+    assert.isTrue(twap.priceY.gtn(0));
+
+    const [oraclePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("oracle"), configPda.toBuffer()],
+      program.programId
+    );
+    const oracle = await program.account.oracle.fetch(oraclePda);
+    assert.isTrue(oracle.len >= 1);
+    assert.deepEqual(oracle.config, configPda);
+  });
+
   it("Deposits additional liquidity into existing pool", async () => {
     // Mint more tokens for the second deposit (generous amounts for slippage after swaps)
     const additionalX = 1_000_000_000;
@@ -306,6 +602,9 @@ describe("anchor-amm-q4-25", () => {
         mintX,
         mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
     console.log("Second deposit tx:", tx);
@@ -318,6 +617,52 @@ describe("anchor-amm-q4-25", () => {
     assert.isTrue(Number(postVaultY.amount) > Number(preVaultY.amount));
   });
 
+  it("Deposits only X via deposit_single", async () => {
+    const amountIn = 50_000_000;
+    await mintTo(provider.connection, payer, mintX, userAtaX, payer, amountIn);
+
+    const userLpAta = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintLpPda,
+        provider.wallet.publicKey,
+        false
+      )
+    ).address;
+
+    const preLp = await getAccount(provider.connection, userLpAta);
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    const preUserY = await getAccount(provider.connection, userAtaY);
+
+    const tx = await program.methods
+      .depositSingle(
+        true, // is_x: deposit X only
+        new anchor.BN(amountIn),
+        new anchor.BN(1), // min_lp_out
+        expiration
+      )
+      .accountsPartial({
+        mintX,
+        mintY,
+        config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    console.log("Deposit single tx:", tx);
+
+    const postLp = await getAccount(provider.connection, userLpAta);
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const postUserY = await getAccount(provider.connection, userAtaY);
+
+    assert.isTrue(Number(postLp.amount) > Number(preLp.amount));
+    assert.equal(Number(preUserX.amount) - Number(postUserX.amount), amountIn);
+    // Y is never touched on the user side
+    assert.equal(Number(postUserY.amount), Number(preUserY.amount));
+  });
+
   it("Withdraws liquidity", async () => {
     const userLpAta = (
       await getOrCreateAssociatedTokenAccount(
@@ -349,6 +694,9 @@ describe("anchor-amm-q4-25", () => {
         mintX,
         mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
     console.log("Withdraw tx:", tx);
@@ -371,31 +719,1417 @@ describe("anchor-amm-q4-25", () => {
     );
   });
 
-  it("Locks the pool via update", async () => {
+  it("Withdraws only Y via withdraw_single", async () => {
+    const userLpAta = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintLpPda,
+        provider.wallet.publicKey,
+        false
+      )
+    ).address;
+
+    const preLp = await getAccount(provider.connection, userLpAta);
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    const preUserY = await getAccount(provider.connection, userAtaY);
+
+    const burnAmount = new anchor.BN(Math.floor(Number(preLp.amount) / 10));
+
     const tx = await program.methods
-      .update(true)
+      .withdrawSingle(
+        false, // is_x: receive Y only
+        burnAmount,
+        new anchor.BN(1), // min_out
+        expiration
+      )
       .accountsPartial({
-        authority: provider.wallet.publicKey,
+        mintX,
+        mintY,
         config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
-    console.log("Lock pool tx:", tx);
+    console.log("Withdraw single tx:", tx);
 
-    const config = await program.account.config.fetch(configPda);
-    assert.isTrue(config.locked);
+    const postLp = await getAccount(provider.connection, userLpAta);
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const postUserY = await getAccount(provider.connection, userAtaY);
+
+    assert.equal(
+      Number(preLp.amount) - Number(postLp.amount),
+      burnAmount.toNumber()
+    );
+    assert.equal(Number(postUserX.amount), Number(preUserX.amount));
+    assert.isTrue(Number(postUserY.amount) > Number(preUserY.amount));
   });
 
-  it("Unlocks the pool via update", async () => {
+  it("Routes a swap X -> Y -> Z across two pools", async () => {
+    const seedYZ = new anchor.BN(Math.floor(Math.random() * 1_000_000));
+    const mintZ = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const [configYZ] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seedYZ.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .initialize(
+        seedYZ,
+        fee,
+        fixedFee,
+        fixedFee,
+        protocolFee,
+        feeTimelock,
+        constantProduct,
+        new anchor.BN(0),
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
+      .accounts({
+        mintX: mintY,
+        mintY: mintZ,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const userAtaZ = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintZ,
+        provider.wallet.publicKey
+      )
+    ).address;
+    await mintTo(provider.connection, payer, mintY, userAtaY, payer, 500_000_000);
+    await mintTo(provider.connection, payer, mintZ, userAtaZ, payer, 500_000_000);
+    await mintTo(provider.connection, payer, mintX, userAtaX, payer, 10_000_000);
+
+    await program.methods
+      .deposit(
+        lpAmount,
+        new anchor.BN(500_000_000),
+        new anchor.BN(500_000_000),
+        expiration
+      )
+      .accountsPartial({
+        mintX: mintY,
+        mintY: mintZ,
+        config: configYZ,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const vaultYZ_Y = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintY,
+        configYZ,
+        true
+      )
+    ).address;
+    const vaultYZ_Z = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintZ,
+        configYZ,
+        true
+      )
+    ).address;
+
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    const preUserY = await getAccount(provider.connection, userAtaY);
+    const preUserZ = await getAccount(provider.connection, userAtaZ);
+
+    const hop = (
+      config: PublicKey,
+      mx: PublicKey,
+      my: PublicKey,
+      vx: PublicKey,
+      vy: PublicKey
+    ) => [
+      { pubkey: config, isSigner: false, isWritable: true },
+      {
+        pubkey: PublicKey.findProgramAddressSync(
+          [Buffer.from("oracle"), config.toBuffer()],
+          program.programId
+        )[0],
+        isSigner: false,
+        isWritable: true,
+      },
+      { pubkey: mx, isSigner: false, isWritable: false },
+      { pubkey: my, isSigner: false, isWritable: false },
+      { pubkey: vx, isSigner: false, isWritable: true },
+      { pubkey: vy, isSigner: false, isWritable: true },
+    ];
+
+    const tx = await program.methods
+      .routeSwap(new anchor.BN(10_000_000), new anchor.BN(1), expiration)
+      .accountsPartial({
+        mintIn: mintX,
+        mintOut: mintZ,
+        tokenProgramIn: TOKEN_PROGRAM_ID,
+        tokenProgramOut: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        ...hop(configPda, mintX, mintY, vaultX, vaultY),
+        ...hop(configYZ, mintY, mintZ, vaultYZ_Y, vaultYZ_Z),
+      ])
+      .rpc();
+    console.log("Route swap tx:", tx);
+
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const postUserY = await getAccount(provider.connection, userAtaY);
+    const postUserZ = await getAccount(provider.connection, userAtaZ);
+
+    assert.equal(
+      Number(preUserX.amount) - Number(postUserX.amount),
+      10_000_000
+    );
+    // The intermediate token never touches the user's account
+    assert.equal(Number(postUserY.amount), Number(preUserY.amount));
+    assert.isTrue(Number(postUserZ.amount) > Number(preUserZ.amount));
+  });
+
+  it("Takes and repays a flash loan in one transaction", async () => {
+    const flashAccounts = {
+      borrower: provider.wallet.publicKey,
+      mintX,
+      mintY,
+      config: configPda,
+      tokenProgramX: TOKEN_PROGRAM_ID,
+      tokenProgramY: TOKEN_PROGRAM_ID,
+    };
+    const amount = 100_000_000;
+
+    // Borrowing without repaying later in the transaction is rejected
+    try {
+      await program.methods
+        .flashLoan(true, new anchor.BN(amount))
+        .accountsPartial(flashAccounts)
+        .rpc();
+      assert.fail("flash_loan should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FlashLoanNotRepaid");
+    }
+
+    const preConfig = await program.account.config.fetch(configPda);
+    const preVaultX = await getAccount(provider.connection, vaultX);
+    const expectedFee = Math.ceil((amount * preConfig.fee) / 10_000);
+
+    const repay = await program.methods
+      .repayFlashLoan()
+      .accountsPartial(flashAccounts)
+      .instruction();
+    const tx = await program.methods
+      .flashLoan(true, new anchor.BN(amount))
+      .accountsPartial(flashAccounts)
+      .postInstructions([repay])
+      .rpc({ commitment: "confirmed" });
+    console.log("Flash loan tx:", tx);
+
+    const events = await eventsOf(tx);
+    const borrowed = events.find((event) => event.name === "flashLoanBorrowed");
+    assert.equal(borrowed.data.amount.toNumber(), amount);
+    assert.equal(borrowed.data.fee.toNumber(), expectedFee);
+    assert.isDefined(events.find((event) => event.name === "flashLoanRepaid"));
+
+    // The fee stays in the vault as LP reserves and the pool is open again
+    const postConfig = await program.account.config.fetch(configPda);
+    const postVaultX = await getAccount(provider.connection, vaultX);
+    assert.equal(
+      Number(postVaultX.amount) - Number(preVaultX.amount),
+      expectedFee
+    );
+    assert.equal(
+      postConfig.protocolFeesX.toNumber(),
+      preConfig.protocolFeesX.toNumber()
+    );
+    assert.equal(postConfig.flashLoanAmount.toNumber(), 0);
+  });
+
+  it("Stakes LP tokens in a farm and harvests rewards", async () => {
+    const rewardMint = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const authorityReward = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      rewardMint,
+      provider.wallet.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      payer,
+      rewardMint,
+      authorityReward.address,
+      payer,
+      1_000_000_000
+    );
+
+    const [farmPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("farm"), configPda.toBuffer(), rewardMint.toBuffer()],
+      program.programId
+    );
+    const rewardPerSecond = 1_000;
+    const startTime = Math.floor(Date.now() / 1000) + 2;
+    const endTime = startTime + 100_000;
+
+    // A schedule ending before it starts is rejected
+    try {
+      await program.methods
+        .createFarm(
+          new anchor.BN(rewardPerSecond),
+          new anchor.BN(endTime),
+          new anchor.BN(startTime)
+        )
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+          rewardMint,
+          authorityReward: authorityReward.address,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.fail("create_farm should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTimestamp");
+    }
+
     const tx = await program.methods
-      .update(false)
+      .createFarm(
+        new anchor.BN(rewardPerSecond),
+        new anchor.BN(startTime),
+        new anchor.BN(endTime)
+      )
       .accountsPartial({
         authority: provider.wallet.publicKey,
         config: configPda,
+        rewardMint,
+        authorityReward: authorityReward.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc();
-    console.log("Unlock pool tx:", tx);
+    console.log("Create farm tx:", tx);
 
-    const config = await program.account.config.fetch(configPda);
-    assert.isFalse(config.locked);
+    // The whole schedule is funded up front
+    const rewardVault = getAssociatedTokenAddressSync(rewardMint, farmPda, true);
+    assert.equal(
+      Number((await getAccount(provider.connection, rewardVault)).amount),
+      rewardPerSecond * (endTime - startTime)
+    );
+
+    const stakeAccounts = {
+      owner: provider.wallet.publicKey,
+      config: configPda,
+      rewardMint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const userLp = getAssociatedTokenAddressSync(
+      mintLpPda,
+      provider.wallet.publicKey
+    );
+    const preUserLp = await getAccount(provider.connection, userLp);
+    const stakeAmount = Number(preUserLp.amount) / 2;
+
+    await program.methods
+      .stakeLp(new anchor.BN(stakeAmount))
+      .accountsPartial(stakeAccounts)
+      .rpc();
+    const farm = await program.account.farm.fetch(farmPda);
+    assert.equal(farm.totalStaked.toNumber(), stakeAmount);
+
+    // Let some rewards accrue past the start
+    await new Promise((resolve) => setTimeout(resolve, 5_000));
+
+    const harvest = await program.methods
+      .harvest()
+      .accountsPartial(stakeAccounts)
+      .rpc({ commitment: "confirmed" });
+    const harvested = (await eventsOf(harvest)).find(
+      (event) => event.name === "rewardsHarvested"
+    );
+    // The only staker gets every reward emitted so far
+    assert.isTrue(harvested.data.amount.toNumber() > 0);
+    const userReward = getAssociatedTokenAddressSync(
+      rewardMint,
+      provider.wallet.publicKey
+    );
+    assert.equal(
+      Number((await getAccount(provider.connection, userReward)).amount),
+      harvested.data.amount.toNumber()
+    );
+
+    // Unstaking more than the stake fails, the full stake comes back
+    try {
+      await program.methods
+        .unstakeLp(new anchor.BN(stakeAmount + 1))
+        .accountsPartial(stakeAccounts)
+        .rpc();
+      assert.fail("unstake_lp should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InsufficientBalance");
+    }
+    await program.methods
+      .unstakeLp(new anchor.BN(stakeAmount))
+      .accountsPartial(stakeAccounts)
+      .rpc();
+    const postUserLp = await getAccount(provider.connection, userLp);
+    assert.equal(Number(postUserLp.amount), Number(preUserLp.amount));
+
+    // Once the rewards earned are harvested nothing is owed, so the farm can be
+    // closed and the rest of the schedule refunded
+    await program.methods.harvest().accountsPartial(stakeAccounts).rpc();
+    assert.equal(
+      (await program.account.farm.fetch(farmPda)).rewardsOwed.toNumber(),
+      0
+    );
+    await program.methods
+      .closeFarm()
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+        rewardMint,
+        authorityReward: authorityReward.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    assert.isNull(await provider.connection.getAccountInfo(farmPda));
+    assert.isNull(await provider.connection.getAccountInfo(rewardVault));
+    assert.equal((await program.account.config.fetch(configPda)).farms, 0);
+  });
+
+  it("Pauses swaps only via update", async () => {
+    const tx = await program.methods
+      .update(pauseSwaps)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+    console.log("Pause swaps tx:", tx);
+
+    try {
+      await program.methods
+        .swap(true, new anchor.BN(1_000), new anchor.BN(1), expiration)
+        .accountsPartial({
+          mintX,
+          mintY,
+          config: configPda,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.fail("swap should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "PoolLocked");
+    }
+
+    // LPs can still get out
+    await program.methods
+      .withdraw(
+        new anchor.BN(1_000),
+        new anchor.BN(0),
+        new anchor.BN(0),
+        expiration
+      )
+      .accountsPartial({
+        mintX,
+        mintY,
+        config: configPda,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    // Only the known bits can be set
+    try {
+      await program.methods
+        .update(8)
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+        })
+        .rpc();
+      assert.fail("update should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidPauseFlags");
+    }
+  });
+
+  it("Locks the pool via update", async () => {
+    const tx = await program.methods
+      .update(pauseAll)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+    console.log("Lock pool tx:", tx);
+
+    const config = await program.account.config.fetch(configPda);
+    assert.equal(config.paused, pauseAll);
+  });
+
+  it("Unlocks the pool via update", async () => {
+    const tx = await program.methods
+      .update(0)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+    console.log("Unlock pool tx:", tx);
+
+    const config = await program.account.config.fetch(configPda);
+    assert.equal(config.paused, 0);
+  });
+
+  it("Caps trade size and trips the circuit breaker", async () => {
+    const authorityAccounts = {
+      authority: provider.wallet.publicKey,
+      config: configPda,
+    };
+    const swapAccounts = {
+      mintX,
+      mintY,
+      config: configPda,
+      tokenProgramX: TOKEN_PROGRAM_ID,
+      tokenProgramY: TOKEN_PROGRAM_ID,
+    };
+    const swapX = (amount: number) =>
+      program.methods
+        .swap(true, new anchor.BN(amount), new anchor.BN(1), expiration)
+        .accountsPartial(swapAccounts)
+        .rpc({ commitment: "confirmed" });
+
+    const config = await program.account.config.fetch(configPda);
+    const reserveX =
+      Number((await getAccount(provider.connection, vaultX)).amount) -
+      config.protocolFeesX.toNumber();
+    const amount = Math.floor(reserveX / 50); // 2% of the reserve
+    await mintTo(provider.connection, payer, mintX, userAtaX, payer, 3 * amount);
+
+    // Swaps above 1% of the reserve are rejected
+    await program.methods
+      .updateTradeLimits(100, 0, new anchor.BN(0))
+      .accountsPartial(authorityAccounts)
+      .rpc();
+    try {
+      await swapX(amount);
+      assert.fail("swap should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "TradeTooLarge");
+    }
+
+    // A breaker without a window is invalid
+    try {
+      await program.methods
+        .updateTradeLimits(0, 100, new anchor.BN(0))
+        .accountsPartial(authorityAccounts)
+        .rpc();
+      assert.fail("update_trade_limits should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTradeLimits");
+    }
+
+    // A ~4% price move trips a 1% breaker; that swap still goes through
+    await program.methods
+      .updateTradeLimits(0, 100, new anchor.BN(600))
+      .accountsPartial(authorityAccounts)
+      .rpc();
+    const tx = await swapX(amount);
+    const tripped = (await eventsOf(tx)).find(
+      (event) => event.name === "circuitBreakerTripped"
+    );
+    assert.isTrue(tripped.data.priceMovement.toNumber() > 100);
+    assert.isTrue(
+      (await program.account.config.fetch(configPda)).breakerTripped
+    );
+
+    // Further swaps are halted until the authority resets the breaker
+    try {
+      await swapX(amount);
+      assert.fail("swap should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "CircuitBreakerTripped");
+    }
+    try {
+      await program.methods
+        .renounceAuthority()
+        .accountsPartial(authorityAccounts)
+        .rpc();
+      assert.fail("renounce_authority should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTradeLimits");
+    }
+
+    await program.methods
+      .resetCircuitBreaker()
+      .accountsPartial(authorityAccounts)
+      .rpc();
+    await program.methods
+      .updateTradeLimits(0, 0, new anchor.BN(0))
+      .accountsPartial(authorityAccounts)
+      .rpc();
+    await swapX(amount);
+    assert.isFalse(
+      (await program.account.config.fetch(configPda)).breakerTripped
+    );
+  });
+
+  it("Updates the fee immediately without a timelock", async () => {
+    await program.methods
+      .updateFee(25)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+
+    const config = await program.account.config.fetch(configPda);
+    assert.equal(config.fee, 25);
+    assert.isNull(config.pendingFee);
+  });
+
+  it("Rejects a fee above the maximum", async () => {
+    try {
+      await program.methods
+        .updateFee(1_001)
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+        })
+        .rpc();
+      assert.fail("fee update should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidFee");
+    }
+  });
+
+  it("Hands authority over in two steps", async () => {
+    const newAuthority = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .proposeAuthority(newAuthority.publicKey)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+
+    let config = await program.account.config.fetch(configPda);
+    assert.deepEqual(config.authority, provider.wallet.publicKey);
+    assert.deepEqual(config.pendingAuthority, newAuthority.publicKey);
+
+    await program.methods
+      .acceptAuthority()
+      .accountsPartial({
+        newAuthority: newAuthority.publicKey,
+        config: configPda,
+      })
+      .signers([newAuthority])
+      .rpc();
+
+    config = await program.account.config.fetch(configPda);
+    assert.deepEqual(config.authority, newAuthority.publicKey);
+    assert.isNull(config.pendingAuthority);
+
+    // Hand it back so the wallet stays in control of the pool
+    await program.methods
+      .proposeAuthority(provider.wallet.publicKey)
+      .accountsPartial({
+        authority: newAuthority.publicKey,
+        config: configPda,
+      })
+      .signers([newAuthority])
+      .rpc();
+    await program.methods
+      .acceptAuthority()
+      .accountsPartial({
+        newAuthority: provider.wallet.publicKey,
+        config: configPda,
+      })
+      .rpc();
+
+    config = await program.account.config.fetch(configPda);
+    assert.deepEqual(config.authority, provider.wallet.publicKey);
+  });
+
+  it("Queues a fee update behind the timelock and renounces", async () => {
+    const seedTimelock = new anchor.BN(Math.floor(Math.random() * 1_000_000));
+    const timelockFee = 100; // Separate fee tier, X/Y at 30 bps is already registered
+    const [configTimelock] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seedTimelock.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .initialize(
+        seedTimelock,
+        timelockFee,
+        fixedFee,
+        fixedFee,
+        protocolFee,
+        new anchor.BN(3_600),
+        constantProduct,
+        new anchor.BN(0),
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
+      .accounts({
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    await program.methods
+      .updateFee(50)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configTimelock,
+      })
+      .rpc();
+
+    let config = await program.account.config.fetch(configTimelock);
+    assert.equal(config.fee, timelockFee);
+    assert.equal(config.pendingFee, 50);
+    assert.isTrue(config.pendingFeeEta.toNumber() > 0);
+
+    try {
+      await program.methods
+        .applyFee()
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configTimelock,
+        })
+        .rpc();
+      assert.fail("apply_fee should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "TimelockNotExpired");
+    }
+
+    await program.methods
+      .renounceAuthority()
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configTimelock,
+      })
+      .rpc();
+
+    config = await program.account.config.fetch(configTimelock);
+    assert.isNull(config.authority);
+    assert.isNull(config.pendingFee);
+
+    // Nobody controls it any more, so it can be registered for its pair
+    await program.methods
+      .registerPool()
+      .accountsPartial({
+        config: configTimelock,
+        poolEntry: poolEntryPda(mintX, mintY, timelockFee),
+      })
+      .rpc();
+    const entry = await program.account.poolEntry.fetch(
+      poolEntryPda(mintY, mintX, timelockFee)
+    );
+    assert.deepEqual(entry.config, configTimelock);
+    assert.equal(entry.fee, timelockFee);
+
+    try {
+      await program.methods
+        .update(pauseAll)
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configTimelock,
+        })
+        .rpc();
+      assert.fail("update should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "NoAuthoritySet");
+    }
+  });
+
+  it("Rejects an amplification ramp on a constant product pool", async () => {
+    try {
+      await program.methods
+        .rampAmp(new anchor.BN(100), expiration)
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+        })
+        .rpc();
+      assert.fail("ramp_amp should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidCurve");
+    }
+  });
+
+  it("Creates and trades a StableSwap pool", async () => {
+    const seedStable = new anchor.BN(Math.floor(Math.random() * 1_000_000));
+    const amp = new anchor.BN(100);
+
+    const mintA = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const mintB = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const [configStable] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seedStable.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .initialize(
+        seedStable,
+        fee,
+        fixedFee,
+        fixedFee,
+        protocolFee,
+        feeTimelock,
+        { stableSwap: {} },
+        amp,
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
+      .accounts({
+        mintX: mintA,
+        mintY: mintB,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    let config = await program.account.config.fetch(configStable);
+    assert.deepEqual(config.curveType, { stableSwap: {} });
+    assert.equal(config.ampTarget.toNumber(), amp.toNumber());
+
+    const userA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintA,
+        provider.wallet.publicKey
+      )
+    ).address;
+    const userB = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintB,
+        provider.wallet.publicKey
+      )
+    ).address;
+    await mintTo(provider.connection, payer, mintA, userA, payer, 2_000_000_000);
+    await mintTo(provider.connection, payer, mintB, userB, payer, 1_000_000_000);
+
+    await program.methods
+      .deposit(
+        lpAmount,
+        new anchor.BN(initialDepositX),
+        new anchor.BN(initialDepositY),
+        expiration
+      )
+      .accountsPartial({
+        mintX: mintA,
+        mintY: mintB,
+        config: configStable,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    // A 10% trade against a balanced pool stays close to the peg, where a constant
+    // product pool would pay out less than 91%
+    const swapAmountIn = 100_000_000;
+    const preUserB = await getAccount(provider.connection, userB);
+    await program.methods
+      .swap(true, new anchor.BN(swapAmountIn), new anchor.BN(1), expiration)
+      .accountsPartial({
+        mintX: mintA,
+        mintY: mintB,
+        config: configStable,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    const postUserB = await getAccount(provider.connection, userB);
+    const amountOut = Number(postUserB.amount) - Number(preUserB.amount);
+    assert.isTrue(amountOut > swapAmountIn * 0.99);
+    assert.isTrue(amountOut < swapAmountIn);
+
+    // Ramps have to be slow enough for LPs to react
+    const now = Math.floor(Date.now() / 1000);
+    try {
+      await program.methods
+        .rampAmp(new anchor.BN(200), new anchor.BN(now + 60))
+        .accountsPartial({
+          authority: provider.wallet.publicKey,
+          config: configStable,
+        })
+        .rpc();
+      assert.fail("ramp_amp should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidRamp");
+    }
+
+    await program.methods
+      .rampAmp(new anchor.BN(200), new anchor.BN(now + 2 * 86_400))
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configStable,
+      })
+      .rpc();
+    config = await program.account.config.fetch(configStable);
+    assert.equal(config.ampInitial.toNumber(), amp.toNumber());
+    assert.equal(config.ampTarget.toNumber(), 200);
+
+    await program.methods
+      .stopRampAmp()
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        config: configStable,
+      })
+      .rpc();
+    config = await program.account.config.fetch(configStable);
+    assert.equal(config.ampTarget.toNumber(), config.ampInitial.toNumber());
+  });
+
+  it("Provides concentrated liquidity over a tick range", async () => {
+    const [concentratedPool] = PublicKey.findProgramAddressSync(
+      [Buffer.from("concentrated"), configPda.toBuffer()],
+      program.programId
+    );
+    // Concentrated liquidity has to start at the price of the pool's reserves
+    let config = await program.account.config.fetch(configPda);
+    const reserveX =
+      Number((await getAccount(provider.connection, vaultX)).amount) -
+      config.protocolFeesX.toNumber();
+    const reserveY =
+      Number((await getAccount(provider.connection, vaultY)).amount) -
+      config.protocolFeesY.toNumber();
+    const price = reserveY / reserveX;
+    const startTick = Math.floor(Math.log(price) / Math.log(1.0001));
+    const tickLower = Math.round(startTick / 10) * 10 - 100;
+    const tickUpper = tickLower + 200;
+    const tickBytes = (tick: number) => {
+      const bytes = Buffer.alloc(4);
+      bytes.writeInt32LE(tick);
+      return bytes;
+    };
+    const [position] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("position"),
+        concentratedPool.toBuffer(),
+        provider.wallet.publicKey.toBuffer(),
+        tickBytes(tickLower),
+        tickBytes(tickUpper),
+      ],
+      program.programId
+    );
+    // Tick arrays hold 32 ticks of 10 each. The swap needs the one holding the current
+    // tick and the one below it, the range's bounds may fall in others
+    const arrayStart = (tick: number) => Math.floor(tick / 320) * 320;
+    const tickArray = (arrayStartTick: number) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("tick_array"),
+          concentratedPool.toBuffer(),
+          tickBytes(arrayStartTick),
+        ],
+        program.programId
+      )[0];
+    const tickArrayLower = tickArray(arrayStart(tickLower));
+    const tickArrayUpper = tickArray(arrayStart(tickUpper));
+    const arrayStarts = [
+      ...new Set([
+        arrayStart(startTick),
+        arrayStart(startTick) - 320,
+        arrayStart(tickLower),
+        arrayStart(tickUpper),
+      ]),
+    ];
+    const modifyAccounts = {
+      owner: provider.wallet.publicKey,
+      mintX,
+      mintY,
+      config: configPda,
+      concentratedPool,
+      position,
+      tickArrayLower,
+      tickArrayUpper,
+      tokenProgramX: TOKEN_PROGRAM_ID,
+      tokenProgramY: TOKEN_PROGRAM_ID,
+    };
+
+    await program.methods
+      .initializeConcentrated(10, startTick)
+      .accountsPartial({
+        authority: provider.wallet.publicKey,
+        mintX,
+        mintY,
+        config: configPda,
+        concentratedPool,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    // Tick bounds must be multiples of the spacing
+    try {
+      await program.methods
+        .openPosition(-15, 100)
+        .accountsPartial({ config: configPda, concentratedPool })
+        .rpc();
+      assert.fail("open_position should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTick");
+    }
+
+    await program.methods
+      .openPosition(tickLower, tickUpper)
+      .accountsPartial({ config: configPda, concentratedPool, position })
+      .rpc();
+    for (const arrayStartTick of arrayStarts) {
+      await program.methods
+        .initializeTickArray(arrayStartTick)
+        .accountsPartial({
+          concentratedPool,
+          tickArray: tickArray(arrayStartTick),
+        })
+        .rpc();
+    }
+
+    await mintTo(provider.connection, payer, mintX, userAtaX, payer, 1_000_000_000);
+    await mintTo(provider.connection, payer, mintY, userAtaY, payer, 1_000_000_000);
+    const reservesBefore = await program.account.config.fetch(configPda);
+
+    const liquidity = new anchor.BN(10_000_000_000);
+    await program.methods
+      .increaseLiquidity(
+        liquidity,
+        new anchor.BN(100_000_000),
+        new anchor.BN(100_000_000),
+        expiration
+      )
+      .accountsPartial(modifyAccounts)
+      .rpc();
+
+    config = await program.account.config.fetch(configPda);
+    // ~L * (1 - 1.0001^-50) of each token, scaled by the square root of the price
+    assert.isTrue(
+      config.concentratedX.toNumber() > 45_000_000 / Math.sqrt(price)
+    );
+    assert.isTrue(
+      config.concentratedY.toNumber() > 45_000_000 * Math.sqrt(price)
+    );
+    assert.equal(
+      (await program.account.position.fetch(position)).liquidity.toString(),
+      liquidity.toString()
+    );
+
+    // Trade X for Y against the position only
+    const preUserY = await getAccount(provider.connection, userAtaY);
+    const tx = await program.methods
+      .swapConcentrated(
+        true,
+        new anchor.BN(1_000_000),
+        new anchor.BN(Math.floor(990_000 * price)),
+        expiration
+      )
+      .accountsPartial({
+        mintX,
+        mintY,
+        config: configPda,
+        concentratedPool,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+      })
+      // Selling X moves the price down, possibly into the array below
+      .remainingAccounts(
+        arrayStarts.map((arrayStartTick) => ({
+          pubkey: tickArray(arrayStartTick),
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .rpc({ commitment: "confirmed" });
+    const postUserY = await getAccount(provider.connection, userAtaY);
+    const amountOut = Number(postUserY.amount) - Number(preUserY.amount);
+    assert.isTrue(
+      amountOut >= Math.floor(990_000 * price) && amountOut < 1_000_000 * price
+    );
+
+    const [swapped] = (await eventsOf(tx)).filter(
+      (event) => event.name === "concentratedSwapped"
+    );
+    assert.equal(swapped.data.amountOut.toNumber(), amountOut);
+    const swapFee = swapped.data.fee.toNumber();
+    const swapProtocolFee = swapped.data.protocolFee.toNumber();
+    assert.isTrue(swapFee >= 3_000 && swapFee <= 3_001);
+    assert.equal(swapProtocolFee, Math.floor(swapFee / 10));
+    assert.isTrue(swapped.data.tick < startTick);
+
+    // The price stayed inside the range and the protocol cut was set aside
+    const pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.equal(pool.liquidity.toString(), liquidity.toString());
+    config = await program.account.config.fetch(configPda);
+    assert.equal(
+      config.protocolFeesX.toNumber(),
+      reservesBefore.protocolFeesX.toNumber() + swapProtocolFee
+    );
+
+    // The position earned the LP share of the fee, less rounding
+    const preUserX = await getAccount(provider.connection, userAtaX);
+    await program.methods
+      .collectFees()
+      .accountsPartial(modifyAccounts)
+      .rpc();
+    const postUserX = await getAccount(provider.connection, userAtaX);
+    const feesX = Number(postUserX.amount) - Number(preUserX.amount);
+    const lpFee = swapFee - swapProtocolFee;
+    assert.isTrue(feesX >= lpFee - 1 && feesX <= lpFee);
+
+    await program.methods
+      .decreaseLiquidity(
+        liquidity,
+        new anchor.BN(1),
+        new anchor.BN(1),
+        expiration
+      )
+      .accountsPartial(modifyAccounts)
+      .rpc();
+
+    // Only rounding dust is left behind in the concentrated reserves
+    config = await program.account.config.fetch(configPda);
+    assert.isTrue(config.concentratedX.toNumber() <= 2);
+    assert.isTrue(config.concentratedY.toNumber() <= 2);
+    assert.equal(
+      (await program.account.position.fetch(position)).liquidity.toNumber(),
+      0
+    );
+  });
+
+  it("Raises the fee of a dynamic fee pool after a large price move", async () => {
+    const seedDynamic = new anchor.BN(Math.floor(Math.random() * 1_000_000));
+    const minFee = 10;
+    const maxFee = 300;
+
+    const mintA = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const mintB = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6
+    );
+    const [configDynamic] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seedDynamic.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    // The base fee has to sit between the bounds
+    try {
+      await program.methods
+        .initialize(
+          seedDynamic,
+          fee,
+          fee + 1,
+          maxFee,
+          protocolFee,
+          feeTimelock,
+          constantProduct,
+          new anchor.BN(0),
+          feeOnInput,
+          noReferralFee,
+          provider.wallet.publicKey
+        )
+        .accounts({
+          mintX: mintA,
+          mintY: mintB,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.fail("initialize should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidFee");
+    }
+
+    await program.methods
+      .initialize(
+        seedDynamic,
+        fee,
+        minFee,
+        maxFee,
+        protocolFee,
+        feeTimelock,
+        constantProduct,
+        new anchor.BN(0),
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
+      .accounts({
+        mintX: mintA,
+        mintY: mintB,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const userA = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintA,
+        provider.wallet.publicKey
+      )
+    ).address;
+    const userB = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintB,
+        provider.wallet.publicKey
+      )
+    ).address;
+    await mintTo(provider.connection, payer, mintA, userA, payer, 2_000_000_000);
+    await mintTo(provider.connection, payer, mintB, userB, payer, 1_000_000_000);
+
+    await program.methods
+      .deposit(
+        lpAmount,
+        new anchor.BN(initialDepositX),
+        new anchor.BN(initialDepositY),
+        expiration
+      )
+      .accountsPartial({
+        mintX: mintA,
+        mintY: mintB,
+        config: configDynamic,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const swapFee = async () => {
+      const tx = await program.methods
+        .swap(true, new anchor.BN(100_000_000), new anchor.BN(1), expiration)
+        .accountsPartial({
+          mintX: mintA,
+          mintY: mintB,
+          config: configDynamic,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+      const [swapped] = (await eventsOf(tx)).filter(
+        (event) => event.name === "swapped"
+      );
+      return swapped.data.fee.toNumber();
+    };
+
+    // A calm pool charges the base fee
+    const calmFee = await swapFee();
+    assert.equal(calmFee, (100_000_000 * fee) / 10_000);
+
+    // That 10% trade moved the price by ~17%, which now costs ~170 bps on top
+    const config = await program.account.config.fetch(configDynamic);
+    assert.isTrue(config.volatility.toNumber() > 1_500);
+    const volatileFee = await swapFee();
+    assert.isTrue(volatileFee > calmFee * 5);
+    assert.isTrue(volatileFee <= (100_000_000 * maxFee) / 10_000);
+  });
+
+  it("Creates and trades a Token-2022 pool", async () => {
+    const seed2022 = new anchor.BN(Math.floor(Math.random() * 1_000_000));
+
+    const mintX2022 = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const mintY2022 = await createMint(
+      provider.connection,
+      payer,
+      provider.wallet.publicKey,
+      null,
+      6,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const [config2022] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seed2022.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .initialize(
+        seed2022,
+        fee,
+        fixedFee,
+        fixedFee,
+        protocolFee,
+        feeTimelock,
+        constantProduct,
+        new anchor.BN(0),
+        feeOnInput,
+        noReferralFee,
+        provider.wallet.publicKey
+      )
+      .accounts({
+        mintX: mintX2022,
+        mintY: mintY2022,
+        tokenProgramX: TOKEN_2022_PROGRAM_ID,
+        tokenProgramY: TOKEN_2022_PROGRAM_ID,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .rpc();
+
+    // The LP mint is a Token-2022 mint too, and gets its metadata all the same
+    const [mintLp2022] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp"), config2022.toBuffer()],
+      program.programId
+    );
+    assert.deepEqual(await lpMetadataOf(mintLp2022), {
+      name: lpNameOf(mintX2022, mintY2022),
+      symbol: lpSymbol,
+    });
+
+    const userX2022 = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintX2022,
+        provider.wallet.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+    const userY2022 = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer,
+        mintY2022,
+        provider.wallet.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+
+    for (const [mint, ata] of [
+      [mintX2022, userX2022],
+      [mintY2022, userY2022],
+    ]) {
+      await mintTo(
+        provider.connection,
+        payer,
+        mint,
+        ata,
+        payer,
+        2_000_000_000,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+    }
+
+    await program.methods
+      .deposit(
+        lpAmount,
+        new anchor.BN(initialDepositX),
+        new anchor.BN(initialDepositY),
+        expiration
+      )
+      .accountsPartial({
+        mintX: mintX2022,
+        mintY: mintY2022,
+        config: config2022,
+        tokenProgramX: TOKEN_2022_PROGRAM_ID,
+        tokenProgramY: TOKEN_2022_PROGRAM_ID,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .rpc();
+
+    const preUserY = await getAccount(
+      provider.connection,
+      userY2022,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await program.methods
+      .swap(true, new anchor.BN(100_000_000), new anchor.BN(1), expiration)
+      .accountsPartial({
+        mintX: mintX2022,
+        mintY: mintY2022,
+        config: config2022,
+        tokenProgramX: TOKEN_2022_PROGRAM_ID,
+        tokenProgramY: TOKEN_2022_PROGRAM_ID,
+      })
+      .rpc();
+
+    const postUserY = await getAccount(
+      provider.connection,
+      userY2022,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    assert.isTrue(Number(postUserY.amount) > Number(preUserY.amount));
   });
 });